@@ -1,26 +1,26 @@
-use std::{collections::HashMap, path::PathBuf, time::Duration};
+use std::{collections::HashMap, path::PathBuf};
 
 use anyhow::{anyhow, Context};
 use indexmap::IndexMap;
-use tauri::AppHandle;
+use tauri::{AppHandle, Manager};
 use tauri_plugin_opener::OpenerExt;
-use tauri_specta::Event;
-use tokio::time::sleep;
 use walkdir::WalkDir;
 
 use crate::{
     config::Config,
     errors::{CommandError, CommandResult},
-    events::UpdateDownloadedComicsEvent,
-    export,
+    export::{self, Archive},
     extensions::{AnyhowErrorToStringChain, AppHandleExt, WalkDirEntryExt},
     logger,
     responses::{
         ChapterInGetChaptersRespData, GetChapterRespData, LoginRespData, UserProfileRespData,
     },
     types::{
-        ChapterInfo, Comic, ComicInFavorite, ComicInSearch, GetFavoriteOrdering, GetFavoriteResult,
-        SearchResult,
+        ChapterDirCreateFailure, ChapterDirWithoutMetadata, ChapterInfo, Comic, ComicInFavorite,
+        ComicInSearch, ConnectionStatus, Diagnostics, DownloadFormat, DownloadSizeEstimate,
+        DownloadedComicStats, DownloadedComicSummary, FavoriteItem, GetFavoriteOrdering,
+        GetFavoriteResult, Group, GroupDownloadCount, IncompleteChapter, MigratedChapter,
+        OverallProgress, SearchResult,
     },
     utils,
 };
@@ -43,6 +43,10 @@ pub fn get_config(app: AppHandle) -> Config {
 #[specta::specta]
 #[allow(clippy::needless_pass_by_value)]
 pub fn save_config(app: AppHandle, config: Config) -> CommandResult<()> {
+    config
+        .validate()
+        .map_err(|err| CommandError::from("保存配置失败", err))?;
+
     let config_state = app.get_config();
 
     let enable_file_logger = config.enable_file_logger;
@@ -50,6 +54,7 @@ pub fn save_config(app: AppHandle, config: Config) -> CommandResult<()> {
         .read()
         .enable_file_logger
         .ne(&enable_file_logger);
+    let network_config_changed = config_state.read().network_config_differs(&config);
 
     {
         // 包裹在大括号中，以便自动释放写锁
@@ -71,6 +76,11 @@ pub fn save_config(app: AppHandle, config: Config) -> CommandResult<()> {
         }
     }
 
+    if network_config_changed {
+        app.get_copy_client().reload_clients();
+        tracing::debug!("重新创建网络请求客户端成功");
+    }
+
     Ok(())
 }
 
@@ -117,6 +127,19 @@ pub async fn get_user_profile(app: AppHandle) -> CommandResult<UserProfileRespDa
     Ok(user_profile_resp_data)
 }
 
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn check_connection(app: AppHandle) -> CommandResult<ConnectionStatus> {
+    let copy_client = app.get_copy_client();
+
+    let connection_status = copy_client
+        .check_connection()
+        .await
+        .map_err(|err| CommandError::from("测试连接失败", err))?;
+
+    Ok(connection_status)
+}
+
 #[tauri::command(async)]
 #[specta::specta]
 pub async fn search(app: AppHandle, keyword: String, page_num: i64) -> CommandResult<SearchResult> {
@@ -145,6 +168,22 @@ pub async fn get_comic(app: AppHandle, comic_path_word: &str) -> CommandResult<C
     Ok(comic)
 }
 
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn get_comic_groups(app: AppHandle, comic_path_word: &str) -> CommandResult<Vec<Group>> {
+    let copy_client = app.get_copy_client();
+
+    let comic_resp_data = copy_client
+        .get_comic(comic_path_word)
+        .await
+        .map_err(|err| {
+            CommandError::from(&format!("获取路径为`{comic_path_word}`的漫画失败"), err)
+        })?;
+    let groups = Group::sorted_by_count(comic_resp_data.groups);
+
+    Ok(groups)
+}
+
 #[tauri::command(async)]
 #[specta::specta]
 pub async fn get_group_chapters(
@@ -179,6 +218,22 @@ pub async fn get_chapter(
     Ok(get_chapter_resp_data)
 }
 
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn estimate_download_size(
+    app: AppHandle,
+    comic: Comic,
+    chapter_uuids: Vec<String>,
+    sample_img_count: usize,
+) -> CommandResult<DownloadSizeEstimate> {
+    let comic_title = &comic.comic.name;
+    let estimate = DownloadSizeEstimate::estimate(&app, &comic, &chapter_uuids, sample_img_count)
+        .await
+        .map_err(|err| CommandError::from(&format!("`{comic_title}`估算下载大小失败"), err))?;
+
+    Ok(estimate)
+}
+
 #[tauri::command(async)]
 #[specta::specta]
 pub async fn get_favorite(
@@ -199,6 +254,24 @@ pub async fn get_favorite(
     Ok(get_favorite_result)
 }
 
+/// 获取收藏夹中的所有漫画，用于批量下载收藏
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn get_all_favorites(app: AppHandle) -> CommandResult<Vec<FavoriteItem>> {
+    let favorites = utils::get_all_favorites(&app)
+        .await
+        .map_err(|err| CommandError::from("获取收藏夹中的所有漫画失败", err))?;
+
+    Ok(favorites)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn get_group_download_counts(comic: Comic) -> Vec<GroupDownloadCount> {
+    comic.get_group_download_counts()
+}
+
 #[tauri::command(async)]
 #[specta::specta]
 #[allow(clippy::needless_pass_by_value)]
@@ -211,6 +284,319 @@ pub fn save_metadata(comic: Comic) -> CommandResult<()> {
     Ok(())
 }
 
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn set_chapter_order_override(
+    mut comic: Comic,
+    chapter_uuid: String,
+    order: Option<f64>,
+) -> CommandResult<Comic> {
+    let comic_title = comic.comic.name.clone();
+    // order为None时表示取消手动指定的order
+    match order {
+        Some(order) => comic.order_overrides.insert(chapter_uuid, order),
+        None => comic.order_overrides.remove(&chapter_uuid),
+    };
+    comic
+        .save_metadata()
+        .map_err(|err| CommandError::from(&format!("`{comic_title}`保存元数据失败"), err))?;
+
+    Ok(comic)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn set_preferred_export_archive(
+    mut comic: Comic,
+    archive: Option<Archive>,
+) -> CommandResult<Comic> {
+    let comic_title = comic.comic.name.clone();
+    // archive为None时表示使用`config.default_export_archive`
+    comic.preferred_export_archive = archive;
+    comic
+        .save_metadata()
+        .map_err(|err| CommandError::from(&format!("`{comic_title}`保存元数据失败"), err))?;
+
+    Ok(comic)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn search_downloaded_comics(
+    app: AppHandle,
+    query: String,
+) -> CommandResult<Vec<DownloadedComicSummary>> {
+    let summaries = DownloadedComicSummary::search(&app, &query)
+        .map_err(|err| CommandError::from("搜索已下载的漫画失败", err))?;
+
+    Ok(summaries)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn list_downloaded_comics(app: AppHandle) -> CommandResult<Vec<DownloadedComicStats>> {
+    let stats_list = utils::list_downloaded_comics(&app)
+        .map_err(|err| CommandError::from("获取已下载漫画列表失败", err))?;
+
+    Ok(stats_list)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn migrate_old_version_chapters(
+    app: AppHandle,
+    comic: Comic,
+    rename: bool,
+) -> CommandResult<Vec<MigratedChapter>> {
+    let comic_title = &comic.comic.name;
+    let migrated_chapters = comic
+        .migrate_old_version_chapters(&app, rename)
+        .map_err(|err| CommandError::from(&format!("`{comic_title}`迁移旧版本章节失败"), err))?;
+
+    Ok(migrated_chapters)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn rename_comic_download_dir(app: AppHandle, mut comic: Comic) -> CommandResult<Comic> {
+    let comic_title = comic.comic.name.clone();
+    comic
+        .rename_comic_download_dir(&app)
+        .map_err(|err| CommandError::from(&format!("`{comic_title}`重命名下载目录失败"), err))?;
+    tracing::debug!("`{comic_title}`重命名下载目录成功");
+
+    Ok(comic)
+}
+
+/// 为手动整理过的目录按标题和序号匹配章节，并写入缺失的章节元数据
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn repair_comic_metadata(mut comic: Comic) -> CommandResult<Comic> {
+    let comic_title = comic.comic.name.clone();
+    comic
+        .repair_metadata()
+        .map_err(|err| CommandError::from(&format!("`{comic_title}`修复章节元数据失败"), err))?;
+    tracing::debug!("`{comic_title}`修复章节元数据成功");
+
+    Ok(comic)
+}
+
+/// 删除已下载的章节目录
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn delete_chapter_download(app: AppHandle, chapter_info: ChapterInfo) -> CommandResult<()> {
+    let comic_title = &chapter_info.comic_title;
+    let chapter_title = &chapter_info.chapter_title;
+    chapter_info.delete_download(&app).map_err(|err| {
+        let err_title = format!("`{comic_title} - {chapter_title}`删除已下载的章节失败");
+        CommandError::from(&err_title, err)
+    })?;
+    tracing::debug!("`{comic_title} - {chapter_title}`删除已下载的章节成功");
+    Ok(())
+}
+
+/// 删除漫画中已下载的章节，返回更新了`is_downloaded`字段的漫画
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn delete_comic_chapter_download(
+    app: AppHandle,
+    mut comic: Comic,
+    chapter_uuid: String,
+) -> CommandResult<Comic> {
+    let comic_title = comic.comic.name.clone();
+    comic
+        .delete_chapter_download(&app, &chapter_uuid)
+        .map_err(|err| {
+            let err_title = format!("`{comic_title}`删除章节ID为`{chapter_uuid}`的已下载章节失败");
+            CommandError::from(&err_title, err)
+        })?;
+    tracing::debug!("`{comic_title}`删除章节ID为`{chapter_uuid}`的已下载章节成功");
+    Ok(comic)
+}
+
+/// 取消漫画所有未结束的下载任务，然后删除整个漫画下载目录
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn delete_comic_download(app: AppHandle, comic: Comic) -> CommandResult<()> {
+    let comic_title = &comic.comic.name;
+    comic
+        .delete_download(&app)
+        .await
+        .map_err(|err| CommandError::from(&format!("`{comic_title}`删除已下载的漫画失败"), err))?;
+    tracing::debug!("`{comic_title}`删除已下载的漫画成功");
+    Ok(())
+}
+
+/// 重新获取漫画信息，返回已下载但远端已经不存在的章节
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn get_orphaned_chapters(
+    app: AppHandle,
+    comic: Comic,
+) -> CommandResult<Vec<ChapterInfo>> {
+    let comic_title = &comic.comic.name;
+
+    let fresh_comic = utils::get_comic(app, &comic.comic.path_word)
+        .await
+        .map_err(|err| {
+            CommandError::from(&format!("`{comic_title}`获取远端已删除的章节失败"), err)
+        })?;
+
+    let orphaned_chapters = comic
+        .get_orphaned_chapters(&fresh_comic)
+        .into_iter()
+        .cloned()
+        .collect();
+
+    Ok(orphaned_chapters)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn create_comic_download_dirs(
+    app: AppHandle,
+    comic: Comic,
+) -> CommandResult<Vec<ChapterDirCreateFailure>> {
+    let comic_title = &comic.comic.name;
+    let failures = comic
+        .create_download_dirs(&app)
+        .map_err(|err| CommandError::from(&format!("`{comic_title}`创建下载目录失败"), err))?;
+    for failure in &failures {
+        let err_title = format!(
+            "`{comic_title} - {}`创建章节目录失败",
+            failure.chapter_title
+        );
+        tracing::error!(err_title, message = failure.err_message);
+    }
+
+    Ok(failures)
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn find_chapter_dirs_without_metadata(
+    app: AppHandle,
+    comic: Comic,
+) -> CommandResult<Vec<ChapterDirWithoutMetadata>> {
+    let comic_title = &comic.comic.name;
+    let chapter_dirs = comic
+        .find_chapter_dirs_without_metadata(&app)
+        .map_err(|err| CommandError::from(&format!("`{comic_title}`检查章节元数据失败"), err))?;
+
+    Ok(chapter_dirs)
+}
+
+/// 找出`comic`中图片不完整的已下载章节，并为它们重新创建下载任务
+///
+/// 返回成功创建了下载任务的章节
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn redownload_incomplete_chapters(
+    app: AppHandle,
+    comic: Comic,
+) -> CommandResult<Vec<IncompleteChapter>> {
+    let comic_title = comic.comic.name.clone();
+    let incomplete_chapters = comic
+        .find_incomplete_chapters()
+        .map_err(|err| CommandError::from(&format!("`{comic_title}`检查章节完整性失败"), err))?;
+
+    let download_manager = app.get_download_manager();
+    let mut repaired_chapters = Vec::new();
+    for incomplete_chapter in incomplete_chapters {
+        let chapter_uuid = &incomplete_chapter.chapter_uuid;
+        if let Err(err) = download_manager.create_download_task(comic.clone(), chapter_uuid) {
+            let err_title = format!(
+                "`{comic_title} - {}`重新创建下载任务失败",
+                incomplete_chapter.chapter_title
+            );
+            let string_chain = err.to_string_chain();
+            tracing::error!(err_title, message = string_chain);
+            continue;
+        }
+        repaired_chapters.push(incomplete_chapter);
+    }
+    tracing::debug!("为`{comic_title}`不完整的章节重新创建下载任务成功");
+
+    Ok(repaired_chapters)
+}
+
+/// 重新获取漫画信息，为已存在的章节目录重新生成`章节元数据.json`，不会重新下载图片
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn regenerate_chapter_metadata(
+    app: AppHandle,
+    comic: Comic,
+    chapter_uuid: String,
+) -> CommandResult<()> {
+    let comic_title = &comic.comic.name;
+    let err_title = format!("`{comic_title}`重新生成章节ID为`{chapter_uuid}`的章节元数据失败");
+
+    let mut fresh_comic = utils::get_comic(app.clone(), &comic.comic.path_word)
+        .await
+        .map_err(|err| CommandError::from(&err_title, err))?;
+    fresh_comic
+        .update_download_dir_fields_by_fmt(&app)
+        .map_err(|err| CommandError::from(&err_title, err))?;
+
+    let mut chapter_info = fresh_comic
+        .comic
+        .groups
+        .values()
+        .flatten()
+        .find(|chapter_info| chapter_info.chapter_uuid == chapter_uuid)
+        .cloned()
+        .ok_or_else(|| {
+            let err = anyhow!("最新的章节列表中没有章节ID为`{chapter_uuid}`的章节");
+            CommandError::from(&err_title, err)
+        })?;
+    // 优先使用本地已知的章节目录，元数据丢失时本地不知道章节目录，只能按照fmt计算
+    let local_chapter_download_dir = comic
+        .comic
+        .groups
+        .values()
+        .flatten()
+        .find(|chapter_info| chapter_info.chapter_uuid == chapter_uuid)
+        .and_then(|chapter_info| chapter_info.chapter_download_dir.clone());
+    // 漫画目录可能被改过名，所以按照fmt计算时只使用相对于漫画目录的路径
+    let fmt_chapter_download_dir = match &comic.comic_download_dir {
+        Some(comic_download_dir) => chapter_info
+            .get_chapter_relative_dir(&fresh_comic)
+            .ok()
+            .map(|relative_dir| comic_download_dir.join(relative_dir)),
+        None => chapter_info.chapter_download_dir.clone(),
+    };
+    let chapter_download_dir = local_chapter_download_dir
+        .or(fmt_chapter_download_dir)
+        .ok_or_else(|| {
+            let err = anyhow!("无法确定章节ID为`{chapter_uuid}`的章节目录");
+            CommandError::from(&err_title, err)
+        })?;
+    if !chapter_download_dir.is_dir() {
+        let err = anyhow!("章节目录`{}`不存在", chapter_download_dir.display());
+        return Err(CommandError::from(&err_title, err));
+    }
+
+    chapter_info.chapter_download_dir = Some(chapter_download_dir);
+    chapter_info
+        .save_metadata()
+        .map_err(|err| CommandError::from(&err_title, err))?;
+    tracing::debug!("`{comic_title}`重新生成章节ID为`{chapter_uuid}`的章节元数据成功");
+
+    Ok(())
+}
+
 #[tauri::command(async)]
 #[specta::specta]
 #[allow(clippy::needless_pass_by_value)]
@@ -353,6 +739,85 @@ pub fn export_pdf(app: AppHandle, comic: Comic) -> CommandResult<()> {
     Ok(())
 }
 
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn export_comics(app: AppHandle, comics: Vec<Comic>, archive: Archive) -> CommandResult<()> {
+    export::batch(&app, &comics, Some(archive))
+        .map_err(|err| CommandError::from("批量导出漫画失败", err))?;
+    Ok(())
+}
+
+/// 按照漫画偏好的导出格式导出，没有设置时使用`config.default_export_archive`
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn export_comic(app: AppHandle, comic: Comic) -> CommandResult<()> {
+    let comic_title = comic.comic.name.clone();
+    export::comic(&app, &comic)
+        .context(format!("漫画`{comic_title}`导出失败"))
+        .map_err(|err| CommandError::from("漫画导出失败", err))?;
+    Ok(())
+}
+
+/// 根据已下载的元数据和图片重新导出漫画，不需要重新下载
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn reexport_comic(
+    app: AppHandle,
+    comic_path_word: String,
+    archive: Archive,
+) -> CommandResult<()> {
+    export::reexport(&app, &comic_path_word, archive)
+        .context(format!("路径词为`{comic_path_word}`的漫画重新导出失败"))
+        .map_err(|err| CommandError::from("漫画重新导出失败", err))?;
+    Ok(())
+}
+
+/// 批量导出多部漫画，每部漫画都按照其偏好的导出格式导出
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn export_comics_by_preference(app: AppHandle, comics: Vec<Comic>) -> CommandResult<()> {
+    export::batch(&app, &comics, None)
+        .map_err(|err| CommandError::from("批量导出漫画失败", err))?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn export_combined_pdf(
+    app: AppHandle,
+    comics: Vec<Comic>,
+    title: String,
+) -> CommandResult<PathBuf> {
+    let pdf_path = export::combined_pdf(&app, &comics, &title)
+        .map_err(|err| CommandError::from(&format!("导出合集`{title}`失败"), err))?;
+    tracing::debug!("导出合集`{title}`成功");
+    Ok(pdf_path)
+}
+
+/// 将漫画所有章节的目录导出为json，保存在漫画的导出目录中，返回文件路径
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+pub fn export_comic_catalog(app: AppHandle, comic: Comic) -> CommandResult<PathBuf> {
+    let comic_title = &comic.comic.name;
+    let export_dir = app.get_config().read().export_dir.clone();
+    let catalog_path = comic
+        .get_comic_export_dir(&app, &export_dir)
+        .map(|comic_export_dir| comic_export_dir.join("catalog.json"))
+        .and_then(|catalog_path| {
+            comic.export_catalog(&catalog_path)?;
+            Ok(catalog_path)
+        })
+        .map_err(|err| CommandError::from(&format!("`{comic_title}`导出章节目录失败"), err))?;
+    tracing::debug!("`{comic_title}`导出章节目录成功");
+    Ok(catalog_path)
+}
+
 #[allow(clippy::needless_pass_by_value)]
 #[tauri::command(async)]
 #[specta::specta]
@@ -374,6 +839,63 @@ pub fn create_download_task(
     Ok(())
 }
 
+/// 为`order`最大的`n`个章节创建下载任务，已下载的章节会被跳过，返回创建了下载任务的章节ID
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+pub fn download_newest_chapters(
+    app: AppHandle,
+    comic: Comic,
+    n: usize,
+) -> CommandResult<Vec<String>> {
+    let download_manager = app.get_download_manager();
+    let comic_title = comic.comic.name.clone();
+
+    let chapter_uuids: Vec<String> = comic
+        .get_newest_chapters(n)
+        .into_iter()
+        .filter(|chapter_info| chapter_info.is_downloaded != Some(true))
+        .map(|chapter_info| chapter_info.chapter_uuid.clone())
+        .collect();
+
+    // 某个章节的下载任务创建失败时记录错误并跳过，不影响其他章节
+    let mut scheduled_chapter_uuids = Vec::new();
+    for chapter_uuid in chapter_uuids {
+        if let Err(err) = download_manager.create_download_task(comic.clone(), &chapter_uuid) {
+            let err_title = format!("`{comic_title}`的章节ID为`{chapter_uuid}`的下载任务创建失败");
+            let string_chain = err.to_string_chain();
+            tracing::error!(err_title, message = string_chain);
+            continue;
+        }
+        scheduled_chapter_uuids.push(chapter_uuid);
+    }
+    tracing::debug!("为`{comic_title}`最新的{n}个章节创建下载任务成功");
+    Ok(scheduled_chapter_uuids)
+}
+
+/// 为分组中`order`最大的`n`个未下载章节创建下载任务，返回创建了下载任务的章节ID
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+pub fn download_latest_chapters_in_group(
+    app: AppHandle,
+    comic: Comic,
+    group_path_word: String,
+    n: usize,
+) -> CommandResult<Vec<String>> {
+    let download_manager = app.get_download_manager();
+    let comic_title = comic.comic.name.clone();
+
+    let chapter_uuids = download_manager
+        .create_tasks_for_latest(comic, &group_path_word, n)
+        .map_err(|err| {
+            let err_title = format!("为`{comic_title}`最新的{n}个章节创建下载任务失败");
+            CommandError::from(&err_title, err)
+        })?;
+    tracing::debug!("为`{comic_title}`分组`{group_path_word}`最新的{n}个章节创建下载任务成功");
+    Ok(chapter_uuids)
+}
+
 #[allow(clippy::needless_pass_by_value)]
 #[tauri::command(async)]
 #[specta::specta]
@@ -419,120 +941,100 @@ pub fn cancel_download_task(app: AppHandle, chapter_uuid: String) -> CommandResu
     Ok(())
 }
 
-#[allow(clippy::cast_possible_wrap)]
+#[allow(clippy::needless_pass_by_value)]
 #[tauri::command(async)]
 #[specta::specta]
-pub async fn update_downloaded_comics(app: AppHandle) -> CommandResult<()> {
-    let config = app.get_config();
-    let download_manager = app.get_download_manager();
-
-    // 从下载目录中获取已下载的漫画
-    let downloaded_comics = get_downloaded_comics(app.clone());
-
-    let total = downloaded_comics.len() as i64;
-    let interval_sec = config.read().update_downloaded_comics_interval_sec;
-    let _ = UpdateDownloadedComicsEvent::GetComicStart { total }.emit(&app);
-
-    for (i, downloaded_comic) in downloaded_comics.into_iter().enumerate() {
-        let comic_title = &downloaded_comic.comic.name;
-        let comic_path_word = &downloaded_comic.comic.path_word;
-        let current = (i + 1) as i64;
-        let _ = UpdateDownloadedComicsEvent::GetComicProgress { current, total }.emit(&app);
-
-        let comic = match utils::get_comic(app.clone(), comic_path_word)
-            .await
-            .context(format!("获取路径为`{comic_path_word}`的漫画失败"))
-        {
-            Ok(comic) => comic,
-            Err(err) => {
-                let err_title = format!("更新库存过程中，获取漫画`{comic_title}`失败，已跳过");
-                let err = err.context("可能是频率太高，请手动去`配置`里调整`更新库存时，每处理完一个已下载的漫画后休息`");
-                let string_chain = err.to_string_chain();
-                tracing::error!(err_title, message = string_chain);
-                sleep(Duration::from_secs(interval_sec)).await;
-                continue;
-            }
-        };
-
-        let has_downloaded_group = comic.comic.groups.iter().any(|(_, chapter_infos)| {
-            chapter_infos
-                .iter()
-                .any(|chapter_info| chapter_info.is_downloaded.unwrap_or(false))
-        });
-
-        if !has_downloaded_group {
-            sleep(Duration::from_secs(interval_sec)).await;
-            continue;
-        }
-
-        let downloaded_groups: HashMap<&String, &Vec<ChapterInfo>> = comic
-            .comic
-            .groups
-            .iter()
-            .filter_map(|(group_path_word, chapter_infos)| {
-                chapter_infos
-                    .iter()
-                    .any(|chapter_info| chapter_info.is_downloaded.unwrap_or(false))
-                    .then_some((group_path_word, chapter_infos))
-            })
-            .collect();
-
-        if downloaded_groups.is_empty() {
-            sleep(Duration::from_secs(interval_sec)).await;
-            continue;
-        }
-
-        // 获取downloaded_groups中所有未下载的章节
-        let chapter_infos: Vec<&ChapterInfo> = downloaded_groups
-            .values()
-            .flat_map(|chapter_infos| {
-                chapter_infos
-                    .iter()
-                    .filter(|chapter_info| !chapter_info.is_downloaded.unwrap_or(false))
-            })
-            .collect();
-
-        if chapter_infos.is_empty() {
-            sleep(Duration::from_secs(interval_sec)).await;
-            continue;
-        }
-
-        let _ = UpdateDownloadedComicsEvent::CreateDownloadTasksStart {
-            comic_path_word: comic_path_word.clone(),
-            comic_title: comic_title.clone(),
-            current: 0,
-            total: chapter_infos.len() as i64,
-        }
-        .emit(&app);
+pub fn get_risk_control_count(app: AppHandle) -> u32 {
+    app.get_download_manager().get_risk_control_count()
+}
 
-        for (i, chapter_info) in chapter_infos.into_iter().enumerate() {
-            let chapter_uuid = &chapter_info.chapter_uuid;
-            let current = (i + 1) as i64;
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+pub fn clear_finished_download_tasks(app: AppHandle) -> Vec<String> {
+    let chapter_uuids = app.get_download_manager().clear_finished_tasks();
+    tracing::debug!("清理了{}个已结束的下载任务", chapter_uuids.len());
+    chapter_uuids
+}
 
-            let _ = download_manager.create_download_task(comic.clone(), chapter_uuid);
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+pub fn get_overall_download_progress(app: AppHandle) -> OverallProgress {
+    app.get_download_manager().overall_progress()
+}
 
-            let _ = UpdateDownloadedComicsEvent::CreateDownloadTaskProgress {
-                comic_path_word: comic_path_word.clone(),
-                current,
-            }
-            .emit(&app);
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+pub fn set_download_paused_global(app: AppHandle, paused: bool) {
+    app.get_download_manager().set_paused_global(paused);
+    tracing::debug!("全局{}下载成功", if paused { "暂停" } else { "恢复" });
+}
 
-            sleep(Duration::from_millis(100)).await;
-        }
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+pub fn reset_risk_control_count(app: AppHandle) {
+    app.get_download_manager().reset_risk_control_count();
+    tracing::debug!("重置风控次数成功");
+}
 
-        let _ = UpdateDownloadedComicsEvent::CreateDownloadTasksEnd {
-            comic_path_word: comic_path_word.clone(),
-        }
-        .emit(&app);
+#[tauri::command(async)]
+#[specta::specta]
+#[allow(clippy::needless_pass_by_value)]
+pub fn transcode_downloaded_comic(
+    app: AppHandle,
+    comic: Comic,
+    target_format: DownloadFormat,
+) -> CommandResult<()> {
+    let comic_title = &comic.comic.name;
+    app.get_download_manager()
+        .transcode(&comic, target_format)
+        .map_err(|err| CommandError::from(&format!("`{comic_title}`转码失败"), err))?;
+    Ok(())
+}
 
-        sleep(Duration::from_secs(interval_sec)).await;
-    }
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+pub fn cancel_transcode(app: AppHandle, comic_uuid: String) {
+    app.get_download_manager().cancel_transcode(&comic_uuid);
+    tracing::debug!("取消转码`{comic_uuid}`");
+}
 
-    let _ = UpdateDownloadedComicsEvent::GetComicEnd.emit(&app);
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn update_downloaded_comics(app: AppHandle) -> CommandResult<()> {
+    let download_manager = app.get_download_manager();
+    download_manager.update_downloaded_comics().await;
+    Ok(())
+}
 
+/// 为收藏夹中所有漫画的章节创建下载任务，`only_not_downloaded`为`true`时只下载未下载的章节
+#[tauri::command(async)]
+#[specta::specta]
+pub async fn download_all_favorites(
+    app: AppHandle,
+    only_not_downloaded: bool,
+) -> CommandResult<()> {
+    let download_manager = app.get_download_manager();
+    download_manager
+        .download_all_favorites(only_not_downloaded)
+        .await
+        .map_err(|err| CommandError::from("批量下载收藏失败", err))?;
     Ok(())
 }
 
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+pub fn get_diagnostics(app: AppHandle) -> CommandResult<Diagnostics> {
+    let diagnostics =
+        Diagnostics::new(&app).map_err(|err| CommandError::from("获取诊断信息失败", err))?;
+    Ok(diagnostics)
+}
+
 #[allow(clippy::needless_pass_by_value)]
 #[tauri::command(async)]
 #[specta::specta]
@@ -551,17 +1053,134 @@ pub fn get_logs_dir_size(app: AppHandle) -> CommandResult<u64> {
     Ok(logs_dir_size)
 }
 
+/// 获取当前日志文件的最后`line_count`行，新的日志会通过`LogEvent`实时发送到前端
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+pub fn get_log_tail(app: AppHandle, line_count: usize) -> CommandResult<Vec<String>> {
+    let lines = logger::tail_log_file(&app, line_count)
+        .map_err(|err| CommandError::from("获取日志文件的最后几行失败", err))?;
+    Ok(lines)
+}
+
 #[allow(clippy::needless_pass_by_value)]
 #[tauri::command(async)]
 #[specta::specta]
-pub fn show_path_in_file_manager(app: AppHandle, path: &str) -> CommandResult<()> {
+pub fn reveal_path(app: AppHandle, path: PathBuf) -> CommandResult<()> {
+    let (download_dir, export_dirs) = {
+        let config = app.get_config();
+        let config = config.read();
+        (config.download_dir.clone(), config.get_export_dirs())
+    };
+    let logs_dir =
+        logger::logs_dir(&app).map_err(|err| CommandError::from("在文件管理器中打开失败", err))?;
+    let config_path = app
+        .path()
+        .app_data_dir()
+        .context("获取app_data_dir目录失败")
+        .map_err(|err| CommandError::from("在文件管理器中打开失败", err))?
+        .join("config.json");
+    let mut allowed_dirs = vec![
+        download_dir.as_path(),
+        logs_dir.as_path(),
+        config_path.as_path(),
+    ];
+    allowed_dirs.extend(export_dirs.iter().map(PathBuf::as_path));
+    // 只允许打开下载目录、导出目录、日志目录中的路径和配置文件，避免被当成打开任意路径的入口
+    utils::ensure_path_in_dirs(&path, &allowed_dirs)
+        .map_err(|err| CommandError::from("在文件管理器中打开失败", err))?;
+
     app.opener()
-        .reveal_item_in_dir(path)
-        .context(format!("在文件管理器中打开`{path}`失败"))
+        .reveal_item_in_dir(&path)
+        .context(format!("在文件管理器中打开`{}`失败", path.display()))
         .map_err(|err| CommandError::from("在文件管理器中打开失败", err))?;
     Ok(())
 }
 
+/// 在文件管理器中打开漫画的下载目录，返回该漫画的所有下载目录
+///
+/// 同一个漫画有多个下载目录时(如修改过`comic_dir_fmt`)，只打开第一个
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+pub fn reveal_comic_dir(app: AppHandle, comic_path_word: String) -> CommandResult<Vec<PathBuf>> {
+    let path_word_to_dir_map = utils::create_path_word_to_dir_map(&app)
+        .context("创建漫画路径词到下载目录映射失败")
+        .map_err(|err| CommandError::from("打开漫画下载目录失败", err))?;
+    let comic_download_dirs = path_word_to_dir_map
+        .get(&comic_path_word)
+        .cloned()
+        .unwrap_or_default();
+    let Some(comic_download_dir) = comic_download_dirs.first() else {
+        let err = anyhow!("路径词为`{comic_path_word}`的漫画还没有下载");
+        return Err(CommandError::from("打开漫画下载目录失败", err));
+    };
+
+    app.opener()
+        .reveal_item_in_dir(comic_download_dir)
+        .context(format!(
+            "在文件管理器中打开`{}`失败",
+            comic_download_dir.display()
+        ))
+        .map_err(|err| CommandError::from("打开漫画下载目录失败", err))?;
+
+    Ok(comic_download_dirs)
+}
+
+/// 在文件管理器中打开章节的下载目录
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+pub fn reveal_chapter_dir(
+    app: AppHandle,
+    comic_path_word: String,
+    chapter_uuid: String,
+) -> CommandResult<()> {
+    let path_word_to_dir_map = utils::create_path_word_to_dir_map(&app)
+        .context("创建漫画路径词到下载目录映射失败")
+        .map_err(|err| CommandError::from("打开章节下载目录失败", err))?;
+    let comic_download_dirs = path_word_to_dir_map
+        .get(&comic_path_word)
+        .cloned()
+        .unwrap_or_default();
+    if comic_download_dirs.is_empty() {
+        let err = anyhow!("路径词为`{comic_path_word}`的漫画还没有下载");
+        return Err(CommandError::from("打开章节下载目录失败", err));
+    }
+
+    let mut chapter_download_dir = None;
+    for comic_download_dir in comic_download_dirs {
+        let metadata_path = comic_download_dir.join("元数据.json");
+        let comic = Comic::from_metadata(&metadata_path)
+            .map_err(|err| CommandError::from("打开章节下载目录失败", err))?;
+        chapter_download_dir = comic
+            .comic
+            .groups
+            .values()
+            .flatten()
+            .find(|chapter_info| chapter_info.chapter_uuid == chapter_uuid)
+            .and_then(|chapter_info| chapter_info.chapter_download_dir.clone())
+            .filter(|dir| dir.exists());
+        if chapter_download_dir.is_some() {
+            break;
+        }
+    }
+    let Some(chapter_download_dir) = chapter_download_dir else {
+        let err = anyhow!("章节ID为`{chapter_uuid}`的章节还没有下载");
+        return Err(CommandError::from("打开章节下载目录失败", err));
+    };
+
+    app.opener()
+        .reveal_item_in_dir(&chapter_download_dir)
+        .context(format!(
+            "在文件管理器中打开`{}`失败",
+            chapter_download_dir.display()
+        ))
+        .map_err(|err| CommandError::from("打开章节下载目录失败", err))?;
+
+    Ok(())
+}
+
 #[allow(clippy::needless_pass_by_value)]
 #[tauri::command(async)]
 #[specta::specta]
@@ -617,8 +1236,34 @@ pub fn get_synced_comic_in_search(
             let err_title = format!("漫画`{comic_title}`同步ComicInSearch的字段失败");
             CommandError::from(&err_title, err)
         })?;
+    let compute_download_status = app.get_config().read().compute_search_download_status;
 
-    comic.update_fields(&path_word_to_dir_map);
+    comic.update_fields(&path_word_to_dir_map, compute_download_status);
 
     Ok(comic)
 }
+
+/// 返回`path_words`中每个漫画当前是否已下载，用于刷新前端已获取的搜索结果和收藏列表
+#[allow(clippy::needless_pass_by_value)]
+#[tauri::command(async)]
+#[specta::specta]
+pub fn get_downloaded_status(
+    app: AppHandle,
+    path_words: Vec<String>,
+) -> CommandResult<HashMap<String, bool>> {
+    let path_word_to_dir_map = utils::create_path_word_to_dir_map(&app)
+        .context("创建漫画路径词到下载目录映射失败")
+        .map_err(|err| CommandError::from("获取漫画下载状态失败", err))?;
+
+    let downloaded_status = path_words
+        .into_iter()
+        .map(|path_word| {
+            let is_downloaded = path_word_to_dir_map
+                .get(&path_word)
+                .is_some_and(|dirs| !dirs.is_empty());
+            (path_word, is_downloaded)
+        })
+        .collect();
+
+    Ok(downloaded_status)
+}
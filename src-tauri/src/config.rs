@@ -1,12 +1,25 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
+use anyhow::{anyhow, Context};
+use regex_lite::Regex;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use tauri::{AppHandle, Manager};
 
-use crate::types::DownloadFormat;
+use crate::{export::Archive, extensions::AnyhowErrorToStringChain, types::DownloadFormat};
 
 const DEFAULT_API_DOMAIN: &str = "api.2025copy.com";
+pub const DEFAULT_USER_AGENT: &str = "COPY/3.0.0";
+/// 与下载目录中的分类结构一致，只有开启`separate_chapter_type`时才会创建`分组/{话|卷|番外}`目录
+pub const DEFAULT_EXPORT_DIR_FMT: &str = "{type_group_title}/{chapter_type}";
+/// 用页码命名，如`001.webp`
+pub const DEFAULT_IMAGE_NAME_FMT: &str = "{index:0>3}";
+/// 用CDN上的原始文件名命名，对应旧版本`img_naming`的`Original`
+const ORIGINAL_IMAGE_NAME_FMT: &str = "{original_name}";
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
 #[serde(rename_all = "camelCase")]
@@ -29,15 +42,60 @@ pub struct Config {
     pub enable_merge_pdf: bool,
     #[serde(default)]
     pub separate_chapter_type: bool,
+    pub export_format_subdir: bool,
+    pub export_filename_fmt: String,
+    pub cbz_export_dir: Option<PathBuf>,
+    pub pdf_export_dir: Option<PathBuf>,
+    pub export_concurrency: usize,
+    pub export_stamp_page_numbers: bool,
+    pub connect_timeout_sec: u64,
+    pub read_timeout_sec: u64,
+    pub verify_exports: bool,
+    pub skip_hd_upgrade_for_webtoon: bool,
+    pub proxy_rules: Vec<ProxyRule>,
+    pub create_progress_file: bool,
+    pub export_normalize_format: Option<DownloadFormat>,
+    pub chapter_unavailable_action: ChapterUnavailableAction,
+    pub save_unrecognized_img_as_bin: bool,
+    pub export_use_dedicated_cover: bool,
+    pub order_frac_width: usize,
+    pub order_decimal_separator: String,
+    pub chapter_title_strip_pattern: Option<String>,
+    pub default_export_archive: Archive,
+    pub incomplete_chapter_requeue_count: u32,
+    pub user_agent: String,
+    pub extra_headers: HashMap<String, String>,
+    pub filename_sanitize_mode: FilenameSanitizeMode,
+    pub auto_update_downloaded_comics_interval_min: u64,
+    pub image_name_fmt: String,
+    pub fallback_api_domains: Vec<String>,
+    pub log_max_size_mb: u64,
+    pub log_max_files: u32,
+    pub enable_download_event_log: bool,
+    pub disk_space_safety_margin_mb: u64,
+    pub keep_gif_as_is: bool,
+    pub comic_overrides: HashMap<String, ComicOverride>,
+    pub risk_control_cooldown_sec: u64,
+    pub auto_export_on_complete: Option<Archive>,
+    pub skip_unchanged_exports: bool,
+    pub export_dir_fmt: String,
+    pub convert_gif_to_animated_webp: bool,
+    pub compute_search_download_status: bool,
+    pub download_status_concurrency: usize,
+    pub download_cover: bool,
 }
 
 impl Config {
-    pub fn new(app: &AppHandle) -> anyhow::Result<Self> {
+    /// 加载配置，不合法的配置项会被重置(见`reset_invalid_fields`)
+    ///
+    /// 加载配置时日志还没有初始化，所以同时返回被重置的原因，由调用方在日志初始化后记录警告
+    pub fn new(app: &AppHandle) -> anyhow::Result<(Self, Vec<String>)> {
         let app_data_dir = app.path().app_data_dir()?;
         let config_path = app_data_dir.join("config.json");
 
-        let config = if config_path.exists() {
+        let mut config = if config_path.exists() {
             let config_string = std::fs::read_to_string(config_path)?;
+            let config_string = Config::migrate_img_naming(&config_string);
             match serde_json::from_str(&config_string) {
                 // 如果能够直接解析为Config，则直接返回
                 Ok(config) => config,
@@ -48,8 +106,25 @@ impl Config {
         } else {
             Config::default(&app_data_dir)
         };
+        let reset_reasons = config.reset_invalid_fields();
         config.save(app)?;
-        Ok(config)
+        Ok((config, reset_reasons))
+    }
+
+    /// 将不合法的配置项重置为默认值，返回每个被重置的配置项的原因
+    ///
+    /// 配置文件可能被手动修改过，或者是由没有检查这些配置项的旧版本保存的，没有经过`validate`
+    fn reset_invalid_fields(&mut self) -> Vec<String> {
+        let mut reset_reasons = Vec::new();
+        if let Err(err) = self.get_chapter_title_strip_regex() {
+            reset_reasons.push(err.to_string_chain());
+            self.chapter_title_strip_pattern = None;
+        }
+        if let Err(err) = self.check_order_decimal_separator() {
+            reset_reasons.push(err.to_string_chain());
+            self.order_decimal_separator = ".".to_string();
+        }
+        reset_reasons
     }
 
     pub fn save(&self, app: &AppHandle) -> anyhow::Result<()> {
@@ -60,6 +135,30 @@ impl Config {
         Ok(())
     }
 
+    /// 旧版本用`img_naming`(`Index`或`Original`)决定图片的命名方式，现在统一由`image_name_fmt`决定
+    ///
+    /// 配置文件中有`imgNaming`且`imageNameFmt`为空时，将其换算成对应的`image_name_fmt`
+    fn migrate_img_naming(config_string: &str) -> String {
+        let Ok(serde_json::Value::Object(mut map)) = serde_json::from_str(config_string) else {
+            return config_string.to_string();
+        };
+        let Some(img_naming) = map.remove("imgNaming") else {
+            return config_string.to_string();
+        };
+        let image_name_fmt_is_empty = map
+            .get("imageNameFmt")
+            .and_then(serde_json::Value::as_str)
+            .is_none_or(str::is_empty);
+        if image_name_fmt_is_empty {
+            let image_name_fmt = match img_naming.as_str() {
+                Some("Original") => ORIGINAL_IMAGE_NAME_FMT,
+                _ => DEFAULT_IMAGE_NAME_FMT,
+            };
+            map.insert("imageNameFmt".to_string(), image_name_fmt.into());
+        }
+        serde_json::Value::Object(map).to_string()
+    }
+
     fn merge_config(config_string: &str, app_data_dir: &Path) -> Config {
         let Ok(mut json_value) = serde_json::from_str::<serde_json::Value>(config_string) else {
             return Config::default(app_data_dir);
@@ -105,13 +204,172 @@ impl Config {
             create_pdf_concurrency: cpu_core_num,
             enable_merge_pdf: true,
             separate_chapter_type: false,
+            export_format_subdir: true,
+            export_filename_fmt: String::new(),
+            cbz_export_dir: None,
+            pdf_export_dir: None,
+            export_concurrency: cpu_core_num,
+            export_stamp_page_numbers: false,
+            connect_timeout_sec: 3,
+            read_timeout_sec: 5,
+            verify_exports: false,
+            skip_hd_upgrade_for_webtoon: true,
+            proxy_rules: Vec::new(),
+            create_progress_file: false,
+            export_normalize_format: None,
+            chapter_unavailable_action: ChapterUnavailableAction::default(),
+            save_unrecognized_img_as_bin: true,
+            export_use_dedicated_cover: false,
+            order_frac_width: 0,
+            order_decimal_separator: ".".to_string(),
+            chapter_title_strip_pattern: None,
+            default_export_archive: Archive::default(),
+            incomplete_chapter_requeue_count: 0,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            extra_headers: HashMap::new(),
+            filename_sanitize_mode: FilenameSanitizeMode::default(),
+            auto_update_downloaded_comics_interval_min: 0,
+            image_name_fmt: DEFAULT_IMAGE_NAME_FMT.to_string(),
+            fallback_api_domains: Vec::new(),
+            log_max_size_mb: 20,
+            log_max_files: 10,
+            enable_download_event_log: false,
+            disk_space_safety_margin_mb: 200,
+            keep_gif_as_is: true,
+            comic_overrides: HashMap::new(),
+            risk_control_cooldown_sec: 60,
+            auto_export_on_complete: None,
+            skip_unchanged_exports: false,
+            export_dir_fmt: DEFAULT_EXPORT_DIR_FMT.to_string(),
+            convert_gif_to_animated_webp: false,
+            compute_search_download_status: false,
+            download_status_concurrency: cpu_core_num,
+            download_cover: true,
         }
     }
 
+    /// 检查配置项是否合法
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.get_chapter_title_strip_regex()?;
+        self.check_order_decimal_separator()?;
+        // 不合法的header不会导致保存失败，只会被忽略并记录警告
+        self.get_custom_headers();
+        Ok(())
+    }
+
+    /// 超时、代理、User-Agent或额外的header与`other`不同时返回`true`，此时需要重新创建`CopyClient`的client
+    pub fn network_config_differs(&self, other: &Config) -> bool {
+        self.connect_timeout_sec != other.connect_timeout_sec
+            || self.read_timeout_sec != other.read_timeout_sec
+            || self.proxy_rules != other.proxy_rules
+            || self.user_agent != other.user_agent
+            || self.extra_headers != other.extra_headers
+    }
+
+    /// 将`user_agent`和`extra_headers`转为`HeaderMap`，不合法的header会被忽略并记录警告
+    ///
+    /// `user_agent`为空时使用默认的User-Agent
+    pub fn get_custom_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        let user_agent = if self.user_agent.is_empty() {
+            DEFAULT_USER_AGENT
+        } else {
+            &self.user_agent
+        };
+        match HeaderValue::from_str(user_agent) {
+            Ok(user_agent) => {
+                headers.insert(USER_AGENT, user_agent);
+            }
+            Err(err) => {
+                let err_msg = err.to_string();
+                tracing::warn!(user_agent, err_msg, "User-Agent不合法，已忽略");
+            }
+        }
+
+        for (name, value) in &self.extra_headers {
+            let header_name = match HeaderName::from_bytes(name.as_bytes()) {
+                Ok(header_name) => header_name,
+                Err(err) => {
+                    let err_msg = err.to_string();
+                    tracing::warn!(name, err_msg, "header名不合法，已忽略");
+                    continue;
+                }
+            };
+            let header_value = match HeaderValue::from_str(value) {
+                Ok(header_value) => header_value,
+                Err(err) => {
+                    let err_msg = err.to_string();
+                    tracing::warn!(name, value, err_msg, "header值不合法，已忽略");
+                    continue;
+                }
+            };
+            headers.insert(header_name, header_value);
+        }
+
+        headers
+    }
+
+    /// 编译`chapter_title_strip_pattern`，未设置或为空时返回`None`
+    pub fn get_chapter_title_strip_regex(&self) -> anyhow::Result<Option<Regex>> {
+        let Some(pattern) = self
+            .chapter_title_strip_pattern
+            .as_deref()
+            .filter(|pattern| !pattern.is_empty())
+        else {
+            return Ok(None);
+        };
+        let re = Regex::new(pattern).context(format!("章节标题清理正则表达式`{pattern}`不合法"))?;
+        Ok(Some(re))
+    }
+
+    /// `order_decimal_separator`会出现在章节目录名中，包含路径分隔符会让章节目录多出一层
+    fn check_order_decimal_separator(&self) -> anyhow::Result<()> {
+        let separator = &self.order_decimal_separator;
+        if separator.contains(['/', '\\']) {
+            return Err(anyhow!(
+                "小数点替换字符`{separator}`不能包含路径分隔符`/`或`\\`"
+            ));
+        }
+        Ok(())
+    }
+
     pub fn get_authorization(&self) -> String {
         format!("Token {}", self.token)
     }
 
+    /// 获取所有导出根目录，包括`export_dir`和各格式专用的导出目录
+    pub fn get_export_dirs(&self) -> Vec<PathBuf> {
+        let mut export_dirs = vec![self.export_dir.clone()];
+        export_dirs.extend(self.cbz_export_dir.clone());
+        export_dirs.extend(self.pdf_export_dir.clone());
+        export_dirs
+    }
+
+    /// 获取`path_word`对应漫画的`download_format`，`comic_overrides`中有设置时优先使用
+    pub fn get_download_format(&self, path_word: &str) -> DownloadFormat {
+        self.comic_overrides
+            .get(path_word)
+            .and_then(|comic_override| comic_override.download_format)
+            .unwrap_or(self.download_format)
+    }
+
+    /// 获取`path_word`对应漫画的`chapter_dir_fmt`，`comic_overrides`中有设置时优先使用
+    pub fn get_chapter_dir_fmt(&self, path_word: &str) -> String {
+        self.comic_overrides
+            .get(path_word)
+            .and_then(|comic_override| comic_override.chapter_dir_fmt.clone())
+            .unwrap_or_else(|| self.chapter_dir_fmt.clone())
+    }
+
+    /// 获取`path_word`对应漫画的`skip_hd_upgrade_for_webtoon`，`comic_overrides`中有设置时优先使用
+    pub fn get_skip_hd_upgrade_for_webtoon(&self, path_word: &str) -> bool {
+        self.comic_overrides
+            .get(path_word)
+            .and_then(|comic_override| comic_override.skip_hd_upgrade_for_webtoon)
+            .unwrap_or(self.skip_hd_upgrade_for_webtoon)
+    }
+
     pub fn get_api_domain(&self) -> String {
         if self.api_domain_mode == ApiDomainMode::Custom {
             self.custom_api_domain.clone()
@@ -121,9 +379,194 @@ impl Config {
     }
 }
 
+/// 按host选择代理的规则
+///
+/// - `host_pattern`: 完整的host(如`api.2025copy.com`)，或`*.`开头的通配符(如`*.mangafunb.fun`，同时匹配根域名)
+/// - `proxy_url`: 代理地址(如`http://127.0.0.1:7890`)，为空表示直连
+///
+/// 有多条规则匹配同一个host时，列表中靠前的规则优先
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyRule {
+    pub host_pattern: String,
+    pub proxy_url: String,
+}
+
+impl ProxyRule {
+    pub fn host_matches(host_pattern: &str, host: &str) -> bool {
+        let host_pattern = host_pattern.trim();
+        match host_pattern.strip_prefix("*.") {
+            Some(domain) => {
+                host.eq_ignore_ascii_case(domain)
+                    || host
+                        .to_ascii_lowercase()
+                        .ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+            }
+            None => host.eq_ignore_ascii_case(host_pattern),
+        }
+    }
+}
+
+/// 单个漫画的下载设置，以`path_word`为key保存在`comic_overrides`中
+///
+/// 为`None`的字段使用全局配置
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ComicOverride {
+    pub download_format: Option<DownloadFormat>,
+    pub chapter_dir_fmt: Option<String>,
+    pub skip_hd_upgrade_for_webtoon: Option<bool>,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 pub enum ApiDomainMode {
     #[default]
     Default,
     Custom,
 }
+
+/// 章节在排队后被删除或屏蔽(404)时的处理方式
+///
+/// - `Skip`: 将任务标记为`Banned`，继续下载其他章节
+/// - `Fail`: 将任务标记为`Failed`，并记录错误日志
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Type)]
+pub enum ChapterUnavailableAction {
+    #[default]
+    Skip,
+    Fail,
+}
+
+/// 文件名中非法字符(`\/:*?"<>|`)的处理方式
+///
+/// - `FullWidthSubstitute`: 替换为全角或相似的字符，如`:`替换为`：`
+/// - `Remove`: 直接删除
+/// - `Underscore`: 替换为`_`
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Type)]
+pub enum FilenameSanitizeMode {
+    #[default]
+    FullWidthSubstitute,
+    Remove,
+    Underscore,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_override(path_word: &str, comic_override: ComicOverride) -> Config {
+        let mut config = Config::default(Path::new("/data"));
+        config.download_format = DownloadFormat::Webp;
+        config.chapter_dir_fmt = "{chapter_title}".to_string();
+        config.skip_hd_upgrade_for_webtoon = true;
+        config
+            .comic_overrides
+            .insert(path_word.to_string(), comic_override);
+        config
+    }
+
+    #[test]
+    fn comic_override_wins_over_global_config() {
+        let config = config_with_override(
+            "dianjuren",
+            ComicOverride {
+                download_format: Some(DownloadFormat::Jpeg),
+                chapter_dir_fmt: Some("{order} {chapter_title}".to_string()),
+                skip_hd_upgrade_for_webtoon: Some(false),
+            },
+        );
+        assert_eq!(
+            config.get_download_format("dianjuren"),
+            DownloadFormat::Jpeg
+        );
+        assert_eq!(
+            config.get_chapter_dir_fmt("dianjuren"),
+            "{order} {chapter_title}"
+        );
+        assert!(!config.get_skip_hd_upgrade_for_webtoon("dianjuren"));
+    }
+
+    #[test]
+    fn absent_comic_override_uses_global_config() {
+        let config = config_with_override(
+            "dianjuren",
+            ComicOverride {
+                download_format: Some(DownloadFormat::Jpeg),
+                ..Default::default()
+            },
+        );
+        // 有覆盖项但字段为`None`
+        assert_eq!(config.get_chapter_dir_fmt("dianjuren"), "{chapter_title}");
+        assert!(config.get_skip_hd_upgrade_for_webtoon("dianjuren"));
+        // 没有覆盖项
+        assert_eq!(config.get_download_format("other"), DownloadFormat::Webp);
+        assert_eq!(config.get_chapter_dir_fmt("other"), "{chapter_title}");
+        assert!(config.get_skip_hd_upgrade_for_webtoon("other"));
+    }
+
+    fn migrated_image_name_fmt(config_string: &str) -> Option<String> {
+        let config_string = Config::migrate_img_naming(config_string);
+        let json_value: serde_json::Value = serde_json::from_str(&config_string).unwrap();
+        assert!(json_value.get("imgNaming").is_none());
+        json_value
+            .get("imageNameFmt")
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_string)
+    }
+
+    #[test]
+    fn img_naming_is_mapped_onto_image_name_fmt() {
+        assert_eq!(
+            migrated_image_name_fmt(r#"{"imgNaming":"Index","imageNameFmt":""}"#).as_deref(),
+            Some(DEFAULT_IMAGE_NAME_FMT)
+        );
+        assert_eq!(
+            migrated_image_name_fmt(r#"{"imgNaming":"Original","imageNameFmt":""}"#).as_deref(),
+            Some(ORIGINAL_IMAGE_NAME_FMT)
+        );
+        assert_eq!(
+            migrated_image_name_fmt(r#"{"imgNaming":"Original"}"#).as_deref(),
+            Some(ORIGINAL_IMAGE_NAME_FMT)
+        );
+    }
+
+    #[test]
+    fn non_empty_image_name_fmt_wins_over_img_naming() {
+        assert_eq!(
+            migrated_image_name_fmt(r#"{"imgNaming":"Original","imageNameFmt":"p{index}"}"#)
+                .as_deref(),
+            Some("p{index}")
+        );
+    }
+
+    #[test]
+    fn invalid_chapter_title_strip_pattern_is_reset_on_load() {
+        let mut config = Config::default(Path::new("/data"));
+        config.chapter_title_strip_pattern = Some("[未闭合".to_string());
+        let reset_reasons = config.reset_invalid_fields();
+        assert_eq!(reset_reasons.len(), 1);
+        assert_eq!(config.chapter_title_strip_pattern, None);
+
+        config.chapter_title_strip_pattern = Some(r"\s*\(.*?\)".to_string());
+        assert!(config.reset_invalid_fields().is_empty());
+        assert_eq!(
+            config.chapter_title_strip_pattern.as_deref(),
+            Some(r"\s*\(.*?\)")
+        );
+    }
+
+    #[test]
+    fn order_decimal_separator_with_path_separator_is_rejected_and_reset() {
+        let mut config = Config::default(Path::new("/data"));
+        for separator in ["/", "\\", "_/_"] {
+            config.order_decimal_separator = separator.to_string();
+            assert!(config.validate().is_err());
+            assert_eq!(config.reset_invalid_fields().len(), 1);
+            assert_eq!(config.order_decimal_separator, ".");
+        }
+
+        config.order_decimal_separator = "_".to_string();
+        assert!(config.validate().is_ok());
+        assert!(config.reset_invalid_fields().is_empty());
+        assert_eq!(config.order_decimal_separator, "_");
+    }
+}
@@ -1,46 +1,83 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Context};
 use base64::{engine::general_purpose, Engine};
 use bytes::Bytes;
 use image::ImageFormat;
 use parking_lot::RwLock;
-use reqwest::StatusCode;
-use reqwest_middleware::ClientWithMiddleware;
-use reqwest_retry::{policies::ExponentialBackoff, Jitter, RetryTransientMiddleware};
+use reqwest::{Response, StatusCode};
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use reqwest_retry::{
+    policies::ExponentialBackoff, DefaultRetryableStrategy, Jitter, RetryTransientMiddleware,
+    Retryable, RetryableStrategy,
+};
 use serde_json::json;
 use tauri::AppHandle;
 use tokio::task::JoinSet;
 
 use crate::{
     account_pool::Account,
-    errors::{CopyMangaError, CopyMangaResult, RiskControlError},
-    extensions::{AppHandleExt, SendWithTimeoutMsg},
+    config::{Config, ProxyRule},
+    errors::{
+        CopyMangaError, CopyMangaResult, ImgRateLimitedError, NetworkTimeoutError, NotImageError,
+        RiskControlError,
+    },
+    extensions::{AnyhowErrorToStringChain, AppHandleExt, SendWithTimeoutMsg},
     responses::{
         ChapterInGetChaptersRespData, CopyResp, GetChapterRespData, GetChaptersRespData,
         GetComicRespData, GetFavoriteRespData, LoginRespData, SearchRespData, UserProfileRespData,
     },
-    types::GetFavoriteOrdering,
+    types::{ConnectionState, ConnectionStatus, GetFavoriteOrdering},
 };
 
 #[derive(Clone)]
 pub struct CopyClient {
     app: AppHandle,
-    api_client: ClientWithMiddleware,
-    img_client: ClientWithMiddleware,
+    api_client: Arc<RwLock<ClientWithMiddleware>>,
+    img_client: Arc<RwLock<ClientWithMiddleware>>,
+    /// 本次运行中最近一次请求成功的API域名
+    last_working_api_domain: Arc<RwLock<Option<String>>>,
 }
 
 impl CopyClient {
     pub fn new(app: AppHandle) -> Self {
-        let api_client = create_api_client();
-        let img_client = create_img_client();
+        let (api_client, img_client) = {
+            let config = app.get_config();
+            let config = config.read();
+            (create_api_client(&config), create_img_client(&config))
+        };
         Self {
             app,
-            api_client,
-            img_client,
+            api_client: Arc::new(RwLock::new(api_client)),
+            img_client: Arc::new(RwLock::new(img_client)),
+            last_working_api_domain: Arc::new(RwLock::new(None)),
         }
     }
 
+    /// 超时、代理、User-Agent和额外的header只在创建client时生效，修改这些配置后需要调用此方法重新创建client
+    ///
+    /// 正在进行的请求不受影响，之后的请求会使用新的client
+    pub fn reload_clients(&self) {
+        let (api_client, img_client) = {
+            let config = self.app.get_config();
+            let config = config.read();
+            (create_api_client(&config), create_img_client(&config))
+        };
+        *self.api_client.write() = api_client;
+        *self.img_client.write() = img_client;
+    }
+
+    fn api_client(&self) -> ClientWithMiddleware {
+        self.api_client.read().clone()
+    }
+
+    fn img_client(&self) -> ClientWithMiddleware {
+        self.img_client.read().clone()
+    }
+
     pub async fn register(&self, username: &str, password: &str) -> CopyMangaResult<()> {
         // 发送注册请求
         let form = json!({
@@ -48,12 +85,12 @@ impl CopyClient {
             "password": password,
             "source": "freeSite"
         });
-        let api_domain = self.get_api_domain();
         let http_resp = self
-            .api_client
-            .post(format!("https://{api_domain}/api/v3/register"))
-            .form(&form)
-            .send_with_timeout_msg()
+            .send_api_request(|api_domain| {
+                self.api_client()
+                    .post(format!("https://{api_domain}/api/v3/register"))
+                    .form(&form)
+            })
             .await?;
         // 检查http响应状态码
         let status = http_resp.status();
@@ -84,12 +121,12 @@ impl CopyClient {
             "salt": SALT,
         });
         // 发送登录请求
-        let api_domain = self.get_api_domain();
         let http_resp = self
-            .api_client
-            .post(format!("https://{api_domain}/api/v3/login"))
-            .form(&form)
-            .send_with_timeout_msg()
+            .send_api_request(|api_domain| {
+                self.api_client()
+                    .post(format!("https://{api_domain}/api/v3/login"))
+                    .form(&form)
+            })
             .await?;
         // 检查http响应状态码
         let status = http_resp.status();
@@ -120,12 +157,12 @@ impl CopyClient {
 
     pub async fn get_user_profile(&self) -> CopyMangaResult<UserProfileRespData> {
         // 发送获取用户信息请求
-        let api_domain = self.get_api_domain();
         let http_resp = self
-            .api_client
-            .get(format!("https://{api_domain}/api/v3/member/info"))
-            .header("authorization", self.get_authorization())
-            .send_with_timeout_msg()
+            .send_api_request(|api_domain| {
+                self.api_client()
+                    .get(format!("https://{api_domain}/api/v3/member/info"))
+                    .header("authorization", self.get_authorization())
+            })
             .await?;
         // 检查http响应状态码
         let status = http_resp.status();
@@ -167,12 +204,12 @@ impl CopyClient {
             "platform": 1,
         });
         // 发送搜索请求
-        let api_domain = self.get_api_domain();
         let http_resp = self
-            .api_client
-            .get(format!("https://{api_domain}/api/v3/search/comic"))
-            .query(&params)
-            .send_with_timeout_msg()
+            .send_api_request(|api_domain| {
+                self.api_client()
+                    .get(format!("https://{api_domain}/api/v3/search/comic"))
+                    .query(&params)
+            })
             .await?;
         // 检查http响应状态码
         let status = http_resp.status();
@@ -203,13 +240,11 @@ impl CopyClient {
             "platform": 1,
         });
         // 发送获取漫画请求
-        let api_domain = self.get_api_domain();
-        let url = format!("https://{api_domain}/api/v3/comic2/{comic_path_word}");
         let http_resp = self
-            .api_client
-            .get(url)
-            .query(&params)
-            .send_with_timeout_msg()
+            .send_api_request(|api_domain| {
+                let url = format!("https://{api_domain}/api/v3/comic2/{comic_path_word}");
+                self.api_client().get(url).query(&params)
+            })
             .await?;
         // 检查http响应状态码
         let status = http_resp.status();
@@ -289,15 +324,13 @@ impl CopyClient {
             "offset": offset,
         });
         // 发送获取章节分页请求
-        let api_domain = self.get_api_domain();
-        let url = format!(
-            "https://{api_domain}/api/v3/comic/{comic_path_word}/group/{group_path_word}/chapters"
-        );
         let http_resp = self
-            .api_client
-            .get(url)
-            .query(&params)
-            .send_with_timeout_msg()
+            .send_api_request(|api_domain| {
+                let url = format!(
+                    "https://{api_domain}/api/v3/comic/{comic_path_word}/group/{group_path_word}/chapters"
+                );
+                self.api_client().get(url).query(&params)
+            })
             .await?;
         // 检查http响应状态码
         let status = http_resp.status();
@@ -352,15 +385,16 @@ impl CopyClient {
             "platform": 1,
         });
         // 发送获取章节请求
-        let api_domain = self.get_api_domain();
-        let url =
-            format!("https://{api_domain}/api/v3/comic/{comic_path_word}/chapter2/{chapter_uuid}");
         let resp = self
-            .api_client
-            .get(url)
-            .query(&params)
-            .header("authorization", authorization)
-            .send_with_timeout_msg()
+            .send_api_request(|api_domain| {
+                let url = format!(
+                    "https://{api_domain}/api/v3/comic/{comic_path_word}/chapter2/{chapter_uuid}"
+                );
+                self.api_client()
+                    .get(url)
+                    .query(&params)
+                    .header("authorization", &authorization)
+            })
             .await?;
         // 检查http响应状态码
         let status = resp.status();
@@ -370,6 +404,10 @@ impl CopyClient {
             account.write().limited_at = chrono::Local::now().timestamp();
             self.app.get_account_pool().write().await.save()?;
             return Err(RiskControlError::GetChapter(body).into());
+        } else if status == StatusCode::NOT_FOUND {
+            // 章节已被删除或屏蔽
+            let err = anyhow!("获取章节失败，章节已被删除或屏蔽({status}): {body}");
+            return Err(CopyMangaError::Unavailable(err));
         } else if status != StatusCode::OK {
             return Err(anyhow!("获取章节失败，预料之外的状态码({status}): {body}").into());
         }
@@ -377,7 +415,10 @@ impl CopyClient {
         let copy_resp = serde_json::from_str::<CopyResp>(&body)
             .context(format!("获取章节失败，将body解析为CopyResp失败: {body}"))?;
         // 检查CopyResp的code字段
-        if copy_resp.code != 200 {
+        if copy_resp.code == 404 {
+            let err = anyhow!("获取章节失败，章节已被删除或屏蔽: {copy_resp:?}");
+            return Err(CopyMangaError::Unavailable(err));
+        } else if copy_resp.code != 200 {
             return Err(anyhow!("获取章节失败，预料之外的code: {copy_resp:?}").into());
         }
         // 尝试将CopyResp的results字段解析为GetChapterRespData
@@ -390,12 +431,25 @@ impl CopyClient {
         Ok(get_chapter_resp_data)
     }
 
-    pub async fn get_img_data_and_format(&self, url: &str) -> anyhow::Result<(Bytes, ImageFormat)> {
+    /// 优先根据图片数据的魔数判断图片格式，无法识别时再参考`content-type`
+    ///
+    /// - 数据无法识别且`content-type`不是图片(如`text/html`)时，返回以`NotImageError`为context的错误
+    /// - 数据无法识别但`content-type`是其他图片类型时返回`None`
+    /// - 状态码为429时，返回以`ImgRateLimitedError`为context的错误
+    pub async fn get_img_data_and_format(
+        &self,
+        url: &str,
+    ) -> anyhow::Result<(Bytes, Option<ImageFormat>)> {
         // 发送下载图片请求
-        let http_resp = self.img_client.get(url).send_with_timeout_msg().await?;
+        let http_resp = self.img_client().get(url).send_with_timeout_msg().await?;
         // 检查http响应状态码
         let status = http_resp.status();
-        if status != StatusCode::OK {
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let body = http_resp.text().await?;
+            return Err(
+                anyhow!("下载图片 {url} 失败，状态码为429: {body}").context(ImgRateLimitedError)
+            );
+        } else if status != StatusCode::OK {
             let body = http_resp.text().await?;
             return Err(anyhow!(
                 "下载图片 {url} 失败，预料之外的状态码({status}): {body}"
@@ -405,21 +459,59 @@ impl CopyClient {
         let content_type = http_resp
             .headers()
             .get("content-type")
-            .ok_or(anyhow!("响应中没有content-type字段"))?
-            .to_str()
-            .context("响应中的content-type字段不是utf-8字符串")?
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
             .to_string();
         // 读取图片数据
         let img_data = http_resp.bytes().await?;
-        let img_format = match content_type.as_str() {
-            "image/webp" => ImageFormat::WebP,
-            "image/jpeg" => ImageFormat::Jpeg,
-            _ => return Err(anyhow!("原图出现了意料之外的格式: {content_type}")),
+        // CDN偶尔会返回状态码为200的HTML错误页面，所以不能只相信`content-type`
+        let img_format = match (image::guess_format(&img_data).ok(), content_type.as_str()) {
+            (Some(img_format), _) => Some(img_format),
+            // webp和jpeg都能根据魔数识别，识别不出来说明数据不是图片
+            (None, "image/webp" | "image/jpeg") => {
+                return Err(anyhow!(
+                    "下载图片 {url} 失败，content-type为`{content_type}`，但数据无法识别为图片"
+                )
+                .context(NotImageError))
+            }
+            (None, content_type) if content_type.starts_with("image/") => {
+                tracing::warn!(
+                    url,
+                    content_type,
+                    "原图出现了意料之外的格式，无法根据图片数据识别"
+                );
+                None
+            }
+            (None, content_type) => {
+                return Err(anyhow!(
+                    "下载图片 {url} 失败，content-type为`{content_type}`，数据无法识别为图片"
+                )
+                .context(NotImageError))
+            }
         };
 
         Ok((img_data, img_format))
     }
 
+    /// 发送HEAD请求获取图片的大小，响应中没有`content-length`时返回`None`
+    pub async fn get_img_size(&self, url: &str) -> anyhow::Result<Option<u64>> {
+        let http_resp = self.img_client().head(url).send_with_timeout_msg().await?;
+        // 检查http响应状态码
+        let status = http_resp.status();
+        if status != StatusCode::OK {
+            return Err(anyhow!(
+                "获取图片 {url} 的大小失败，预料之外的状态码({status})"
+            ));
+        }
+        let img_size = http_resp
+            .headers()
+            .get("content-length")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+
+        Ok(img_size)
+    }
+
     pub async fn get_favorite(
         &self,
         page_num: i64,
@@ -433,13 +525,13 @@ impl CopyClient {
             "ordering": ordering.as_params(),
         });
         // 发送获取收藏请求
-        let api_domain = self.get_api_domain();
         let http_resp = self
-            .api_client
-            .get(format!("https://{api_domain}/api/v3/member/collect/comics"))
-            .query(&params)
-            .header("authorization", self.get_authorization())
-            .send_with_timeout_msg()
+            .send_api_request(|api_domain| {
+                self.api_client()
+                    .get(format!("https://{api_domain}/api/v3/member/collect/comics"))
+                    .query(&params)
+                    .header("authorization", self.get_authorization())
+            })
             .await?;
         // 检查http响应状态码
         let status = http_resp.status();
@@ -466,12 +558,90 @@ impl CopyClient {
         Ok(get_favorite_resp_data)
     }
 
+    /// 用每页1条的收藏请求测试API域名是否可以访问、token是否有效，并测量延迟
+    ///
+    /// 预料之外的响应(如5xx)会返回错误，而不是`ConnectionStatus`
+    pub async fn check_connection(&self) -> anyhow::Result<ConnectionStatus> {
+        let params = json!({
+            "limit": 1,
+            "offset": 0,
+            "free_type": 1,
+            "ordering": GetFavoriteOrdering::Added.as_params(),
+        });
+        let start = Instant::now();
+        let http_resp = match self
+            .send_api_request(|api_domain| {
+                self.api_client()
+                    .get(format!("https://{api_domain}/api/v3/member/collect/comics"))
+                    .query(&params)
+                    .header("authorization", self.get_authorization())
+            })
+            .await
+        {
+            Ok(http_resp) => http_resp,
+            Err(err) if err.is::<NetworkTimeoutError>() || is_connect_error(&err) => {
+                return Ok(ConnectionStatus {
+                    state: ConnectionState::DomainUnreachable,
+                    latency_ms: None,
+                    err_message: Some(err.to_string_chain()),
+                });
+            }
+            Err(err) => return Err(err),
+        };
+        #[allow(clippy::cast_possible_truncation)]
+        let latency_ms = start.elapsed().as_millis() as u64;
+
+        let status = http_resp.status();
+        let state = if status == 210 {
+            ConnectionState::RiskControl
+        } else if status == 401 {
+            ConnectionState::TokenInvalid
+        } else if status == StatusCode::OK {
+            ConnectionState::Ok
+        } else {
+            let body = http_resp.text().await?;
+            return Err(anyhow!("测试连接失败，预料之外的状态码({status}): {body}"));
+        };
+
+        Ok(ConnectionStatus {
+            state,
+            latency_ms: Some(latency_ms),
+            err_message: None,
+        })
+    }
+
     fn get_authorization(&self) -> String {
         self.app.get_config().read().get_authorization()
     }
 
-    fn get_api_domain(&self) -> String {
-        self.app.get_config().read().get_api_domain()
+    /// 依次用每个API域名发送请求，连接失败(如域名被屏蔽、DNS解析失败)时换下一个域名
+    ///
+    /// 只要收到了HTTP响应(包括4xx)就直接返回，不会换域名，成功的域名在本次运行中会被优先使用
+    async fn send_api_request<F>(&self, create_request: F) -> anyhow::Result<Response>
+    where
+        F: Fn(&str) -> RequestBuilder,
+    {
+        let api_domains = self.get_api_domains();
+        let (http_resp, api_domain) =
+            send_to_first_reachable_domain(&api_domains, create_request).await?;
+        *self.last_working_api_domain.write() = Some(api_domain.to_string());
+        Ok(http_resp)
+    }
+
+    /// 按尝试顺序返回所有API域名，见`order_api_domains`
+    fn get_api_domains(&self) -> Vec<String> {
+        let (api_domain, fallback_api_domains) = {
+            let config = self.app.get_config();
+            let config = config.read();
+            (config.get_api_domain(), config.fallback_api_domains.clone())
+        };
+        let last_working_api_domain = self.last_working_api_domain.read().clone();
+
+        order_api_domains(
+            api_domain,
+            fallback_api_domains,
+            last_working_api_domain.as_deref(),
+        )
     }
 
     async fn get_account_from_pool(&self) -> Option<Arc<RwLock<Account>>> {
@@ -483,17 +653,152 @@ impl CopyClient {
     }
 }
 
-fn create_img_client() -> ClientWithMiddleware {
+/// 按顺序用`api_domains`中的每个域名发送请求，返回第一个收到的HTTP响应及其域名
+///
+/// 只有连接失败时才会换下一个域名，其他错误直接返回
+async fn send_to_first_reachable_domain<F>(
+    api_domains: &[String],
+    create_request: F,
+) -> anyhow::Result<(Response, &str)>
+where
+    F: Fn(&str) -> RequestBuilder,
+{
+    let mut last_err = None;
+    for api_domain in api_domains {
+        match create_request(api_domain).send_with_timeout_msg().await {
+            Ok(http_resp) => {
+                if last_err.is_some() {
+                    tracing::info!(api_domain, "已切换到API域名`{api_domain}`");
+                }
+                return Ok((http_resp, api_domain.as_str()));
+            }
+            Err(err) if is_connect_error(&err) => {
+                let string_chain = err.to_string_chain();
+                tracing::warn!(
+                    api_domain,
+                    message = string_chain,
+                    "连接API域名`{api_domain}`失败，尝试下一个域名"
+                );
+                last_err = Some(err);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| anyhow!("没有可用的API域名")))
+}
+
+/// 按尝试顺序返回所有API域名，依次是主域名`api_domain`和`fallback_api_domains`，已去重
+///
+/// 如果`last_working_api_domain`(本次运行中最近一次成功的域名)仍在其中，则把它排在最前面
+fn order_api_domains(
+    api_domain: String,
+    fallback_api_domains: Vec<String>,
+    last_working_api_domain: Option<&str>,
+) -> Vec<String> {
+    let mut api_domains: Vec<String> = Vec::new();
+    for api_domain in std::iter::once(api_domain).chain(fallback_api_domains) {
+        let api_domain = api_domain.trim().to_string();
+        if !api_domain.is_empty() && !api_domains.contains(&api_domain) {
+            api_domains.push(api_domain);
+        }
+    }
+
+    if let Some(pos) = last_working_api_domain
+        .and_then(|last_working| api_domains.iter().position(|d| d == last_working))
+    {
+        let last_working = api_domains.remove(pos);
+        api_domains.insert(0, last_working);
+    }
+
+    api_domains
+}
+
+/// 是否是连接失败(包括DNS解析失败和连接超时)，这类错误换个域名可能就好了
+fn is_connect_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(reqwest::Error::is_connect)
+            || cause
+                .downcast_ref::<reqwest_middleware::Error>()
+                .is_some_and(reqwest_middleware::Error::is_connect)
+    })
+}
+
+/// 创建应用了超时和代理配置的`ClientBuilder`
+fn create_client_builder(config: &Config) -> reqwest::ClientBuilder {
+    let builder = reqwest::ClientBuilder::new()
+        .connect_timeout(Duration::from_secs(config.connect_timeout_sec))
+        .read_timeout(Duration::from_secs(config.read_timeout_sec));
+    // 添加代理后reqwest就不会再使用系统代理，所以只在有代理规则时才添加
+    match create_proxy(&config.proxy_rules) {
+        Some(proxy) => builder.proxy(proxy),
+        None => builder,
+    }
+}
+
+/// 根据`proxy_rules`创建代理
+///
+/// 按顺序匹配，第一条匹配上host的规则生效，`proxy_url`为空表示直连
+fn create_proxy(proxy_rules: &[ProxyRule]) -> Option<reqwest::Proxy> {
+    let mut rules = Vec::new();
+    for rule in proxy_rules {
+        if rule.proxy_url.trim().is_empty() {
+            rules.push((rule.host_pattern.clone(), None));
+            continue;
+        }
+        match reqwest::Url::parse(rule.proxy_url.trim()) {
+            Ok(proxy_url) => rules.push((rule.host_pattern.clone(), Some(proxy_url))),
+            Err(err) => {
+                let err_title = format!("代理地址`{}`无效，已忽略该规则", rule.proxy_url);
+                let string_chain = anyhow::Error::from(err).to_string_chain();
+                tracing::error!(err_title, message = string_chain);
+            }
+        }
+    }
+    if rules.is_empty() {
+        return None;
+    }
+
+    let proxy = reqwest::Proxy::custom(move |url| {
+        let host = url.host_str()?;
+        rules
+            .iter()
+            .find(|(host_pattern, _)| ProxyRule::host_matches(host_pattern, host))
+            .and_then(|(_, proxy_url)| proxy_url.clone())
+    });
+    Some(proxy)
+}
+
+/// 与`DefaultRetryableStrategy`相同，但不重试429
+///
+/// 图片服务器限流时由`DownloadImgTask`统一退避，如果这里也重试，两边的等待时间会叠加
+struct ImgRetryableStrategy;
+
+impl RetryableStrategy for ImgRetryableStrategy {
+    fn handle(&self, res: &reqwest_middleware::Result<Response>) -> Option<Retryable> {
+        match res {
+            Ok(http_resp) if http_resp.status() == StatusCode::TOO_MANY_REQUESTS => None,
+            _ => DefaultRetryableStrategy.handle(res),
+        }
+    }
+}
+
+fn create_img_retry_middleware(
+) -> RetryTransientMiddleware<ExponentialBackoff, ImgRetryableStrategy> {
     let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
+    RetryTransientMiddleware::new_with_policy_and_strategy(retry_policy, ImgRetryableStrategy)
+}
 
-    let client = reqwest::ClientBuilder::new().build().unwrap();
+fn create_img_client(config: &Config) -> ClientWithMiddleware {
+    let client = create_client_builder(config).build().unwrap();
 
     reqwest_middleware::ClientBuilder::new(client)
-        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+        .with(create_img_retry_middleware())
         .build()
 }
 
-fn create_api_client() -> ClientWithMiddleware {
+fn create_api_client(config: &Config) -> ClientWithMiddleware {
     use reqwest::header::{HeaderMap, HeaderValue};
 
     let retry_policy = ExponentialBackoff::builder()
@@ -504,16 +809,16 @@ fn create_api_client() -> ClientWithMiddleware {
     let from_static = HeaderValue::from_static;
 
     let mut headers = HeaderMap::new();
-    headers.insert("User-Agent", from_static("COPY/3.0.0"));
     headers.insert("Accept", from_static("application/json"));
     headers.insert("version", from_static("2025.08.15"));
     headers.insert("platform", from_static("1"));
     headers.insert("webp", from_static("1"));
     headers.insert("region", from_static("1"));
+    // User-Agent和用户配置的额外header，与上面的header同名时覆盖上面的
+    headers.extend(config.get_custom_headers());
 
-    let client = reqwest::ClientBuilder::new()
+    let client = create_client_builder(config)
         .default_headers(headers)
-        .timeout(Duration::from_secs(3)) // 每个请求超过3秒就超时
         .build()
         .unwrap();
 
@@ -521,3 +826,136 @@ fn create_api_client() -> ClientWithMiddleware {
         .with(RetryTransientMiddleware::new_with_policy(retry_policy))
         .build()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    fn domains(api_domains: &[&str]) -> Vec<String> {
+        api_domains.iter().map(ToString::to_string).collect()
+    }
+
+    /// 在本地随机端口启动一个HTTP服务，对每个请求都返回`status_line`
+    ///
+    /// 返回`host:port`和收到的请求数
+    async fn spawn_http_server(status_line: &'static str) -> (String, Arc<AtomicU32>) {
+        let request_count = Arc::new(AtomicU32::new(0));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn({
+            let request_count = request_count.clone();
+            async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    // 先读取请求，否则关闭连接时未读的数据可能导致客户端收到RST
+                    let mut buf = [0u8; 4096];
+                    let _ = stream.read(&mut buf).await;
+                    request_count.fetch_add(1, Ordering::Relaxed);
+                    let resp = format!("HTTP/1.1 {status_line}\r\ncontent-length: 0\r\n\r\n");
+                    let _ = stream.write_all(resp.as_bytes()).await;
+                }
+            }
+        });
+        (addr.to_string(), request_count)
+    }
+
+    /// 返回一个没有监听的本地端口`host:port`，连接它会被拒绝
+    async fn closed_port() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    fn create_request(api_domain: &str) -> RequestBuilder {
+        reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+            .build()
+            .get(format!("http://{api_domain}/"))
+    }
+
+    #[tokio::test]
+    async fn img_client_does_not_retry_429() {
+        let (addr, request_count) = spawn_http_server("429 Too Many Requests").await;
+        let img_client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+            .with(create_img_retry_middleware())
+            .build();
+
+        let http_resp = img_client
+            .get(format!("http://{addr}/1.webp"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(http_resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(request_count.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn order_api_domains_dedups_and_keeps_config_order() {
+        let api_domains = order_api_domains(
+            "a.com".to_string(),
+            domains(&[" b.com ", "a.com", "", "c.com"]),
+            None,
+        );
+        assert_eq!(api_domains, domains(&["a.com", "b.com", "c.com"]));
+    }
+
+    #[test]
+    fn order_api_domains_tries_last_working_first() {
+        let api_domains = order_api_domains(
+            "a.com".to_string(),
+            domains(&["b.com", "c.com"]),
+            Some("c.com"),
+        );
+        assert_eq!(api_domains, domains(&["c.com", "a.com", "b.com"]));
+    }
+
+    #[test]
+    fn order_api_domains_ignores_removed_last_working() {
+        let api_domains =
+            order_api_domains("a.com".to_string(), domains(&["b.com"]), Some("x.com"));
+        assert_eq!(api_domains, domains(&["a.com", "b.com"]));
+    }
+
+    #[tokio::test]
+    async fn failover_to_next_domain_after_connect_error() {
+        let closed = closed_port().await;
+        let (working, working_request_count) = spawn_http_server("200 OK").await;
+        let api_domains = vec![closed, working.clone()];
+
+        let (http_resp, api_domain) = send_to_first_reachable_domain(&api_domains, create_request)
+            .await
+            .unwrap();
+        assert_eq!(http_resp.status(), StatusCode::OK);
+        assert_eq!(api_domain, working);
+        // 请求确实发到了第二个域名，而且只发了一次
+        assert_eq!(working_request_count.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn no_failover_on_4xx() {
+        let (not_found, _) = spawn_http_server("404 Not Found").await;
+        let (working, working_request_count) = spawn_http_server("200 OK").await;
+        let api_domains = vec![not_found.clone(), working];
+
+        let (http_resp, api_domain) = send_to_first_reachable_domain(&api_domains, create_request)
+            .await
+            .unwrap();
+        assert_eq!(http_resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(api_domain, not_found);
+        assert_eq!(working_request_count.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn error_when_all_domains_unreachable() {
+        let api_domains = vec![closed_port().await, closed_port().await];
+
+        let err = send_to_first_reachable_domain(&api_domains, create_request)
+            .await
+            .unwrap_err();
+        assert!(is_connect_error(&err));
+    }
+}
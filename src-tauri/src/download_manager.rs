@@ -1,10 +1,11 @@
 use std::{
     collections::HashMap,
-    io::Cursor,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{Cursor, Write},
     ops::ControlFlow,
     path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicU32, AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
         Arc,
     },
     time::Duration,
@@ -12,8 +13,9 @@ use std::{
 
 use anyhow::{anyhow, Context};
 use bytes::Bytes;
+use float_ord::FloatOrd;
 use image::ImageFormat;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use regex_lite::{Captures, Regex};
 use serde::{Deserialize, Serialize};
 use specta::Type;
@@ -22,17 +24,25 @@ use tauri_specta::Event;
 use tokio::{
     sync::{watch, Semaphore, SemaphorePermit},
     task::JoinSet,
-    time::sleep,
+    time::{sleep, Instant},
 };
 
 use crate::{
-    errors::{CopyMangaError, RiskControlError},
+    config::{ChapterUnavailableAction, Config, FilenameSanitizeMode, DEFAULT_IMAGE_NAME_FMT},
+    errors::{
+        CopyMangaError, CopyMangaResult, ImgRateLimitedError, LoginRequiredError,
+        NetworkTimeoutError, NotImageError, RiskControlError,
+    },
     events::{
-        DownloadControlRiskEvent, DownloadSleepingEvent, DownloadSpeedEvent, DownloadTaskEvent,
+        ComicDownloadCompleteEvent, DownloadControlRiskEvent, DownloadInsufficientSpaceEvent,
+        DownloadSleepingEvent, DownloadSpeedEvent, DownloadTaskEvent, FavoriteBatchEvent,
+        ImgRateLimitedEvent, RiskControlCooldownEvent, TranscodeEvent, UpdateDownloadedComicsEvent,
     },
-    extensions::{AnyhowErrorToStringChain, AppHandleExt},
-    responses::GetChapterRespData,
-    types::{ChapterInfo, Comic},
+    export::{self, Archive},
+    extensions::{AnyhowErrorToStringChain, AppHandleExt, PathIsImg},
+    logger,
+    responses::{ChapterInGetChapterRespData, GetChapterRespData},
+    types::{AsyncMutex, ChapterInfo, Comic, DownloadFormat, OverallProgress},
     utils,
 };
 
@@ -51,6 +61,21 @@ pub struct DownloadManager {
     img_sem: Arc<Semaphore>,
     byte_per_sec: Arc<AtomicU64>,
     download_tasks: Arc<RwLock<HashMap<String, DownloadTask>>>,
+    /// 自启动或上次重置以来，触发风控的次数
+    risk_control_count: Arc<AtomicU32>,
+    /// `comic_uuid` -> 转码任务的取消标志
+    transcode_cancel_flags: Arc<RwLock<HashMap<String, Arc<AtomicBool>>>>,
+    /// 全局暂停开关，为`true`时所有任务都不再下载，但保留各自的状态和排队位置
+    global_paused_sender: Arc<watch::Sender<bool>>,
+    /// 是否正在更新已下载的漫画，避免手动更新和自动更新同时进行
+    updating_downloaded_comics: Arc<AtomicBool>,
+    /// `comic_uuid` -> 上次保存的元数据的哈希值，用于跳过内容相同的重复保存，这个漫画这一批的下载任务全部结束后移除
+    saved_metadata_hashes: Arc<Mutex<HashMap<String, u64>>>,
+    download_event_log_lock: Arc<Mutex<()>>,
+    /// 注册风控的冷却结束时间，冷却期间所有任务都不再请求章节，以免延长风控时间
+    risk_control_cooldown_until: Arc<Mutex<Option<Instant>>>,
+    /// `comic_uuid` -> 这个漫画当前这一批的下载任务，这一批全部结束后移除
+    comic_batches: Arc<Mutex<HashMap<String, Vec<DownloadTask>>>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
@@ -61,6 +86,61 @@ pub enum DownloadTaskState {
     Cancelled,
     Completed,
     Failed,
+    /// 章节已被删除或屏蔽
+    Banned,
+}
+
+const DOWNLOAD_EVENT_LOG_FILENAME: &str = "download_events.jsonl";
+/// 检查磁盘空间时，估算的每张图片的大小
+const ESTIMATED_IMG_BYTES: u64 = 1024 * 1024;
+
+/// gif的保存方式，gif可能是动图，`Convert`会和其他图片一样转换为`download_format`，从而丢失动画
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GifSaveMode {
+    Convert,
+    KeepAsIs,
+    #[cfg(feature = "animated-webp")]
+    AnimatedWebp,
+}
+
+impl GifSaveMode {
+    /// `convert_gif_to_animated_webp`优先于`keep_gif_as_is`
+    fn from_config(config: &Config) -> Self {
+        #[cfg(feature = "animated-webp")]
+        if config.convert_gif_to_animated_webp {
+            return GifSaveMode::AnimatedWebp;
+        }
+        if config.keep_gif_as_is {
+            return GifSaveMode::KeepAsIs;
+        }
+        GifSaveMode::Convert
+    }
+
+    /// 保存gif时使用的扩展名，`Convert`时与其他图片一样，返回`None`
+    fn extension(self) -> Option<&'static str> {
+        match self {
+            GifSaveMode::Convert => None,
+            GifSaveMode::KeepAsIs => Some("gif"),
+            #[cfg(feature = "animated-webp")]
+            GifSaveMode::AnimatedWebp => Some("webp"),
+        }
+    }
+}
+
+/// `download_events.jsonl`中的一行，记录下载任务的一次状态变化，字段名是对外的格式，不要随意修改
+#[derive(Debug, Serialize)]
+struct DownloadEventRecord<'a> {
+    timestamp: String,
+    chapter_uuid: &'a str,
+    comic_path_word: &'a str,
+    old_state: DownloadTaskState,
+    new_state: DownloadTaskState,
+    downloaded_img_count: u32,
+    total_img_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    err_title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    err_message: Option<&'a str>,
 }
 
 impl DownloadManager {
@@ -77,9 +157,18 @@ impl DownloadManager {
             img_sem: Arc::new(Semaphore::new(img_concurrency)),
             byte_per_sec: Arc::new(AtomicU64::new(0)),
             download_tasks: Arc::new(RwLock::new(HashMap::new())),
+            risk_control_count: Arc::new(AtomicU32::new(0)),
+            transcode_cancel_flags: Arc::new(RwLock::new(HashMap::new())),
+            global_paused_sender: Arc::new(watch::channel(false).0),
+            updating_downloaded_comics: Arc::new(AtomicBool::new(false)),
+            saved_metadata_hashes: Arc::new(Mutex::new(HashMap::new())),
+            download_event_log_lock: Arc::new(Mutex::new(())),
+            risk_control_cooldown_until: Arc::new(Mutex::new(None)),
+            comic_batches: Arc::new(Mutex::new(HashMap::new())),
         };
 
         tauri::async_runtime::spawn(manager.clone().emit_download_speed_loop());
+        tauri::async_runtime::spawn(manager.clone().auto_update_downloaded_comics_loop());
 
         manager
     }
@@ -98,8 +187,241 @@ impl DownloadManager {
         }
     }
 
+    /// 每隔`auto_update_downloaded_comics_interval_min`分钟自动更新一次已下载的漫画，为0时不自动更新
+    async fn auto_update_downloaded_comics_loop(self) {
+        loop {
+            let interval_min = self
+                .app
+                .get_config()
+                .read()
+                .auto_update_downloaded_comics_interval_min;
+            if interval_min == 0 {
+                // 没有启用自动更新，每分钟检查一次配置
+                sleep(Duration::from_secs(60)).await;
+                continue;
+            }
+            sleep(Duration::from_secs(interval_min * 60)).await;
+            tracing::info!("开始自动更新已下载的漫画");
+            self.update_downloaded_comics().await;
+        }
+    }
+
+    /// 重新获取所有已下载漫画的章节列表，为已有下载的分组中的新章节创建下载任务
+    ///
+    /// 每处理完一部漫画休息`update_downloaded_comics_interval_sec`秒，避免触发风控
+    #[allow(clippy::cast_possible_wrap)]
+    pub async fn update_downloaded_comics(&self) {
+        if self
+            .updating_downloaded_comics
+            .swap(true, Ordering::Relaxed)
+        {
+            tracing::warn!("已有正在进行的更新，跳过本次更新");
+            return;
+        }
+
+        let app = &self.app;
+        let interval_sec = app
+            .get_config()
+            .read()
+            .update_downloaded_comics_interval_sec;
+
+        let downloaded_comics: Vec<Comic> = match utils::create_path_word_to_dir_map(app) {
+            Ok(path_word_to_dir_map) => path_word_to_dir_map
+                .values()
+                .filter_map(|comic_download_dirs| comic_download_dirs.first())
+                .filter_map(|comic_download_dir| {
+                    let metadata_path = comic_download_dir.join("元数据.json");
+                    match Comic::from_metadata(&metadata_path) {
+                        Ok(comic) => Some(comic),
+                        Err(err) => {
+                            let err_title = "获取已下载漫画的过程中遇到错误，已跳过";
+                            let string_chain = err.to_string_chain();
+                            tracing::error!(err_title, message = string_chain);
+                            None
+                        }
+                    }
+                })
+                .collect(),
+            Err(err) => {
+                let err_title = "更新库存失败";
+                let string_chain = err.to_string_chain();
+                tracing::error!(err_title, message = string_chain);
+                self.updating_downloaded_comics
+                    .store(false, Ordering::Relaxed);
+                return;
+            }
+        };
+
+        let total = downloaded_comics.len() as i64;
+        let _ = UpdateDownloadedComicsEvent::GetComicStart { total }.emit(app);
+
+        // 漫画标题 -> 新章节数
+        let mut new_chapter_counts = HashMap::new();
+        for (i, downloaded_comic) in downloaded_comics.into_iter().enumerate() {
+            let comic_title = &downloaded_comic.comic.name;
+            let comic_path_word = &downloaded_comic.comic.path_word;
+            let current = (i + 1) as i64;
+            let _ = UpdateDownloadedComicsEvent::GetComicProgress { current, total }.emit(app);
+
+            let comic = match utils::get_comic(app.clone(), comic_path_word)
+                .await
+                .context(format!("获取路径为`{comic_path_word}`的漫画失败"))
+            {
+                Ok(comic) => comic,
+                Err(err) => {
+                    let err_title = format!("更新库存过程中，获取漫画`{comic_title}`失败，已跳过");
+                    let err = err.context("可能是频率太高，请手动去`配置`里调整`更新库存时，每处理完一个已下载的漫画后休息`");
+                    let string_chain = err.to_string_chain();
+                    tracing::error!(err_title, message = string_chain);
+                    sleep(Duration::from_secs(interval_sec)).await;
+                    continue;
+                }
+            };
+
+            // 获取有已下载章节的分组中所有未下载的章节
+            let mut chapter_infos: Vec<&ChapterInfo> = comic
+                .comic
+                .groups
+                .values()
+                .filter(|chapter_infos| {
+                    chapter_infos
+                        .iter()
+                        .any(|chapter_info| chapter_info.is_downloaded.unwrap_or(false))
+                })
+                .flat_map(|chapter_infos| {
+                    chapter_infos
+                        .iter()
+                        .filter(|chapter_info| !chapter_info.is_downloaded.unwrap_or(false))
+                })
+                .collect();
+
+            if chapter_infos.is_empty() {
+                sleep(Duration::from_secs(interval_sec)).await;
+                continue;
+            }
+            new_chapter_counts.insert(comic_title.clone(), chapter_infos.len() as i64);
+            // 跨分组按阅读顺序创建下载任务，这样靠前的章节会先下载完成
+            chapter_infos
+                .sort_by_key(|chapter_info| FloatOrd(comic.get_chapter_order(chapter_info)));
+
+            let _ = UpdateDownloadedComicsEvent::CreateDownloadTasksStart {
+                comic_path_word: comic_path_word.clone(),
+                comic_title: comic_title.clone(),
+                current: 0,
+                total: chapter_infos.len() as i64,
+            }
+            .emit(app);
+
+            for (i, chapter_info) in chapter_infos.into_iter().enumerate() {
+                let chapter_uuid = &chapter_info.chapter_uuid;
+                let current = (i + 1) as i64;
+
+                let _ = self.create_download_task(comic.clone(), chapter_uuid);
+
+                let _ = UpdateDownloadedComicsEvent::CreateDownloadTaskProgress {
+                    comic_path_word: comic_path_word.clone(),
+                    current,
+                }
+                .emit(app);
+
+                sleep(Duration::from_millis(100)).await;
+            }
+
+            let _ = UpdateDownloadedComicsEvent::CreateDownloadTasksEnd {
+                comic_path_word: comic_path_word.clone(),
+            }
+            .emit(app);
+
+            sleep(Duration::from_secs(interval_sec)).await;
+        }
+
+        let _ = UpdateDownloadedComicsEvent::NewChaptersSummary { new_chapter_counts }.emit(app);
+        let _ = UpdateDownloadedComicsEvent::GetComicEnd.emit(app);
+
+        self.updating_downloaded_comics
+            .store(false, Ordering::Relaxed);
+    }
+
+    /// 获取收藏夹中的所有漫画，为每部漫画的章节创建下载任务
+    ///
+    /// `only_not_downloaded`为`true`时只为未下载的章节创建任务，否则为所有章节创建任务
+    ///
+    /// 每处理完一部漫画休息`update_downloaded_comics_interval_sec`秒，避免触发风控
+    #[allow(clippy::cast_possible_truncation)]
+    pub async fn download_all_favorites(&self, only_not_downloaded: bool) -> anyhow::Result<()> {
+        let app = &self.app;
+        let interval_sec = app
+            .get_config()
+            .read()
+            .update_downloaded_comics_interval_sec;
+
+        let favorites = utils::get_all_favorites(app)
+            .await
+            .context("获取收藏夹中的所有漫画失败")?;
+
+        let total = favorites.len() as u32;
+        for (i, favorite) in favorites.into_iter().enumerate() {
+            let comic_title = &favorite.comic.name;
+            let comic_path_word = &favorite.comic.path_word;
+            let _ = FavoriteBatchEvent {
+                current: (i + 1) as u32,
+                total,
+                comic_name: comic_title.clone(),
+            }
+            .emit(app);
+
+            let comic = match utils::get_comic(app.clone(), comic_path_word)
+                .await
+                .context(format!("获取路径为`{comic_path_word}`的漫画失败"))
+            {
+                Ok(comic) => comic,
+                Err(err) => {
+                    let err_title =
+                        format!("批量下载收藏过程中，获取漫画`{comic_title}`失败，已跳过");
+                    let string_chain = err.to_string_chain();
+                    tracing::error!(err_title, message = string_chain);
+                    sleep(Duration::from_secs(interval_sec)).await;
+                    continue;
+                }
+            };
+
+            let mut chapter_infos: Vec<&ChapterInfo> = comic
+                .comic
+                .groups
+                .values()
+                .flatten()
+                .filter(|chapter_info| {
+                    !only_not_downloaded || chapter_info.is_downloaded != Some(true)
+                })
+                .collect();
+            // 跨分组按阅读顺序创建下载任务，这样靠前的章节会先下载完成
+            chapter_infos
+                .sort_by_key(|chapter_info| FloatOrd(comic.get_chapter_order(chapter_info)));
+
+            for chapter_info in chapter_infos {
+                let chapter_uuid = &chapter_info.chapter_uuid;
+                if let Err(err) = self.create_download_task(comic.clone(), chapter_uuid) {
+                    let chapter_title = &chapter_info.chapter_title;
+                    let err_title = format!("`{comic_title} - {chapter_title}`创建下载任务失败");
+                    let string_chain = err.to_string_chain();
+                    tracing::error!(err_title, message = string_chain);
+                }
+                sleep(Duration::from_millis(100)).await;
+            }
+
+            sleep(Duration::from_secs(interval_sec)).await;
+        }
+
+        Ok(())
+    }
+
     pub fn create_download_task(&self, comic: Comic, chapter_uuid: &str) -> anyhow::Result<()> {
         use DownloadTaskState::{Downloading, Paused, Pending};
+        // 提前拦截，否则要等到获取章节失败并重试多次后才会报错
+        let logged_in = !self.app.get_config().read().token.is_empty();
+        if !logged_in && comic.requires_login() {
+            return Err(anyhow!(LoginRequiredError));
+        }
         let mut tasks = self.download_tasks.write();
         if let Some(task) = tasks.get(chapter_uuid) {
             // 如果任务已经存在，且状态是`Pending`、`Downloading`或`Paused`，则不创建新任务
@@ -111,11 +433,70 @@ impl DownloadManager {
         tasks.remove(chapter_uuid);
         let task = DownloadTask::new(self.app.clone(), comic, chapter_uuid)
             .context("DownloadTask创建失败")?;
+        {
+            let mut comic_batches = self.comic_batches.lock();
+            let batch = comic_batches
+                .entry(task.comic.comic.uuid.clone())
+                .or_default();
+            batch.retain(|task| task.chapter_info.chapter_uuid != chapter_uuid);
+            batch.push(task.clone());
+        }
         tauri::async_runtime::spawn(task.clone().process());
         tasks.insert(chapter_uuid.to_string(), task);
         Ok(())
     }
 
+    /// 为`group_path_word`分组中`order`最大的`n`个未下载章节创建下载任务，返回创建了下载任务的章节ID
+    ///
+    /// 会先根据本地的元数据更新`is_downloaded`字段，未下载的章节不足`n`个时为所有未下载的章节创建任务
+    ///
+    /// 某个章节的下载任务创建失败时记录错误并跳过，不影响其他章节
+    pub fn create_tasks_for_latest(
+        &self,
+        mut comic: Comic,
+        group_path_word: &str,
+        n: usize,
+    ) -> anyhow::Result<Vec<String>> {
+        let comic_title = comic.comic.name.clone();
+        let path_word_to_dir_map = utils::create_path_word_to_dir_map(&self.app)
+            .context("创建漫画路径词到下载目录映射失败")?;
+        comic
+            .update_fields(&path_word_to_dir_map)
+            .context(format!("`{comic_title}`更新Comic的字段失败"))?;
+
+        let chapter_infos = comic
+            .comic
+            .groups
+            .get(group_path_word)
+            .context(format!("`{comic_title}`没有分组`{group_path_word}`"))?;
+        let mut chapter_infos: Vec<&ChapterInfo> = chapter_infos
+            .iter()
+            .filter(|chapter_info| chapter_info.is_downloaded != Some(true))
+            .collect();
+        chapter_infos.sort_by_key(|chapter_info| {
+            std::cmp::Reverse(FloatOrd(comic.get_chapter_order(chapter_info)))
+        });
+        let chapter_uuids: Vec<String> = chapter_infos
+            .into_iter()
+            .take(n)
+            .map(|chapter_info| chapter_info.chapter_uuid.clone())
+            .collect();
+
+        let mut scheduled_chapter_uuids = Vec::new();
+        for chapter_uuid in chapter_uuids {
+            if let Err(err) = self.create_download_task(comic.clone(), &chapter_uuid) {
+                let err_title =
+                    format!("`{comic_title}`的章节ID为`{chapter_uuid}`的下载任务创建失败");
+                let string_chain = err.to_string_chain();
+                tracing::error!(err_title, message = string_chain);
+                continue;
+            }
+            scheduled_chapter_uuids.push(chapter_uuid);
+        }
+
+        Ok(scheduled_chapter_uuids)
+    }
+
     pub fn pause_download_task(&self, chapter_uuid: &str) -> anyhow::Result<()> {
         let tasks = self.download_tasks.read();
         let Some(task) = tasks.get(chapter_uuid) else {
@@ -142,6 +523,353 @@ impl DownloadManager {
         task.set_state(DownloadTaskState::Cancelled);
         Ok(())
     }
+
+    /// 取消漫画`comic_uuid`所有未结束的下载任务，并等待这些任务停止，超过`timeout`仍未停止则返回错误
+    pub async fn cancel_comic_download_tasks(
+        &self,
+        comic_uuid: &str,
+        timeout: Duration,
+    ) -> anyhow::Result<()> {
+        use DownloadTaskState::{Downloading, Paused, Pending};
+        let mut finished_receivers = Vec::new();
+        {
+            let tasks = self.download_tasks.read();
+            for task in tasks.values() {
+                if task.comic.comic.uuid != comic_uuid {
+                    continue;
+                }
+                let state = *task.state_sender.borrow();
+                if matches!(state, Pending | Downloading | Paused) {
+                    task.set_state(DownloadTaskState::Cancelled);
+                }
+                let chapter_title = task.chapter_info.chapter_title.clone();
+                finished_receivers.push((chapter_title, task.finished_sender.subscribe()));
+            }
+        }
+
+        for (chapter_title, mut finished_receiver) in finished_receivers {
+            // sender已被drop也说明任务已经结束，所以只需要处理超时
+            let wait_finished = finished_receiver.wait_for(|finished| *finished);
+            if tokio::time::timeout(timeout, wait_finished).await.is_err() {
+                return Err(anyhow!("等待`{chapter_title}`的下载任务停止超时"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 移除所有状态为`Cancelled`、`Completed`、`Failed`或`Banned`的下载任务，返回被移除任务的章节ID
+    ///
+    /// 已结束的任务只有在重新创建同一章节的任务时才会被替换，不清理的话`download_tasks`会一直增长
+    pub fn clear_finished_tasks(&self) -> Vec<String> {
+        use DownloadTaskState::{Banned, Cancelled, Completed, Failed};
+        let mut tasks = self.download_tasks.write();
+        let finished_chapter_uuids: Vec<String> = tasks
+            .iter()
+            .filter(|(_, task)| {
+                matches!(
+                    *task.state_sender.borrow(),
+                    Cancelled | Completed | Failed | Banned
+                )
+            })
+            .map(|(chapter_uuid, _)| chapter_uuid.clone())
+            .collect();
+        for chapter_uuid in &finished_chapter_uuids {
+            tasks.remove(chapter_uuid);
+        }
+        finished_chapter_uuids
+    }
+
+    /// 汇总所有下载任务的进度，只读取原子计数和状态，开销很小
+    pub fn overall_progress(&self) -> OverallProgress {
+        let mut progress = OverallProgress::default();
+        let tasks = self.download_tasks.read();
+        for task in tasks.values() {
+            progress.downloaded_img_count +=
+                u64::from(task.downloaded_img_count.load(Ordering::Relaxed));
+            progress.total_img_count += u64::from(task.total_img_count.load(Ordering::Relaxed));
+            let state_count = match *task.state_sender.borrow() {
+                DownloadTaskState::Pending => &mut progress.pending_count,
+                DownloadTaskState::Downloading => &mut progress.downloading_count,
+                DownloadTaskState::Paused => &mut progress.paused_count,
+                DownloadTaskState::Cancelled => &mut progress.cancelled_count,
+                DownloadTaskState::Completed => &mut progress.completed_count,
+                DownloadTaskState::Failed => &mut progress.failed_count,
+                DownloadTaskState::Banned => &mut progress.banned_count,
+            };
+            *state_count += 1;
+        }
+        progress
+    }
+
+    /// 全局暂停或恢复所有下载任务(如切换到电池供电或按流量计费的网络时)
+    ///
+    /// 与`pause_download_task`不同，全局暂停不会修改任务的状态，恢复后任务会按原来的顺序继续
+    pub fn set_paused_global(&self, paused: bool) {
+        self.global_paused_sender.send_replace(paused);
+    }
+
+    /// 在`download_events.jsonl`末尾追加一条记录，`enable_download_event_log`为`false`时什么都不做
+    ///
+    /// 文件超过`log_max_size_mb`时滚动，与文件日志一样最多保留`log_max_files`个文件
+    fn append_download_event(&self, record: &DownloadEventRecord) -> anyhow::Result<()> {
+        let (enable_download_event_log, log_max_size_mb, log_max_files) = {
+            let config = self.app.get_config();
+            let config = config.read();
+            (
+                config.enable_download_event_log,
+                config.log_max_size_mb,
+                config.log_max_files as usize,
+            )
+        };
+        if !enable_download_event_log {
+            return Ok(());
+        }
+
+        let mut line = serde_json::to_string(record).context("将下载事件序列化为json失败")?;
+        line.push('\n');
+
+        // 多个任务会同时写入，写入期间一直持有锁，保证每行都是完整的
+        let _lock = self.download_event_log_lock.lock();
+        let logs_dir = logger::logs_dir(&self.app).context("获取日志目录失败")?;
+        std::fs::create_dir_all(&logs_dir)
+            .context(format!("创建日志目录`{}`失败", logs_dir.display()))?;
+        let log_path = logs_dir.join(DOWNLOAD_EVENT_LOG_FILENAME);
+
+        let max_size = log_max_size_mb * 1024 * 1024;
+        let size = log_path
+            .metadata()
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        if max_size > 0 && size > 0 && size + line.len() as u64 > max_size {
+            rotate_download_event_log(&logs_dir, log_max_files).context("滚动下载事件日志失败")?;
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+            .context(format!("打开文件`{}`失败", log_path.display()))?;
+        file.write_all(line.as_bytes())
+            .context(format!("写入文件`{}`失败", log_path.display()))?;
+
+        Ok(())
+    }
+
+    /// 保存漫画元数据，与上次保存的内容相同且`元数据.json`仍存在时跳过
+    ///
+    /// 同一漫画的多个章节会并发调用，保存期间一直持有锁，所以同一时间只有一个章节在写`元数据.json`
+    fn save_comic_metadata(&self, comic: &Comic) -> anyhow::Result<()> {
+        let mut saved_metadata_hashes = self.saved_metadata_hashes.lock();
+
+        let comic_uuid = &comic.comic.uuid;
+        let comic_json = comic.to_metadata_json()?;
+        let metadata_hash = {
+            let mut hasher = DefaultHasher::new();
+            comic_json.hash(&mut hasher);
+            hasher.finish()
+        };
+        let metadata_exists = comic
+            .comic_download_dir
+            .as_ref()
+            .is_some_and(|comic_download_dir| comic_download_dir.join("元数据.json").exists());
+        let already_saved = saved_metadata_hashes.get(comic_uuid) == Some(&metadata_hash);
+        if metadata_exists && already_saved {
+            return Ok(());
+        }
+
+        comic.write_metadata_json(&comic_json)?;
+        saved_metadata_hashes.insert(comic_uuid.clone(), metadata_hash);
+
+        Ok(())
+    }
+
+    /// 下载任务结束后调用，`comic`这一批的任务全部结束且都是`Completed`时发送`ComicDownloadCompleteEvent`
+    ///
+    /// 这一批中只要有任务被取消、失败或章节被屏蔽，就不发送
+    fn finish_comic_batch_if_done(&self, comic: &Comic) {
+        let comic_uuid = &comic.comic.uuid;
+        let all_completed = {
+            let mut comic_batches = self.comic_batches.lock();
+            let Some(batch) = comic_batches.get(comic_uuid) else {
+                return;
+            };
+            if !batch.iter().all(|task| *task.finished_sender.borrow()) {
+                return;
+            }
+            let all_completed = batch
+                .iter()
+                .all(|task| *task.state_sender.borrow() == DownloadTaskState::Completed);
+            comic_batches.remove(comic_uuid);
+            self.saved_metadata_hashes.lock().remove(comic_uuid);
+            all_completed
+        };
+        if !all_completed {
+            return;
+        }
+
+        let comic_title = &comic.comic.name;
+        tracing::debug!(comic_title, "漫画这一批的章节全部下载完成");
+        let _ = ComicDownloadCompleteEvent {
+            comic_path_word: comic.comic.path_word.clone(),
+        }
+        .emit(&self.app);
+
+        let auto_export_on_complete = self.app.get_config().read().auto_export_on_complete;
+        if let Some(archive) = auto_export_on_complete {
+            self.spawn_auto_export(comic, archive);
+        }
+    }
+
+    /// 在阻塞线程中按已下载的元数据导出漫画，导出失败只记录日志，不影响下载任务的状态
+    fn spawn_auto_export(&self, comic: &Comic, archive: Archive) {
+        let app = self.app.clone();
+        let comic_title = comic.comic.name.clone();
+        let comic_path_word = comic.comic.path_word.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            tracing::debug!(comic_title, "开始自动导出{archive:?}");
+            if let Err(err) = export::reexport(&app, &comic_path_word, archive) {
+                let err_title = format!("`{comic_title}`下载完成后自动导出{archive:?}失败");
+                let string_chain = err.to_string_chain();
+                tracing::error!(err_title, message = string_chain);
+            }
+        });
+    }
+
+    pub fn get_risk_control_count(&self) -> u32 {
+        self.risk_control_count.load(Ordering::Relaxed)
+    }
+
+    pub fn reset_risk_control_count(&self) {
+        self.risk_control_count.store(0, Ordering::Relaxed);
+    }
+
+    /// 开始冷却`risk_control_cooldown_sec`秒，已经在冷却且结束得更晚时不做任何事
+    fn start_risk_control_cooldown(&self) {
+        let cooldown_sec = self.app.get_config().read().risk_control_cooldown_sec;
+        let until = Instant::now() + Duration::from_secs(cooldown_sec);
+        {
+            let mut cooldown_until = self.risk_control_cooldown_until.lock();
+            if cooldown_until.is_some_and(|cooldown_until| cooldown_until >= until) {
+                return;
+            }
+            *cooldown_until = Some(until);
+        }
+
+        let _ = RiskControlCooldownEvent {
+            remaining_sec: cooldown_sec,
+        }
+        .emit(&self.app);
+    }
+
+    fn get_risk_control_cooldown_remaining(&self) -> Duration {
+        self.risk_control_cooldown_until
+            .lock()
+            .map_or(Duration::ZERO, |cooldown_until| {
+                cooldown_until.saturating_duration_since(Instant::now())
+            })
+    }
+
+    /// 将已下载漫画的所有图片转码为`target_format`，转码成功后删除原图
+    ///
+    /// 已经是`target_format`的图片会被跳过，可以通过`cancel_transcode`中途取消
+    pub fn transcode(&self, comic: &Comic, target_format: DownloadFormat) -> anyhow::Result<()> {
+        let comic_uuid = comic.comic.uuid.clone();
+        let comic_title = &comic.comic.name;
+
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+        {
+            let mut transcode_cancel_flags = self.transcode_cancel_flags.write();
+            if transcode_cancel_flags.contains_key(&comic_uuid) {
+                return Err(anyhow!("`{comic_title}`已经在转码中"));
+            }
+            transcode_cancel_flags.insert(comic_uuid.clone(), cancel_flag.clone());
+        }
+        let result = self.transcode_img_paths(comic, target_format, &cancel_flag);
+        self.transcode_cancel_flags.write().remove(&comic_uuid);
+
+        if result.is_err() {
+            let _ = TranscodeEvent::Error { uuid: comic_uuid }.emit(&self.app);
+        }
+        result
+    }
+
+    pub fn cancel_transcode(&self, comic_uuid: &str) {
+        if let Some(cancel_flag) = self.transcode_cancel_flags.read().get(comic_uuid) {
+            cancel_flag.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn transcode_img_paths(
+        &self,
+        comic: &Comic,
+        target_format: DownloadFormat,
+        cancel_flag: &AtomicBool,
+    ) -> anyhow::Result<()> {
+        let comic_uuid = &comic.comic.uuid;
+        let comic_title = &comic.comic.name;
+
+        let mut img_paths = Vec::new();
+        for chapter_info in comic.comic.groups.values().flatten() {
+            if chapter_info.is_downloaded != Some(true) {
+                continue;
+            }
+            let Some(chapter_download_dir) = &chapter_info.chapter_download_dir else {
+                continue;
+            };
+            let entries = std::fs::read_dir(chapter_download_dir)
+                .context(format!("读取目录`{}`失败", chapter_download_dir.display()))?;
+            img_paths.extend(
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_img()),
+            );
+        }
+
+        let _ = TranscodeEvent::Start {
+            uuid: comic_uuid.clone(),
+            comic_title: comic_title.clone(),
+            total: img_paths.len() as u32,
+        }
+        .emit(&self.app);
+
+        let extension = target_format.extension();
+        let keep_gif_as_is = self.app.get_config().read().keep_gif_as_is;
+        for (i, img_path) in img_paths.into_iter().enumerate() {
+            if cancel_flag.load(Ordering::Relaxed) {
+                let _ = TranscodeEvent::Cancelled {
+                    uuid: comic_uuid.clone(),
+                }
+                .emit(&self.app);
+                return Ok(());
+            }
+
+            // 开启`keep_gif_as_is`时，gif同样不转码，以免丢失动画
+            let should_skip = img_path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ext == extension || (keep_gif_as_is && ext == "gif"));
+            if !should_skip {
+                transcode_img(&img_path, target_format)
+                    .context(format!("`{comic_title}`转码`{}`失败", img_path.display()))?;
+            }
+
+            let _ = TranscodeEvent::Progress {
+                uuid: comic_uuid.clone(),
+                current: (i + 1) as u32,
+            }
+            .emit(&self.app);
+        }
+
+        let _ = TranscodeEvent::End {
+            uuid: comic_uuid.clone(),
+        }
+        .emit(&self.app);
+
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
@@ -153,6 +881,12 @@ struct DownloadTask {
     state_sender: watch::Sender<DownloadTaskState>,
     downloaded_img_count: Arc<AtomicU32>,
     total_img_count: Arc<AtomicU32>,
+    /// 因图片未全部下载成功而重新排队的次数
+    requeue_count: Arc<AtomicU32>,
+    /// 章节的图片下载任务，放在`download_chapter`之外，这样`process`结束时可以等待它们全部停止
+    img_join_set: Arc<AsyncMutex<JoinSet<()>>>,
+    /// `process`结束并等待所有图片下载任务停止后变为`true`，之后这个任务不会再写入任何文件
+    finished_sender: watch::Sender<bool>,
 }
 
 impl DownloadTask {
@@ -172,9 +906,25 @@ impl DownloadTask {
             .find(|chapter_info| chapter_info.chapter_uuid == chapter_uuid)
             .cloned()
             .context(format!("未找到章节ID为`{chapter_uuid}`的章节信息"))?;
+        if let Some(colliding_chapter_info) = comic.find_chapter_dir_collision(chapter_uuid) {
+            return Err(anyhow!(
+                "章节`{} - {}`与`{} - {}`(章节ID: `{}`)的下载目录都是`{}`，请在`配置`里修改章节目录格式，加入能唯一区分章节的字段(如`{{chapter_uuid}}`或`{{order}}`)",
+                chapter_info.group_name,
+                chapter_info.chapter_title,
+                colliding_chapter_info.group_name,
+                colliding_chapter_info.chapter_title,
+                colliding_chapter_info.chapter_uuid,
+                chapter_info
+                    .chapter_download_dir
+                    .as_ref()
+                    .map(|dir| dir.display().to_string())
+                    .unwrap_or_default(),
+            ));
+        }
 
         let download_manager = app.get_download_manager().inner().clone();
         let (state_sender, _) = watch::channel(DownloadTaskState::Pending);
+        let (finished_sender, _) = watch::channel(false);
 
         let task = Self {
             app,
@@ -184,6 +934,9 @@ impl DownloadTask {
             state_sender,
             downloaded_img_count: Arc::new(AtomicU32::new(0)),
             total_img_count: Arc::new(AtomicU32::new(0)),
+            requeue_count: Arc::new(AtomicU32::new(0)),
+            img_join_set: Arc::new(AsyncMutex::new(JoinSet::new())),
+            finished_sender,
         };
 
         Ok(task)
@@ -192,18 +945,32 @@ impl DownloadTask {
     async fn process(self) {
         self.emit_download_task_create_event();
 
-        let download_chapter_task = self.download_chapter();
-        tokio::pin!(download_chapter_task);
+        // 用`Box::pin`而不是`tokio::pin!`，这样结束时可以提前drop
+        let mut download_chapter_task = Box::pin(self.download_chapter());
 
         let mut state_receiver = self.state_sender.subscribe();
         state_receiver.mark_changed();
+        let mut global_paused_receiver = self.download_manager.global_paused_sender.subscribe();
         let mut permit = None;
         loop {
             let state_is_downloading = *state_receiver.borrow() == DownloadTaskState::Downloading;
             let state_is_pending = *state_receiver.borrow() == DownloadTaskState::Pending;
+            let global_paused = *global_paused_receiver.borrow();
+            // 全局暂停期间会释放permit，恢复后`Downloading`状态的任务需要重新获取permit
+            let need_permit = state_is_pending || (state_is_downloading && permit.is_none());
             tokio::select! {
-                () = &mut download_chapter_task, if state_is_downloading && permit.is_some() => break,
-                control_flow = self.acquire_chapter_permit(&mut permit), if state_is_pending => {
+                () = &mut download_chapter_task, if state_is_downloading && permit.is_some() && !global_paused => {
+                    if *self.state_sender.borrow() != DownloadTaskState::Pending {
+                        break;
+                    }
+                    // 图片未全部下载成功，释放permit后重新排队，临时下载目录中已下载的图片会被跳过
+                    permit = None;
+                    self.requeue_count.fetch_add(1, Ordering::Relaxed);
+                    self.downloaded_img_count.store(0, Ordering::Relaxed);
+                    self.total_img_count.store(0, Ordering::Relaxed);
+                    download_chapter_task.set(self.download_chapter());
+                },
+                control_flow = self.acquire_chapter_permit(&mut permit), if need_permit && !global_paused => {
                     match control_flow {
                         ControlFlow::Continue(()) => continue,
                         ControlFlow::Break(()) => break,
@@ -214,28 +981,59 @@ impl DownloadTask {
                         ControlFlow::Continue(()) => continue,
                         ControlFlow::Break(()) => break,
                     }
+                },
+                _ = global_paused_receiver.changed() => {
+                    if *global_paused_receiver.borrow() {
+                        let comic_title = &self.comic.comic.name;
+                        let chapter_title = &self.chapter_info.chapter_title;
+                        tracing::debug!(comic_title, chapter_title, "章节全局暂停中");
+                        if let Some(permit) = permit.take() {
+                            drop(permit);
+                        }
+                    }
                 }
             }
         }
+        // 先drop掉`download_chapter_task`，释放`img_join_set`的锁
+        drop(download_chapter_task);
+        // 仅drop`JoinSet`只会通知图片下载任务取消，不会等待它们停止
+        // 正在其他线程同步写入文件的任务要等到下一个`.await`才会停止，所以要等它们全部停止后才算结束
+        self.img_join_set.lock().await.shutdown().await;
+        self.finished_sender.send_replace(true);
+        self.download_manager
+            .finish_comic_batch_if_done(&self.comic);
     }
 
     async fn download_chapter(&self) {
         let comic_title = &self.comic.comic.name;
         let chapter_title = &self.chapter_info.chapter_title;
-        if let Err(err) = self.comic.save_metadata() {
+        if let Err(err) = self.download_manager.save_comic_metadata(&self.comic) {
             let err_title = format!("`{comic_title}`保存元数据失败");
             let string_chain = err.to_string_chain();
             tracing::error!(err_title, message = string_chain);
 
-            self.set_state(DownloadTaskState::Failed);
+            self.set_failed(&err_title, &string_chain);
             self.emit_download_task_update_event();
 
             return;
         }
+        // 封面下载失败不影响章节下载
+        let download_cover = self.app.get_config().read().download_cover;
+        if download_cover {
+            if let Err(err) = self.download_cover().await {
+                let err_title = format!("`{comic_title}`下载封面失败");
+                let string_chain = err.to_string_chain();
+                tracing::error!(err_title, message = string_chain);
+            }
+        }
         // 获取章节图片URL列表
         let Some(url_and_index_pairs) = self.get_url_and_index_pairs().await else {
             return;
         };
+        // 磁盘空间不足时直接失败，避免下载到一半才在保存图片时报错
+        if !self.check_disk_space(url_and_index_pairs.len()) {
+            return;
+        }
         // 记录总共需要下载的图片数量
         #[allow(clippy::cast_possible_truncation)]
         self.total_img_count
@@ -247,15 +1045,17 @@ impl DownloadTask {
         // 清理临时下载目录中与`config.download_format`对不上的文件
         self.clean_temp_download_dir(&temp_download_dir);
 
-        let mut join_set = JoinSet::new();
-        for (url, index) in url_and_index_pairs {
-            let url = url.clone();
+        let img_stems = self.get_img_stems(&url_and_index_pairs);
+
+        let mut join_set = self.img_join_set.lock().await;
+        for ((url, _), img_stem) in url_and_index_pairs.into_iter().zip(img_stems) {
             let temp_download_dir = temp_download_dir.clone();
             // 创建下载任务
-            let download_img_task = DownloadImgTask::new(self, url, index, temp_download_dir);
+            let download_img_task = DownloadImgTask::new(self, url, img_stem, temp_download_dir);
             join_set.spawn(download_img_task.process());
         }
-        join_set.join_all().await;
+        while join_set.join_next().await.is_some() {}
+        drop(join_set);
         tracing::trace!(comic_title, chapter_title, "所有图片下载任务完成");
         // 如果DownloadManager所有图片全部都已下载(无论成功或失败)，则清空下载进度
         let downloaded_img_count = self.downloaded_img_count.load(Ordering::Relaxed);
@@ -265,9 +1065,27 @@ impl DownloadTask {
             let err_title = format!("`{comic_title} - {chapter_title}`下载不完整");
             let err_msg =
                 format!("总共有`{total_img_count}`张图片，但只下载了`{downloaded_img_count}`张");
+
+            let max_requeue_count = self
+                .app
+                .get_config()
+                .read()
+                .incomplete_chapter_requeue_count;
+            let requeue_count = self.requeue_count.load(Ordering::Relaxed);
+            if requeue_count < max_requeue_count {
+                // 保留临时下载目录，重新排队后只需要下载缺失的图片
+                let requeue_msg = format!("第{}次重新排队", requeue_count + 1);
+                tracing::warn!(err_title, message = err_msg, requeue_msg);
+
+                self.set_state(DownloadTaskState::Pending);
+                self.emit_download_task_update_event();
+
+                return;
+            }
+
             tracing::error!(err_title, message = err_msg);
 
-            self.set_state(DownloadTaskState::Failed);
+            self.set_failed(&err_title, &err_msg);
             self.emit_download_task_update_event();
 
             return;
@@ -278,7 +1096,7 @@ impl DownloadTask {
             let string_chain = err.to_string_chain();
             tracing::error!(err_title, message = string_chain);
 
-            self.set_state(DownloadTaskState::Failed);
+            self.set_failed(&err_title, &string_chain);
             self.emit_download_task_update_event();
 
             return;
@@ -288,8 +1106,21 @@ impl DownloadTask {
             let err_title = format!("`{comic_title} - {chapter_title}`保存章节元数据失败");
             let string_chain = err.to_string_chain();
             tracing::error!(err_title, message = string_chain);
+        } else {
+            self.emit_download_task_finished_event(downloaded_img_count);
+        }
+
+        let create_progress_file = self.app.get_config().read().create_progress_file;
+        if create_progress_file {
+            if let Err(err) = self.create_progress_file() {
+                let err_title = format!("`{comic_title} - {chapter_title}`创建阅读进度文件失败");
+                let string_chain = err.to_string_chain();
+                tracing::error!(err_title, message = string_chain);
+            }
         }
 
+        utils::invalidate_library_cache(&self.app);
+
         self.sleep_between_chapter().await;
         tracing::info!(comic_title, chapter_title, "章节下载成功");
 
@@ -297,30 +1128,156 @@ impl DownloadTask {
         self.emit_download_task_update_event();
     }
 
+    /// 将漫画封面保存为漫画目录中的`cover.<ext>`，封面已存在或没有封面URL时跳过
+    async fn download_cover(&self) -> anyhow::Result<()> {
+        let cover_url = &self.comic.comic.cover;
+        if cover_url.is_empty() {
+            return Ok(());
+        }
+        let comic_download_dir = self
+            .comic
+            .comic_download_dir
+            .as_ref()
+            .context("`comic_download_dir`字段为`None`")?;
+        if Comic::get_cover_path(comic_download_dir).is_some() {
+            return Ok(());
+        }
+
+        let (img_data, img_format) = self
+            .app
+            .get_copy_client()
+            .get_img_data_and_format(cover_url)
+            .await?;
+        let extension = img_format
+            .and_then(|img_format| img_format.extensions_str().first().copied())
+            .context(format!("无法识别封面 {cover_url} 的格式"))?;
+
+        let cover_path = comic_download_dir.join(format!("cover.{extension}"));
+        // 同一漫画的多个章节可能同时下载封面，用原子写入避免文件内容交错
+        utils::write_atomically(&cover_path, img_data)
+            .context(format!("写入文件`{}`失败", cover_path.display()))?;
+
+        Ok(())
+    }
+
+    /// 在章节目录中创建初始的阅读进度文件`progress.json`，已存在则不覆盖
+    ///
+    /// 导出时只会打包`is_img`为`true`的文件，所以这个文件不会被打包进cbz或pdf
+    fn create_progress_file(&self) -> anyhow::Result<()> {
+        let chapter_download_dir = self
+            .chapter_info
+            .chapter_download_dir
+            .as_ref()
+            .context("`chapter_download_dir`字段为`None`")?;
+        let progress_path = chapter_download_dir.join("progress.json");
+        if progress_path.exists() {
+            return Ok(());
+        }
+
+        let progress_json = serde_json::to_string_pretty(&serde_json::json!({ "page": 0 }))
+            .context("将阅读进度序列化为json失败")?;
+        std::fs::write(&progress_path, progress_json)
+            .context(format!("写入文件`{}`失败", progress_path.display()))?;
+
+        Ok(())
+    }
+
+    /// 检查`download_dir`所在磁盘的剩余空间是否足够保存`img_count`张图片，不够时将任务标记为`Failed`并返回`false`
+    ///
+    /// 按每张图片`ESTIMATED_IMG_BYTES`估算，再加上`disk_space_safety_margin_mb`，无法获取剩余空间时不做检查
+    fn check_disk_space(&self, img_count: usize) -> bool {
+        let comic_title = &self.comic.comic.name;
+        let chapter_title = &self.chapter_info.chapter_title;
+        let (download_dir, safety_margin_mb) = {
+            let config = self.app.get_config();
+            let config = config.read();
+            (
+                config.download_dir.clone(),
+                config.disk_space_safety_margin_mb,
+            )
+        };
+        // 下载目录可能还没创建，用最近的已存在的上级目录查询
+        let Some(existing_dir) = download_dir.ancestors().find(|dir| dir.exists()) else {
+            return true;
+        };
+        let available_bytes = match fs4::available_space(existing_dir) {
+            Ok(available_bytes) => available_bytes,
+            Err(err) => {
+                let err_title = format!("`{comic_title} - {chapter_title}`获取磁盘剩余空间失败");
+                let string_chain = anyhow::Error::from(err).to_string_chain();
+                tracing::warn!(err_title, message = string_chain);
+                return true;
+            }
+        };
+        let required_bytes =
+            img_count as u64 * ESTIMATED_IMG_BYTES + safety_margin_mb * 1024 * 1024;
+        if available_bytes >= required_bytes {
+            return true;
+        }
+
+        let err_title = format!("`{comic_title} - {chapter_title}`磁盘空间不足");
+        let err_msg = format!(
+            "下载目录`{}`所在磁盘只剩{}MB，至少需要{}MB",
+            download_dir.display(),
+            available_bytes / 1024 / 1024,
+            required_bytes / 1024 / 1024
+        );
+        tracing::error!(err_title, message = err_msg);
+        let _ = DownloadInsufficientSpaceEvent {
+            chapter_uuid: self.chapter_info.chapter_uuid.clone(),
+            available_bytes,
+            required_bytes,
+        }
+        .emit(&self.app);
+
+        self.set_failed(&err_title, &err_msg);
+        self.emit_download_task_update_event();
+
+        false
+    }
+
     async fn get_url_and_index_pairs(&self) -> Option<Vec<(String, i64)>> {
         let comic_title = &self.comic.comic.name;
         let chapter_title = &self.chapter_info.chapter_title;
 
         let chapter_resp_data = match self.get_chapter_with_retry().await {
             Ok(data) => data,
+            Err(CopyMangaError::Unavailable(err)) => {
+                let unavailable_action = self.app.get_config().read().chapter_unavailable_action;
+                let err_title = format!("漫画 {comic_title} 的 {chapter_title} 已被删除或屏蔽");
+                let string_chain = err.to_string_chain();
+                let state = match unavailable_action {
+                    ChapterUnavailableAction::Skip => {
+                        tracing::warn!(err_title, message = string_chain);
+                        DownloadTaskState::Banned
+                    }
+                    ChapterUnavailableAction::Fail => {
+                        tracing::error!(err_title, message = string_chain);
+                        DownloadTaskState::Failed
+                    }
+                };
+
+                self.set_state_with_err(state, Some((&err_title, &string_chain)));
+                self.emit_download_task_update_event();
+
+                return None;
+            }
             Err(err) => {
+                let err = anyhow::Error::from(err);
                 let err_title = format!("获取漫画 {comic_title} 的 {chapter_title} 信息失败");
                 let string_chain = err.to_string_chain();
                 tracing::error!(err_title, message = string_chain);
 
-                self.set_state(DownloadTaskState::Failed);
+                self.set_failed(&err_title, &string_chain);
                 self.emit_download_task_update_event();
 
                 return None;
             }
         };
 
-        let urls: Vec<String> = chapter_resp_data
-            .chapter
-            .contents
-            .into_iter()
-            .map(|content| content.url.replace(".c800x.", ".c1500x."))
-            .collect();
+        let urls = self
+            .comic
+            .get_img_urls(&self.app, &chapter_resp_data.chapter);
 
         let url_and_index_pairs: Vec<(String, i64)> = urls
             .into_iter()
@@ -334,6 +1291,41 @@ impl DownloadTask {
         Some(url_and_index_pairs)
     }
 
+    /// 生成每张图片的文件名(不含扩展名)，与`url_and_index_pairs`一一对应
+    ///
+    /// 用`config.image_name_fmt`生成，为空时使用`DEFAULT_IMAGE_NAME_FMT`，格式错误时退回`DEFAULT_IMAGE_NAME_FMT`
+    fn get_img_stems(&self, url_and_index_pairs: &[(String, i64)]) -> Vec<String> {
+        let (image_name_fmt, sanitize_mode) = {
+            let config = self.app.get_config();
+            let config = config.read();
+            (config.image_name_fmt.clone(), config.filename_sanitize_mode)
+        };
+        let image_name_fmt = if image_name_fmt.is_empty() {
+            DEFAULT_IMAGE_NAME_FMT
+        } else {
+            &image_name_fmt
+        };
+
+        match format_img_stems(image_name_fmt, url_and_index_pairs, sanitize_mode) {
+            Ok(img_stems) => img_stems,
+            Err(err) => {
+                let comic_title = &self.comic.comic.name;
+                let chapter_title = &self.chapter_info.chapter_title;
+                let string_chain = err.to_string_chain();
+                tracing::warn!(
+                    comic_title,
+                    chapter_title,
+                    message = string_chain,
+                    "无法使用`image_name_fmt`生成图片文件名，改用默认格式"
+                );
+                url_and_index_pairs
+                    .iter()
+                    .map(|(_, index)| index_img_stem(*index))
+                    .collect()
+            }
+        }
+    }
+
     fn create_temp_download_dir(&self) -> Option<PathBuf> {
         let comic_title = &self.comic.comic.name;
         let chapter_title = &self.chapter_info.chapter_title;
@@ -345,7 +1337,7 @@ impl DownloadTask {
                 let string_chain = err.to_string_chain();
                 tracing::error!(err_title, message = string_chain);
 
-                self.set_state(DownloadTaskState::Failed);
+                self.set_failed(&err_title, &string_chain);
                 self.emit_download_task_update_event();
 
                 return None;
@@ -360,7 +1352,7 @@ impl DownloadTask {
             let string_chain = err.to_string_chain();
             tracing::error!(err_title, message = string_chain);
 
-            self.set_state(DownloadTaskState::Failed);
+            self.set_failed(&err_title, &string_chain);
             self.emit_download_task_update_event();
 
             return None;
@@ -376,7 +1368,8 @@ impl DownloadTask {
         Some(temp_download_dir)
     }
 
-    async fn get_chapter_with_retry(&self) -> anyhow::Result<GetChapterRespData> {
+    /// 章节已被删除或屏蔽时不重试，直接返回`CopyMangaError::Unavailable`
+    async fn get_chapter_with_retry(&self) -> CopyMangaResult<GetChapterRespData> {
         let comic_path_word = &self.chapter_info.comic_path_word;
         let chapter_uuid = &self.chapter_info.chapter_uuid;
 
@@ -385,27 +1378,26 @@ impl DownloadTask {
         loop {
             match copy_client.get_chapter(comic_path_word, chapter_uuid).await {
                 Ok(data) => return Ok(data),
-                Err(CopyMangaError::Anyhow(err)) => return Err(err),
+                Err(err @ (CopyMangaError::Anyhow(_) | CopyMangaError::Unavailable(_))) => {
+                    return Err(err)
+                }
                 Err(CopyMangaError::RiskControl(RiskControlError::Register(_))) => {
-                    const RETRY_WAIT_TIME: u32 = 60;
-                    for i in 1..=RETRY_WAIT_TIME {
-                        let _ = DownloadControlRiskEvent {
-                            chapter_uuid: chapter_uuid.clone(),
-                            retry_after: RETRY_WAIT_TIME - i,
-                        }
-                        .emit(&self.app);
-                        sleep(Duration::from_secs(1)).await;
-                    }
+                    self.download_manager
+                        .risk_control_count
+                        .fetch_add(1, Ordering::Relaxed);
+                    // 让其他任务也暂停请求章节，一起等待冷却结束
+                    self.download_manager.start_risk_control_cooldown();
+                    self.wait_for_risk_control_cooldown().await;
                 }
                 Err(err) => {
-                    // 随机等待1000-5000ms
+                    // 其他风控错误或网络超时，随机等待1000-5000ms后重试
                     let wait_time = 1000 + rand::random::<u64>() % 4000;
                     sleep(Duration::from_millis(wait_time)).await;
                     if retry_count < 5 {
                         retry_count += 1;
                         continue;
                     }
-                    return Err(err.into());
+                    return Err(err);
                 }
             }
         }
@@ -429,18 +1421,38 @@ impl DownloadTask {
             }
         };
 
-        let download_format = self.app.get_config().read().download_format;
+        let mut entries = entries.filter_map(Result::ok).peekable();
+        if entries.peek().is_none() {
+            // 全新下载时临时目录为空，无需清理
+            return;
+        }
+
+        let (download_format, gif_save_mode) = {
+            let config = self.app.get_config();
+            let config = config.read();
+            (
+                config.get_download_format(&self.comic.comic.path_word),
+                GifSaveMode::from_config(&config),
+            )
+        };
         let extension = download_format.extension();
-        for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
-            // path有扩展名，且能转换为utf8，并与`config.download_format`一致或是gif，则保留
-            let should_keep = path
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .is_some_and(|ext| ext == extension);
+        let gif_extension = gif_save_mode.extension();
+        for entry in entries {
+            // 只用`file_name`判断扩展名，不需要stat文件，断点续传时大部分文件都会被保留
+            let file_name = entry.file_name();
+            // 文件名有扩展名，且能转换为utf8，并与`config.download_format`一致，则保留
+            // 按`gif_save_mode`单独保存的gif，以及无法识别格式时保存的原始数据也保留
+            let file_path = Path::new(&file_name);
+            let should_keep = file_path.is_unrecognized_img()
+                || file_path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| ext == extension || gif_extension == Some(ext));
             if should_keep {
                 continue;
             }
             // 否则删除文件
+            let path = entry.path();
             if let Err(err) = std::fs::remove_file(&path).map_err(anyhow::Error::from) {
                 let err_title =
                     format!("`{comic_title}`删除临时下载目录的`{}`失败", path.display());
@@ -507,13 +1519,15 @@ impl DownloadTask {
                     let string_chain = err.to_string_chain();
                     tracing::error!(err_title, message = string_chain);
 
-                    self.set_state(DownloadTaskState::Failed);
+                    self.set_failed(&err_title, &string_chain);
                     self.emit_download_task_update_event();
 
                     return ControlFlow::Break(());
                 }
             },
         };
+        // 其他任务触发了风控时，等冷却结束再开始下载
+        self.wait_for_risk_control_cooldown().await;
         // 如果当前任务状态不是`Pending`，则不将任务状态设置为`Downloading`
         if *self.state_sender.borrow() != DownloadTaskState::Pending {
             return ControlFlow::Continue(());
@@ -532,6 +1546,22 @@ impl DownloadTask {
         ControlFlow::Continue(())
     }
 
+    /// 等待`DownloadManager`的风控冷却结束，等待期间每秒发送一次`DownloadControlRiskEvent`
+    async fn wait_for_risk_control_cooldown(&self) {
+        loop {
+            let remaining = self.download_manager.get_risk_control_cooldown_remaining();
+            if remaining.is_zero() {
+                return;
+            }
+            let _ = DownloadControlRiskEvent {
+                chapter_uuid: self.chapter_info.chapter_uuid.clone(),
+                retry_after: u32::try_from(remaining.as_secs()).unwrap_or(u32::MAX),
+            }
+            .emit(&self.app);
+            sleep(remaining.min(Duration::from_secs(1))).await;
+        }
+    }
+
     fn handle_state_change<'a>(
         &'a self,
         permit: &mut Option<SemaphorePermit<'a>>,
@@ -573,14 +1603,44 @@ impl DownloadTask {
     }
 
     fn set_state(&self, state: DownloadTaskState) {
+        self.set_state_with_err(state, None);
+    }
+
+    fn set_failed(&self, err_title: &str, err_message: &str) {
+        self.set_state_with_err(DownloadTaskState::Failed, Some((err_title, err_message)));
+    }
+
+    /// 设置状态，`err`为`(err_title, err_message)`，会与状态变化一起写入`download_events.jsonl`
+    fn set_state_with_err(&self, state: DownloadTaskState, err: Option<(&str, &str)>) {
         let comic_title = &self.comic.comic.name;
         let chapter_title = &self.chapter_info.chapter_title;
 
+        let old_state = *self.state_sender.borrow();
         if let Err(err) = self.state_sender.send(state).map_err(anyhow::Error::from) {
             let err_title = format!("`{comic_title} - {chapter_title}`发送状态`{state:?}`失败");
             let string_chain = err.to_string_chain();
             tracing::error!(err_title, message = string_chain);
         }
+        if old_state == state {
+            return;
+        }
+
+        let record = DownloadEventRecord {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            chapter_uuid: &self.chapter_info.chapter_uuid,
+            comic_path_word: &self.chapter_info.comic_path_word,
+            old_state,
+            new_state: state,
+            downloaded_img_count: self.downloaded_img_count.load(Ordering::Relaxed),
+            total_img_count: self.total_img_count.load(Ordering::Relaxed),
+            err_title: err.map(|(err_title, _)| err_title),
+            err_message: err.map(|(_, err_message)| err_message),
+        };
+        if let Err(err) = self.download_manager.append_download_event(&record) {
+            let err_title = format!("`{comic_title} - {chapter_title}`写入下载事件日志失败");
+            let string_chain = err.to_string_chain();
+            tracing::error!(err_title, message = string_chain);
+        }
     }
 
     fn emit_download_task_update_event(&self) {
@@ -593,6 +1653,18 @@ impl DownloadTask {
         .emit(&self.app);
     }
 
+    fn emit_download_task_finished_event(&self, img_count: u32) {
+        let Some(chapter_download_dir) = self.chapter_info.chapter_download_dir.clone() else {
+            return;
+        };
+        let _ = DownloadTaskEvent::Finished {
+            chapter_uuid: self.chapter_info.chapter_uuid.clone(),
+            chapter_download_dir,
+            img_count,
+        }
+        .emit(&self.app);
+    }
+
     fn emit_download_task_create_event(&self) {
         let _ = DownloadTaskEvent::Create {
             state: *self.state_sender.borrow(),
@@ -611,7 +1683,8 @@ struct DownloadImgTask {
     download_manager: DownloadManager,
     download_task: DownloadTask,
     url: String,
-    index: i64,
+    /// 保存的文件名(不含扩展名)
+    img_stem: String,
     temp_download_dir: PathBuf,
 }
 
@@ -619,7 +1692,7 @@ impl DownloadImgTask {
     pub fn new(
         download_task: &DownloadTask,
         url: String,
-        index: i64,
+        img_stem: String,
         temp_download_dir: PathBuf,
     ) -> Self {
         DownloadImgTask {
@@ -627,7 +1700,7 @@ impl DownloadImgTask {
             download_manager: download_task.download_manager.clone(),
             download_task: download_task.clone(),
             url,
-            index,
+            img_stem,
             temp_download_dir,
         }
     }
@@ -638,13 +1711,15 @@ impl DownloadImgTask {
 
         let mut state_receiver = self.download_task.state_sender.subscribe();
         state_receiver.mark_changed();
+        let mut global_paused_receiver = self.download_manager.global_paused_sender.subscribe();
         let mut permit = None;
 
         loop {
             let state_is_downloading = *state_receiver.borrow() == DownloadTaskState::Downloading;
+            let global_paused = *global_paused_receiver.borrow();
             tokio::select! {
-                () = &mut download_img_task, if state_is_downloading && permit.is_some() => break,
-                control_flow = self.acquire_img_permit(&mut permit), if state_is_downloading && permit.is_none() => {
+                () = &mut download_img_task, if state_is_downloading && permit.is_some() && !global_paused => break,
+                control_flow = self.acquire_img_permit(&mut permit), if state_is_downloading && permit.is_none() && !global_paused => {
                     match control_flow {
                         ControlFlow::Continue(()) => continue,
                         ControlFlow::Break(()) => break,
@@ -655,6 +1730,13 @@ impl DownloadImgTask {
                         ControlFlow::Continue(()) => continue,
                         ControlFlow::Break(()) => break,
                     }
+                },
+                _ = global_paused_receiver.changed() => {
+                    if *global_paused_receiver.borrow() {
+                        if let Some(permit) = permit.take() {
+                            drop(permit);
+                        }
+                    }
                 }
             }
         }
@@ -665,12 +1747,26 @@ impl DownloadImgTask {
         let comic_title = &self.download_task.comic.comic.name;
         let chapter_title = &self.download_task.chapter_info.chapter_title;
 
-        let download_format = self.app.get_config().read().download_format;
+        let (download_format, gif_save_mode) = {
+            let config = self.app.get_config();
+            let config = config.read();
+            (
+                config.get_download_format(&self.download_task.comic.comic.path_word),
+                GifSaveMode::from_config(&config),
+            )
+        };
         let extension = download_format.extension();
         let save_path = self
             .temp_download_dir
-            .join(format!("{:03}.{extension}", self.index + 1));
-        if save_path.exists() {
+            .join(format!("{}.{extension}", self.img_stem));
+        let gif_save_path = gif_save_mode
+            .extension()
+            .map(|gif_extension| save_path.with_extension(gif_extension));
+        let bin_save_path = save_path.with_extension("bin");
+        if save_path.exists()
+            || gif_save_path.as_ref().is_some_and(|path| path.exists())
+            || bin_save_path.exists()
+        {
             // 如果图片已经存在，则直接跳过下载
             self.download_task
                 .downloaded_img_count
@@ -684,8 +1780,7 @@ impl DownloadImgTask {
 
         tracing::trace!(url, comic_title, chapter_title, "开始下载图片");
 
-        let copy_client = self.app.get_copy_client();
-        let (img_data, img_format) = match copy_client.get_img_data_and_format(url).await {
+        let (img_data, img_format) = match self.get_img_data_and_format_with_retry().await {
             Ok(data_and_format) => data_and_format,
             Err(err) => {
                 let err_title = format!("下载图片`{url}`失败");
@@ -699,13 +1794,48 @@ impl DownloadImgTask {
         tracing::trace!(url, comic_title, chapter_title, "图片成功下载到内存");
 
         // 保存图片
-        let target_format = download_format.to_image_format();
-        if let Err(err) = save_img(&save_path, target_format, &img_data, img_format) {
-            let err_title = format!("保存图片`{url}`失败");
-            let string_chain = err.to_string_chain();
-            tracing::error!(err_title, message = string_chain);
-            return;
-        }
+        let save_path = if let Some(img_format) = img_format {
+            let save_result = save_recognized_img(
+                save_path,
+                gif_save_mode,
+                download_format,
+                &img_data,
+                img_format,
+            );
+            match save_result {
+                Ok(save_path) => save_path,
+                Err(err) => {
+                    let err_title = format!("保存图片`{url}`失败");
+                    let string_chain = err.to_string_chain();
+                    tracing::error!(err_title, message = string_chain);
+                    return;
+                }
+            }
+        } else {
+            let save_unrecognized_img_as_bin =
+                self.app.get_config().read().save_unrecognized_img_as_bin;
+            if !save_unrecognized_img_as_bin {
+                let err_title = format!("保存图片`{url}`失败");
+                tracing::error!(err_title, message = "无法识别图片格式");
+                return;
+            }
+            // 无法识别格式时保存原始数据，以免丢失这一页
+            if let Err(err) = std::fs::write(&bin_save_path, &img_data).map_err(anyhow::Error::from)
+            {
+                let err_title = format!("保存图片`{url}`的原始数据失败");
+                let string_chain = err.to_string_chain();
+                tracing::error!(err_title, message = string_chain);
+                return;
+            }
+            tracing::warn!(
+                url,
+                comic_title,
+                chapter_title,
+                "无法识别图片格式，已将原始数据保存到`{}`",
+                bin_save_path.display()
+            );
+            bin_save_path
+        };
 
         tracing::trace!(
             url,
@@ -730,6 +1860,60 @@ impl DownloadImgTask {
         sleep(Duration::from_secs(img_download_interval_sec)).await;
     }
 
+    /// 响应的内容不是图片或网络超时时，随机等待1000-5000ms后重试，最多重试5次
+    ///
+    /// 图片服务器限流(429)时，开始全局的风控冷却，等待冷却结束与指数退避中更久的那个后重试，最多重试5次
+    ///
+    /// 两类错误分别计数，见`ImgRetryCounts`
+    async fn get_img_data_and_format_with_retry(
+        &self,
+    ) -> anyhow::Result<(Bytes, Option<ImageFormat>)> {
+        let url = &self.url;
+
+        let copy_client = self.app.get_copy_client();
+        let mut retry_counts = ImgRetryCounts::default();
+        loop {
+            let err = match copy_client.get_img_data_and_format(url).await {
+                Ok(data_and_format) => return Ok(data_and_format),
+                Err(err) => err,
+            };
+            match retry_counts.next_retry(&err) {
+                ImgRetry::AfterRandomDelay => {
+                    tracing::warn!(
+                        url,
+                        retry_count = retry_counts.transient,
+                        message = err.to_string_chain(),
+                        "下载图片失败，稍后重试"
+                    );
+                    let wait_time = 1000 + rand::random::<u64>() % 4000;
+                    sleep(Duration::from_millis(wait_time)).await;
+                }
+                ImgRetry::RateLimited { backoff } => {
+                    // 限流时其他章节也不应该开始下载，所以同时开始全局的风控冷却
+                    self.download_manager.start_risk_control_cooldown();
+                    let wait_time = self
+                        .download_manager
+                        .get_risk_control_cooldown_remaining()
+                        .max(backoff);
+                    tracing::warn!(
+                        url,
+                        retry_count = retry_counts.rate_limited,
+                        wait_sec = wait_time.as_secs(),
+                        message = err.to_string_chain(),
+                        "图片服务器限流，稍后重试"
+                    );
+                    let _ = ImgRateLimitedEvent {
+                        url: url.clone(),
+                        wait_sec: wait_time.as_secs(),
+                    }
+                    .emit(&self.app);
+                    sleep(wait_time).await;
+                }
+                ImgRetry::GiveUp => return Err(err),
+            }
+        }
+    }
+
     async fn acquire_img_permit<'a>(
         &'a self,
         permit: &mut Option<SemaphorePermit<'a>>,
@@ -764,77 +1948,453 @@ impl DownloadImgTask {
         ControlFlow::Continue(())
     }
 
-    fn handle_state_change<'a>(
-        &'a self,
-        permit: &mut Option<SemaphorePermit<'a>>,
-        state_receiver: &mut watch::Receiver<DownloadTaskState>,
-    ) -> ControlFlow<()> {
-        let url = &self.url;
-        let comic_title = &self.download_task.comic.comic.name;
-        let chapter_title = &self.download_task.chapter_info.chapter_title;
+    fn handle_state_change<'a>(
+        &'a self,
+        permit: &mut Option<SemaphorePermit<'a>>,
+        state_receiver: &mut watch::Receiver<DownloadTaskState>,
+    ) -> ControlFlow<()> {
+        let url = &self.url;
+        let comic_title = &self.download_task.comic.comic.name;
+        let chapter_title = &self.download_task.chapter_info.chapter_title;
+
+        let state = *state_receiver.borrow();
+        match state {
+            DownloadTaskState::Paused => {
+                tracing::trace!(comic_title, chapter_title, url, "图片暂停下载");
+                if let Some(permit) = permit.take() {
+                    drop(permit);
+                }
+                ControlFlow::Continue(())
+            }
+            DownloadTaskState::Cancelled => {
+                tracing::trace!(comic_title, chapter_title, url, "图片取消下载");
+                ControlFlow::Break(())
+            }
+            _ => ControlFlow::Continue(()),
+        }
+    }
+}
+
+/// 下载图片失败后的处理方式
+#[derive(Debug, PartialEq)]
+enum ImgRetry {
+    /// 随机等待一段时间后重试
+    AfterRandomDelay,
+    /// 图片服务器限流，至少等待`backoff`后重试
+    RateLimited { backoff: Duration },
+    /// 不再重试，返回错误
+    GiveUp,
+}
+
+/// 下载图片的重试次数，内容不是图片或网络超时(`transient`)与限流(`rate_limited`)分别计数，各自最多重试5次
+///
+/// 限流的重试不会占用`transient`的次数，反之亦然
+#[derive(Debug, Default)]
+struct ImgRetryCounts {
+    transient: u32,
+    rate_limited: u32,
+}
+
+impl ImgRetryCounts {
+    const MAX_RETRY_COUNT: u32 = 5;
+
+    /// 根据`err`决定是否重试，需要重试时增加对应的计数
+    fn next_retry(&mut self, err: &anyhow::Error) -> ImgRetry {
+        if err.is::<NotImageError>() || err.is::<NetworkTimeoutError>() {
+            if self.transient >= Self::MAX_RETRY_COUNT {
+                return ImgRetry::GiveUp;
+            }
+            self.transient += 1;
+            return ImgRetry::AfterRandomDelay;
+        }
+        if err.is::<ImgRateLimitedError>() {
+            if self.rate_limited >= Self::MAX_RETRY_COUNT {
+                return ImgRetry::GiveUp;
+            }
+            self.rate_limited += 1;
+            // 10s, 20s, 40s, 80s, 160s
+            let backoff = Duration::from_secs(5 << self.rate_limited);
+            return ImgRetry::RateLimited { backoff };
+        }
+        ImgRetry::GiveUp
+    }
+}
+
+/// 保存识别出格式的图片，返回实际保存的路径
+///
+/// gif可能是动图，转换为`download_format`会丢失动画，所以按`gif_save_mode`单独保存到扩展名为`gif_save_mode.extension()`的路径
+fn save_recognized_img(
+    save_path: PathBuf,
+    gif_save_mode: GifSaveMode,
+    download_format: DownloadFormat,
+    img_data: &Bytes,
+    img_format: ImageFormat,
+) -> anyhow::Result<PathBuf> {
+    let gif_save_path = gif_save_mode
+        .extension()
+        .map(|gif_extension| save_path.with_extension(gif_extension));
+    match (gif_save_path, img_format) {
+        #[cfg(feature = "animated-webp")]
+        (Some(gif_save_path), ImageFormat::Gif) if gif_save_mode == GifSaveMode::AnimatedWebp => {
+            save_gif_as_animated_webp(&gif_save_path, img_data)?;
+            Ok(gif_save_path)
+        }
+        (Some(gif_save_path), ImageFormat::Gif) => {
+            std::fs::write(&gif_save_path, img_data)
+                .context(format!("将图片数据写入`{}`失败", gif_save_path.display()))?;
+            Ok(gif_save_path)
+        }
+        _ => {
+            save_img(&save_path, download_format, img_data, img_format)?;
+            Ok(save_path)
+        }
+    }
+}
+
+fn save_img(
+    save_path: &Path,
+    target_format: DownloadFormat,
+    src_img_data: &Bytes,
+    src_format: ImageFormat,
+) -> anyhow::Result<()> {
+    if target_format.to_image_format() == Some(src_format) {
+        // 如果target_format与src_format匹配，则直接保存
+        std::fs::write(save_path, src_img_data)
+            .context(format!("将图片数据写入`{}`失败", save_path.display()))?;
+        return Ok(());
+    }
+    // 如果target_format与src_format不匹配，则需要转换格式
+    let converted_data = convert_img(src_img_data, src_format, target_format)?;
+
+    std::fs::write(save_path, &converted_data)
+        .context(format!("将图片数据写入`{}`失败", save_path.display()))?;
+
+    Ok(())
+}
+
+/// 用`image_name_fmt`生成每张图片的文件名(不含扩展名)，与`url_and_index_pairs`一一对应
+///
+/// 可用字段为`index`(从1开始的页码)和`original_name`(图片URL中的原始文件名)
+///
+/// 导出时按文件名排序(见`utils::natural_cmp`)，所以按页码排列后文件名必须严格递增(这也保证了没有重名)，否则在文件名前加上页码
+fn format_img_stems(
+    image_name_fmt: &str,
+    url_and_index_pairs: &[(String, i64)],
+    sanitize_mode: FilenameSanitizeMode,
+) -> anyhow::Result<Vec<String>> {
+    use strfmt::strfmt;
+
+    let mut img_stems = Vec::with_capacity(url_and_index_pairs.len());
+    for (url, index) in url_and_index_pairs {
+        let vars = HashMap::from([
+            ("index".to_string(), (index + 1).to_string()),
+            (
+                "original_name".to_string(),
+                original_img_stem(url, sanitize_mode),
+            ),
+        ]);
+        let img_stem = strfmt(image_name_fmt, &vars)
+            .context(format!("用`{image_name_fmt}`格式化图片文件名失败"))?;
+        let img_stem = utils::filename_filter(&img_stem, sanitize_mode);
+        if img_stem.is_empty() {
+            return Err(anyhow!("用`{image_name_fmt}`格式化出的图片文件名为空"));
+        }
+        img_stems.push(img_stem);
+    }
+
+    let mut index_and_stem_pairs: Vec<(i64, &str)> = url_and_index_pairs
+        .iter()
+        .zip(&img_stems)
+        .map(|((_, index), stem)| (*index, stem.as_str()))
+        .collect();
+    index_and_stem_pairs.sort_by_key(|(index, _)| *index);
+    let sorted_by_page = index_and_stem_pairs
+        .windows(2)
+        .all(|pair| utils::natural_cmp(pair[0].1, pair[1].1).is_lt());
+    if sorted_by_page {
+        return Ok(img_stems);
+    }
+
+    let img_stems = url_and_index_pairs
+        .iter()
+        .zip(img_stems)
+        .map(|((_, index), img_stem)| format!("{}_{img_stem}", index_img_stem(*index)))
+        .collect();
+    Ok(img_stems)
+}
+
+/// 用页码生成图片文件名(不含扩展名)，如`index`为0时生成`001`，与`DEFAULT_IMAGE_NAME_FMT`一致
+fn index_img_stem(index: i64) -> String {
+    format!("{:03}", index + 1)
+}
+
+/// 将`download_events.jsonl`重命名为`download_events.1.jsonl`，已有的`download_events.{i}.jsonl`依次重命名为`download_events.{i+1}.jsonl`
+///
+/// `max_files`不为0时，删除多出来的旧文件，使包括`download_events.jsonl`在内最多只有`max_files`个文件
+fn rotate_download_event_log(logs_dir: &Path, max_files: usize) -> anyhow::Result<()> {
+    let log_path = logs_dir.join(DOWNLOAD_EVENT_LOG_FILENAME);
+    let rotated_log_path = |i: usize| logs_dir.join(format!("download_events.{i}.jsonl"));
+
+    let mut last_index = 0;
+    while rotated_log_path(last_index + 1).exists() {
+        last_index += 1;
+    }
+    // 从后往前重命名，避免覆盖还没重命名的文件
+    for i in (1..=last_index).rev() {
+        let (from, to) = (rotated_log_path(i), rotated_log_path(i + 1));
+        std::fs::rename(&from, &to).context(format!(
+            "将`{}`重命名为`{}`失败",
+            from.display(),
+            to.display()
+        ))?;
+    }
+    let to = rotated_log_path(1);
+    std::fs::rename(&log_path, &to).context(format!(
+        "将`{}`重命名为`{}`失败",
+        log_path.display(),
+        to.display()
+    ))?;
+
+    if max_files > 0 {
+        for i in max_files..=last_index + 1 {
+            let path = rotated_log_path(i);
+            std::fs::remove_file(&path).context(format!("删除`{}`失败", path.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 从图片URL中提取原始文件名(不含扩展名)，例如`.../abc.jpg.c800x.webp`提取为`abc`
+fn original_img_stem(url: &str, sanitize_mode: FilenameSanitizeMode) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let basename = path.rsplit('/').next().unwrap_or_default();
+    let stem = basename.split('.').next().unwrap_or_default();
+    utils::filename_filter(stem, sanitize_mode)
+}
+
+/// 在`comic_export_dir`下按照`export_dir_fmt`逐级拼接章节的导出目录，格式化后为空的目录会被跳过
+///
+/// `extension`不为`None`时，最后再加一层扩展名目录
+fn format_chapter_export_dir(
+    comic_export_dir: &Path,
+    export_dir_fmt: &str,
+    vars: &HashMap<String, String>,
+    sanitize_mode: FilenameSanitizeMode,
+    extension: Option<&str>,
+) -> anyhow::Result<PathBuf> {
+    use strfmt::strfmt;
+
+    let mut chapter_export_dir = comic_export_dir.to_path_buf();
+    for fmt in export_dir_fmt.split('/') {
+        let dir_name = strfmt(fmt, vars).context("格式化目录名失败")?;
+        let dir_name = utils::filename_filter(&dir_name, sanitize_mode);
+        if !dir_name.is_empty() {
+            chapter_export_dir = chapter_export_dir.join(dir_name);
+        }
+    }
+    if let Some(extension) = extension {
+        chapter_export_dir = chapter_export_dir.join(extension);
+    }
+
+    Ok(chapter_export_dir)
+}
 
-        let state = *state_receiver.borrow();
-        match state {
-            DownloadTaskState::Paused => {
-                tracing::trace!(comic_title, chapter_title, url, "图片暂停下载");
-                if let Some(permit) = permit.take() {
-                    drop(permit);
-                }
-                ControlFlow::Continue(())
-            }
-            DownloadTaskState::Cancelled => {
-                tracing::trace!(comic_title, chapter_title, url, "图片取消下载");
-                ControlFlow::Break(())
-            }
-            _ => ControlFlow::Continue(()),
-        }
+/// 用`chapter_title_strip_regex`删除章节标题中的匹配部分(如`【XX汉化组】`)
+///
+/// 删除后标题为空时，保留原标题，以免生成空的目录名
+fn strip_chapter_title(chapter_title: &str, chapter_title_strip_regex: Option<&Regex>) -> String {
+    let Some(re) = chapter_title_strip_regex else {
+        return chapter_title.to_string();
+    };
+    let stripped_title = re.replace_all(chapter_title, "");
+    let stripped_title = stripped_title.trim();
+    if stripped_title.is_empty() {
+        return chapter_title.to_string();
     }
+    stripped_title.to_string()
 }
 
-fn save_img(
-    save_path: &Path,
-    target_format: ImageFormat,
-    src_img_data: &Bytes,
+/// 将`img_path`转码为`target_format`，保存为同名但扩展名不同的文件，然后删除原图
+fn transcode_img(img_path: &Path, target_format: DownloadFormat) -> anyhow::Result<()> {
+    let src_img_data =
+        std::fs::read(img_path).context(format!("读取`{}`失败", img_path.display()))?;
+    let src_format = image::guess_format(&src_img_data).context("无法识别图片格式")?;
+    let converted_data = convert_img(&src_img_data, src_format, target_format)?;
+
+    let save_path = img_path.with_extension(target_format.extension());
+    std::fs::write(&save_path, &converted_data)
+        .context(format!("将图片数据写入`{}`失败", save_path.display()))?;
+    std::fs::remove_file(img_path).context(format!("删除`{}`失败", img_path.display()))?;
+
+    Ok(())
+}
+
+/// 将`src_format`格式的`src_img_data`转换为`target_format`格式
+pub fn convert_img(
+    src_img_data: &[u8],
     src_format: ImageFormat,
-) -> anyhow::Result<()> {
-    if target_format == src_format {
-        // 如果target_format与src_format匹配，则直接保存
-        std::fs::write(save_path, src_img_data)
-            .context(format!("将图片数据写入`{}`失败", save_path.display()))?;
-        return Ok(());
-    }
-    // 如果target_format与src_format不匹配，则需要转换格式
+    target_format: DownloadFormat,
+) -> anyhow::Result<Vec<u8>> {
     let img = image::load_from_memory(src_img_data).context("加载图片数据失败")?;
 
     let mut converted_data = Vec::new();
     match target_format {
-        ImageFormat::WebP => img
+        DownloadFormat::Webp => img
             .to_rgba8()
-            .write_to(&mut Cursor::new(&mut converted_data), ImageFormat::WebP),
-        ImageFormat::Jpeg => img
+            .write_to(&mut Cursor::new(&mut converted_data), ImageFormat::WebP)
+            .map_err(anyhow::Error::from),
+        DownloadFormat::Jpeg => img
             .to_rgb8()
-            .write_to(&mut Cursor::new(&mut converted_data), ImageFormat::Jpeg),
-        _ => return Err(anyhow!("不支持的图片格式: {:?}", target_format)),
+            .write_to(&mut Cursor::new(&mut converted_data), ImageFormat::Jpeg)
+            .map_err(anyhow::Error::from),
+        #[cfg(feature = "jxl")]
+        DownloadFormat::Jxl => encode_jxl(&img).map(|data| converted_data = data),
+        #[cfg(not(feature = "jxl"))]
+        DownloadFormat::Jxl => Err(anyhow!("编译时未启用`jxl`功能，不支持JPEG XL格式")),
     }
     .context(format!("将`{src_format:?}`转换为`{target_format:?}`失败"))?;
 
-    std::fs::write(save_path, &converted_data)
-        .context(format!("将图片数据写入`{}`失败", save_path.display()))?;
+    Ok(converted_data)
+}
 
+/// 将gif转换为webp动图后保存到`save_path`
+#[cfg(feature = "animated-webp")]
+fn save_gif_as_animated_webp(save_path: &Path, src_img_data: &Bytes) -> anyhow::Result<()> {
+    let webp_data = convert_gif_to_animated_webp(src_img_data)?;
+    std::fs::write(save_path, webp_data)
+        .context(format!("将图片数据写入`{}`失败", save_path.display()))?;
     Ok(())
 }
 
+/// 将gif转换为webp动图，保留所有帧及其延迟，只有一帧的gif会转换为只有一帧的webp
+///
+/// `image`库的webp编码器不支持动图，所以用`webp-animation`编码
+#[cfg(feature = "animated-webp")]
+fn convert_gif_to_animated_webp(src_img_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    use image::{codecs::gif::GifDecoder, AnimationDecoder};
+    // 浏览器会把延迟为0的帧当作100ms处理，这里保持一致，否则帧的时间戳不会递增
+    const ZERO_DELAY_FALLBACK_MS: u32 = 100;
+
+    let decoder = GifDecoder::new(Cursor::new(src_img_data)).context("创建gif解码器失败")?;
+    let frames = decoder
+        .into_frames()
+        .collect_frames()
+        .context("解码gif的帧失败")?;
+    let first_frame = frames.first().context("gif没有任何帧")?;
+
+    let mut encoder = webp_animation::Encoder::new(first_frame.buffer().dimensions())
+        .map_err(|err| anyhow!("{err:?}"))
+        .context("创建webp动图编码器失败")?;
+    let mut timestamp_ms: i32 = 0;
+    for (i, frame) in frames.iter().enumerate() {
+        encoder
+            .add_frame(frame.buffer().as_raw(), timestamp_ms)
+            .map_err(|err| anyhow!("{err:?}"))
+            .context(format!("添加第{}帧失败", i + 1))?;
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        let delay_ms = match numer / denom.max(1) {
+            0 => ZERO_DELAY_FALLBACK_MS,
+            delay_ms => delay_ms,
+        };
+        timestamp_ms = timestamp_ms.saturating_add(i32::try_from(delay_ms).unwrap_or(i32::MAX));
+    }
+    let webp_data = encoder
+        .finalize(timestamp_ms)
+        .map_err(|err| anyhow!("{err:?}"))
+        .context("生成webp动图失败")?;
+
+    Ok(webp_data.to_vec())
+}
+
+/// 将`img`编码为JPEG XL，`image`库不支持jxl，所以用`jpegxl-rs`编码
+#[cfg(feature = "jxl")]
+fn encode_jxl(img: &image::DynamicImage) -> anyhow::Result<Vec<u8>> {
+    use jpegxl_rs::encode::EncoderResult;
+
+    let mut encoder = jpegxl_rs::encoder_builder()
+        .build()
+        .context("创建JPEG XL编码器失败")?;
+    let rgb_img = img.to_rgb8();
+    let encoder_result: EncoderResult<u8> = encoder
+        .encode::<u8, u8>(rgb_img.as_raw(), rgb_img.width(), rgb_img.height())
+        .context("JPEG XL编码失败")?;
+
+    Ok(encoder_result.data)
+}
+
 #[derive(Default, Debug, PartialEq, Clone, Serialize, Deserialize, Type)]
 pub struct ComicDirFmtParams {
     pub comic_uuid: String,
     pub comic_path_word: String,
     pub comic_title: String,
     pub author: String,
+    /// API返回的原始更新时间，无法解析时为空字符串
+    pub datetime_updated: String,
+    pub year: String,
+    pub month: String,
+    pub update_date: String,
+}
+
+impl ComicDirFmtParams {
+    /// 解析`datetime_updated`，返回`(year, month, update_date)`
+    ///
+    /// 无法解析时都为空字符串，这样`get_comic_download_dir_by_fmt`会跳过对应的目录层级
+    fn parse_datetime_updated(datetime_updated: &str) -> (String, String, String) {
+        // 拷贝漫画返回的格式为`2024-05-01`，以防万一也兼容带时间的格式
+        let date_str = datetime_updated.get(..10).unwrap_or(datetime_updated);
+        match chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d") {
+            Ok(date) => (
+                date.format("%Y").to_string(),
+                date.format("%m").to_string(),
+                date.format("%Y-%m-%d").to_string(),
+            ),
+            Err(_) => (String::new(), String::new(), String::new()),
+        }
+    }
 }
 
 impl Comic {
+    /// 获取`chapter`中所有图片的下载URL，顺序与`chapter.contents`一致
+    ///
+    /// 下载和估算下载大小时都用这个URL，以免两者下载的图片不同
+    pub fn get_img_urls(
+        &self,
+        app: &AppHandle,
+        chapter: &ChapterInGetChapterRespData,
+    ) -> Vec<String> {
+        let skip_hd_upgrade_for_webtoon = app
+            .get_config()
+            .read()
+            .get_skip_hd_upgrade_for_webtoon(&self.comic.path_word);
+        let upgrade_img_quality =
+            self.should_upgrade_img_quality(skip_hd_upgrade_for_webtoon, chapter.is_long);
+        chapter
+            .contents
+            .iter()
+            .map(|content| {
+                if upgrade_img_quality {
+                    content.url.replace(".c800x.", ".c1500x.")
+                } else {
+                    content.url.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// 是否将图片URL中的`.c800x.`替换为`.c1500x.`以下载更高清的图片
+    ///
+    /// 条漫的图片本来就很长，升级后体积暴增但观感几乎没有提升，所以开启`skip_hd_upgrade_for_webtoon`时跳过
+    fn should_upgrade_img_quality(&self, skip_hd_upgrade_for_webtoon: bool, is_long: bool) -> bool {
+        if !skip_hd_upgrade_for_webtoon {
+            return true;
+        }
+        let is_webtoon = is_long || self.comic.reclass.display.contains("条漫");
+        !is_webtoon
+    }
+
     /// 根据fmt更新`comic_download_dir`和`chapter_infos.chapter_download_dir`字段
-    fn update_download_dir_fields_by_fmt(&mut self, app: &AppHandle) -> anyhow::Result<()> {
+    pub fn update_download_dir_fields_by_fmt(&mut self, app: &AppHandle) -> anyhow::Result<()> {
         let comic_uuid = self.comic.uuid.clone();
         let comic_title = self.comic.name.clone();
         let comic_path_word = self.comic.path_word.clone();
@@ -846,23 +2406,37 @@ impl Comic {
             .collect::<Vec<_>>()
             .join(", ");
 
+        let (year, month, update_date) =
+            ComicDirFmtParams::parse_datetime_updated(&self.comic.datetime_updated);
+        // 与`year`等字段保持一致，无法解析时用空字符串，让`get_comic_download_dir_by_fmt`跳过对应的目录层级
+        let datetime_updated = if update_date.is_empty() {
+            String::new()
+        } else {
+            self.comic.datetime_updated.trim().to_string()
+        };
         let comic_dir_fmt_params = ComicDirFmtParams {
             comic_uuid: comic_uuid.clone(),
             comic_path_word: comic_path_word.clone(),
             comic_title: comic_title.clone(),
             author: author.clone(),
+            datetime_updated,
+            year,
+            month,
+            update_date,
         };
         let comic_download_dir = Comic::get_comic_download_dir_by_fmt(app, &comic_dir_fmt_params)?;
         self.comic_download_dir = Some(comic_download_dir.clone());
 
         let separate_chapter_type = app.get_config().read().separate_chapter_type;
-
-        for chapter_info in &mut self
-            .comic
-            .groups
-            .iter_mut()
-            .flat_map(|(_, chapters)| chapters)
-        {
+        let chapter_title_strip_regex = app.get_config().read().get_chapter_title_strip_regex()?;
+        let chapter_indexes = self.get_chapter_indexes();
+        // 用于检测不同章节是否解析到了同一个下载目录
+        let mut dir_to_chapter_desc: HashMap<PathBuf, String> = HashMap::new();
+        let mut collisions = Vec::new();
+        // 与`self.comic.groups.values().flatten()`的顺序一一对应，循环结束后再写回`chapter_download_dir`
+        let mut chapter_download_dirs = Vec::new();
+
+        for chapter_info in self.comic.groups.values().flatten() {
             let mut final_comic_download_dir = comic_download_dir.clone();
             
             // 如果开启了`separate_chapter_type`，则根据章节类型，追加对应的目录
@@ -882,6 +2456,10 @@ impl Comic {
                 }
             }
 
+            let (chapter_index, total_chapters) = chapter_indexes
+                .get(&chapter_info.chapter_uuid)
+                .copied()
+                .unwrap_or_default();
             let chapter_dir_fmt_params = ChapterDirFmtParams {
                 comic_uuid: comic_uuid.clone(),
                 comic_path_word: comic_path_word.clone(),
@@ -890,13 +2468,18 @@ impl Comic {
                 group_path_word: chapter_info.group_path_word.clone(),
                 group_title: chapter_info.group_name.clone(),
                 chapter_uuid: chapter_info.chapter_uuid.clone(),
-                chapter_title: chapter_info.chapter_title.clone(),
-                order: chapter_info.order,
+                chapter_title: strip_chapter_title(
+                    &chapter_info.chapter_title,
+                    chapter_title_strip_regex.as_ref(),
+                ),
+                order: self.get_chapter_order(chapter_info),
+                chapter_index,
+                total_chapters,
             };
 
             let mut chapter_dir_fmt_override = None;
             if separate_chapter_type {
-                 let mut chapter_dir_fmt = app.get_config().read().chapter_dir_fmt.clone();
+                 let mut chapter_dir_fmt = app.get_config().read().get_chapter_dir_fmt(&comic_path_word);
                  if chapter_dir_fmt.contains("{group_title}/") {
                      chapter_dir_fmt_override = Some(chapter_dir_fmt.replace("{group_title}/", ""));
                  } else if chapter_dir_fmt.contains("{group_title}\\") {
@@ -910,12 +2493,59 @@ impl Comic {
                 chapter_dir_fmt_override,
             )
             .context("获取章节下载目录失败")?;
+
+            let chapter_desc = format!(
+                "`{} - {}`(章节ID: `{}`)",
+                chapter_info.group_name, chapter_info.chapter_title, chapter_info.chapter_uuid
+            );
+            if let Some(existing_chapter_desc) = dir_to_chapter_desc.get(&chapter_download_dir) {
+                collisions.push(format!(
+                    "{existing_chapter_desc}与{chapter_desc}都解析为`{}`",
+                    chapter_download_dir.display()
+                ));
+            } else {
+                dir_to_chapter_desc.insert(chapter_download_dir.clone(), chapter_desc);
+            }
+
+            chapter_download_dirs.push(chapter_download_dir);
+        }
+        for (chapter_info, chapter_download_dir) in self
+            .comic
+            .groups
+            .values_mut()
+            .flatten()
+            .zip(chapter_download_dirs)
+        {
             chapter_info.chapter_download_dir = Some(chapter_download_dir);
         }
 
+        // 只警告，冲突的章节在创建下载任务时才会失败，不影响同一漫画的其他章节
+        if !collisions.is_empty() {
+            let collisions_string = collisions.join(", ");
+            tracing::warn!(
+                comic_title,
+                "有多个章节的下载目录相同，请在`配置`里修改章节目录格式，加入能唯一区分章节的字段(如`{{chapter_uuid}}`或`{{order}}`)，冲突的章节: [{collisions_string}]"
+            );
+        }
+
         Ok(())
     }
 
+    /// 找出与章节ID为`chapter_uuid`的章节下载目录相同的另一个章节
+    ///
+    /// 需要先调用`update_download_dir_fields_by_fmt`
+    pub fn find_chapter_dir_collision(&self, chapter_uuid: &str) -> Option<&ChapterInfo> {
+        let chapter_infos = || self.comic.groups.values().flatten();
+        let chapter_download_dir = chapter_infos()
+            .find(|chapter_info| chapter_info.chapter_uuid == chapter_uuid)?
+            .chapter_download_dir
+            .as_ref()?;
+        chapter_infos().find(|chapter_info| {
+            chapter_info.chapter_uuid != chapter_uuid
+                && chapter_info.chapter_download_dir.as_ref() == Some(chapter_download_dir)
+        })
+    }
+
     fn get_comic_download_dir_by_fmt(
         app: &AppHandle,
         fmt_params: &ComicDirFmtParams,
@@ -941,10 +2571,14 @@ impl Comic {
             })
             .collect();
 
-        let (download_dir, comic_dir_fmt) = {
+        let (download_dir, comic_dir_fmt, sanitize_mode) = {
             let config = app.get_config();
             let config = config.read();
-            (config.download_dir.clone(), config.comic_dir_fmt.clone())
+            (
+                config.download_dir.clone(),
+                config.comic_dir_fmt.clone(),
+                config.filename_sanitize_mode,
+            )
         };
 
         let dir_fmt_parts: Vec<&str> = comic_dir_fmt.split('/').collect();
@@ -952,7 +2586,7 @@ impl Comic {
         let mut dir_names = Vec::new();
         for fmt in dir_fmt_parts {
             let dir_name = strfmt(fmt, &vars).context("格式化目录名失败")?;
-            let dir_name = utils::filename_filter(&dir_name);
+            let dir_name = utils::filename_filter(&dir_name, sanitize_mode);
             if !dir_name.is_empty() {
                 dir_names.push(dir_name);
             }
@@ -978,6 +2612,10 @@ pub struct ChapterDirFmtParams {
     pub chapter_uuid: String,
     pub chapter_title: String,
     pub order: f64,
+    /// 章节在分组中按`order`排序后的位置，从1开始，与API返回的`order`无关
+    pub chapter_index: u32,
+    /// 分组的章节总数
+    pub total_chapters: u32,
 }
 
 impl ChapterInfo {
@@ -1007,16 +2645,21 @@ impl ChapterInfo {
                 (key, value)
             })
             .collect();
-        let mut chapter_dir_fmt = fmt_override.unwrap_or_else(|| app.get_config().read().chapter_dir_fmt.clone());
-        Self::preprocess_order_placeholder(&mut chapter_dir_fmt, &vars)
+        let mut chapter_dir_fmt = fmt_override.unwrap_or_else(|| {
+            app.get_config()
+                .read()
+                .get_chapter_dir_fmt(&fmt_params.comic_path_word)
+        });
+        Self::preprocess_order_placeholder(app, &mut chapter_dir_fmt, &vars)
             .context("预处理`order`占位符失败")?;
+        let sanitize_mode = app.get_config().read().filename_sanitize_mode;
 
         let dir_fmt_parts: Vec<&str> = chapter_dir_fmt.split('/').collect();
 
         let mut dir_names = Vec::new();
         for fmt in dir_fmt_parts {
             let dir_name = strfmt(fmt, &vars).context("格式化目录名失败")?;
-            let dir_name = utils::filename_filter(&dir_name);
+            let dir_name = utils::filename_filter(&dir_name, sanitize_mode);
             if !dir_name.is_empty() {
                 dir_names.push(dir_name);
             }
@@ -1030,6 +2673,173 @@ impl ChapterInfo {
         Ok(chapter_download_dir)
     }
 
+    /// 根据`config.export_filename_fmt`获取导出文件名(不含扩展名)
+    ///
+    /// 如果`export_filename_fmt`为空，或格式化后的文件名为空，则使用章节下载目录名
+    ///
+    /// `chapter_indexes`为`comic.get_chapter_indexes()`的结果，导出多个章节时只需计算一次
+    pub fn get_export_filename_by_fmt(
+        &self,
+        app: &AppHandle,
+        comic: &Comic,
+        chapter_indexes: &HashMap<String, (u32, u32)>,
+    ) -> anyhow::Result<String> {
+        use strfmt::strfmt;
+
+        let chapter_download_dir = self
+            .chapter_download_dir
+            .as_ref()
+            .context("`chapter_download_dir`字段为`None`")?;
+        let chapter_download_dir_name = chapter_download_dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .context(format!(
+                "获取`{}`的目录名失败",
+                chapter_download_dir.display()
+            ))?
+            .to_string();
+
+        let mut export_filename_fmt = app.get_config().read().export_filename_fmt.clone();
+        if export_filename_fmt.is_empty() {
+            return Ok(chapter_download_dir_name);
+        }
+
+        let vars = self.get_export_fmt_vars(app, comic, chapter_indexes)?;
+        Self::preprocess_order_placeholder(app, &mut export_filename_fmt, &vars)
+            .context("预处理`order`占位符失败")?;
+
+        let export_filename =
+            strfmt(&export_filename_fmt, &vars).context("格式化导出文件名失败")?;
+        let sanitize_mode = app.get_config().read().filename_sanitize_mode;
+        let export_filename = utils::filename_filter(&export_filename, sanitize_mode);
+        if export_filename.is_empty() {
+            return Ok(chapter_download_dir_name);
+        }
+
+        Ok(export_filename)
+    }
+
+    /// 获取章节的导出目录，导出的文件保存在这个目录中
+    ///
+    /// 在`comic_export_dir`下按照`config.export_dir_fmt`创建目录，除了`chapter_dir_fmt`的字段外，还可以用
+    /// - `chapter_type`：开启`separate_chapter_type`且章节类型已知时为`话`、`卷`或`番外`，否则为空
+    /// - `type_group_title`：`chapter_type`不为空时为分组名，否则为空
+    ///
+    /// 格式化后为空的目录会被跳过，开启`export_format_subdir`时最后再加一层扩展名目录。
+    /// 默认的`{type_group_title}/{chapter_type}`与下载目录中的分类结构一致
+    ///
+    /// `chapter_indexes`与`get_export_filename_by_fmt`相同
+    pub fn get_chapter_export_dir_by_fmt(
+        &self,
+        app: &AppHandle,
+        comic: &Comic,
+        chapter_indexes: &HashMap<String, (u32, u32)>,
+        comic_export_dir: &Path,
+        extension: &str,
+    ) -> anyhow::Result<PathBuf> {
+        let (mut export_dir_fmt, separate_chapter_type, export_format_subdir, sanitize_mode) = {
+            let config = app.get_config();
+            let config = config.read();
+            (
+                config.export_dir_fmt.clone(),
+                config.separate_chapter_type,
+                config.export_format_subdir,
+                config.filename_sanitize_mode,
+            )
+        };
+
+        let mut vars = self.get_export_fmt_vars(app, comic, chapter_indexes)?;
+        vars.extend(self.get_chapter_type_vars(separate_chapter_type));
+        Self::preprocess_order_placeholder(app, &mut export_dir_fmt, &vars)
+            .context("预处理`order`占位符失败")?;
+
+        format_chapter_export_dir(
+            comic_export_dir,
+            &export_dir_fmt,
+            &vars,
+            sanitize_mode,
+            export_format_subdir.then_some(extension),
+        )
+    }
+
+    /// 导出目录格式中与章节类型有关的字段，见`get_chapter_export_dir_by_fmt`
+    fn get_chapter_type_vars(&self, separate_chapter_type: bool) -> [(String, String); 2] {
+        // 只有开启`separate_chapter_type`时，下载目录中才会有类型文件夹
+        let chapter_type = match self.chapter_type {
+            1 if separate_chapter_type => "话",
+            2 if separate_chapter_type => "卷",
+            3 if separate_chapter_type => "番外",
+            _ => "",
+        };
+        let type_group_title = if chapter_type.is_empty() {
+            ""
+        } else {
+            self.group_name.as_str()
+        };
+        [
+            ("chapter_type".to_string(), chapter_type.to_string()),
+            ("type_group_title".to_string(), type_group_title.to_string()),
+        ]
+    }
+
+    /// 格式化导出文件名和导出目录时可用的字段，与`chapter_dir_fmt`的字段相同
+    fn get_export_fmt_vars(
+        &self,
+        app: &AppHandle,
+        comic: &Comic,
+        chapter_indexes: &HashMap<String, (u32, u32)>,
+    ) -> anyhow::Result<HashMap<String, String>> {
+        let author = comic
+            .comic
+            .author
+            .iter()
+            .map(|a| a.name.clone())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let chapter_title_strip_regex = app.get_config().read().get_chapter_title_strip_regex()?;
+        let (chapter_index, total_chapters) = chapter_indexes
+            .get(&self.chapter_uuid)
+            .copied()
+            .unwrap_or_default();
+        let fmt_params = ChapterDirFmtParams {
+            comic_uuid: comic.comic.uuid.clone(),
+            comic_path_word: comic.comic.path_word.clone(),
+            comic_title: comic.comic.name.clone(),
+            author,
+            group_path_word: self.group_path_word.clone(),
+            group_title: self.group_name.clone(),
+            chapter_uuid: self.chapter_uuid.clone(),
+            chapter_title: strip_chapter_title(
+                &self.chapter_title,
+                chapter_title_strip_regex.as_ref(),
+            ),
+            order: comic.get_chapter_order(self),
+            chapter_index,
+            total_chapters,
+        };
+
+        let json_value = serde_json::to_value(&fmt_params)
+            .context("将ChapterDirFmtParams转为serde_json::Value失败")?;
+
+        let json_map = json_value
+            .as_object()
+            .context("ChapterDirFmtParams不是JSON对象")?;
+
+        let vars: HashMap<String, String> = json_map
+            .into_iter()
+            .map(|(k, v)| {
+                let key = k.clone();
+                let value = match v {
+                    serde_json::Value::String(s) => s.clone(),
+                    _ => v.to_string(),
+                };
+                (key, value)
+            })
+            .collect();
+
+        Ok(vars)
+    }
+
     /// 预处理`fmt`中的`order`占位符
     ///
     /// ### 功能描述
@@ -1048,12 +2858,36 @@ impl ChapterInfo {
     ///    - 若存在非零小数部分，将其追加到格式化后的整数后面
     /// 4. **原地替换**：将计算出的最终字符串(如 `0005.1`)直接替换掉原模板中的占位符
     ///
+    /// 小数部分会用`0`在右侧补齐到`config.order_frac_width`位(为0则不补齐)，
+    /// 并用`config.order_decimal_separator`代替小数点
+    ///
     /// ### 示例
     /// - 输入 fmt: `"{order:0>3} {chapter_title}"`, order: `"1.5"`
     /// - 处理后 fmt: `"001.5 {chapter_title}"`
+    /// - `order_frac_width`为2，`order_decimal_separator`为`_`时，处理后 fmt: `"001_50 {chapter_title}"`
     fn preprocess_order_placeholder(
+        app: &AppHandle,
+        fmt: &mut String,
+        vars: &HashMap<String, String>,
+    ) -> anyhow::Result<()> {
+        let (frac_width, decimal_separator) = {
+            let config = app.get_config();
+            let config = config.read();
+            (
+                config.order_frac_width,
+                config.order_decimal_separator.clone(),
+            )
+        };
+
+        Self::format_order_placeholder(fmt, vars, frac_width, &decimal_separator)
+    }
+
+    /// `preprocess_order_placeholder`的实现，`frac_width`和`decimal_separator`对应同名的配置项
+    fn format_order_placeholder(
         fmt: &mut String,
         vars: &HashMap<String, String>,
+        frac_width: usize,
+        decimal_separator: &str,
     ) -> anyhow::Result<()> {
         use strfmt::strfmt;
 
@@ -1061,12 +2895,21 @@ impl ChapterInfo {
             return Ok(());
         };
 
+        // API返回的order精确到0.1，先格式化为固定一位小数，避免`5.1000001`之类的浮点误差
+        // 手动指定的order可能有更多位小数，这时保留原样
+        let order_str = match order_str.parse::<f64>() {
+            Ok(order) if ((order * 10.0).round() - order * 10.0).abs() < 1e-4 => {
+                format!("{order:.1}")
+            }
+            _ => order_str.clone(),
+        };
         // 分离整数和小数
         let (int_part, frac_part) = match order_str.split_once('.') {
             Some((i, f)) => (i, f),
             None => (order_str.as_str(), ""),
         };
         let should_append_frac = !frac_part.is_empty() && frac_part != "0";
+        let frac_part = format!("{frac_part:0<frac_width$}");
 
         // group 1: "{{" (转义左括号)
         // group 2: "}}" (转义右括号)
@@ -1096,7 +2939,7 @@ impl ChapterInfo {
             let formatted_int = strfmt(&int_fmt, &temp_vars).unwrap_or(int_part.to_string());
 
             if should_append_frac {
-                format!("{formatted_int}.{frac_part}")
+                format!("{formatted_int}{decimal_separator}{frac_part}")
             } else {
                 formatted_int
             }
@@ -1107,3 +2950,301 @@ impl ChapterInfo {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use image::{codecs::gif::GifEncoder, Delay, Frame, Rgba, RgbaImage};
+
+    use super::*;
+    use crate::test_utils::create_test_dir;
+
+    fn format_order(fmt: &str, order: &str) -> String {
+        let mut fmt = fmt.to_string();
+        let vars = HashMap::from([("order".to_string(), order.to_string())]);
+        ChapterInfo::format_order_placeholder(&mut fmt, &vars, 0, ".").unwrap();
+        fmt
+    }
+
+    #[test]
+    fn rate_limited_retries_back_off_exponentially_then_give_up() {
+        let mut retry_counts = ImgRetryCounts::default();
+        let rate_limited_err = || anyhow!("状态码为429").context(ImgRateLimitedError);
+        for backoff_sec in [10, 20, 40, 80, 160] {
+            assert_eq!(
+                retry_counts.next_retry(&rate_limited_err()),
+                ImgRetry::RateLimited {
+                    backoff: Duration::from_secs(backoff_sec)
+                }
+            );
+        }
+        assert_eq!(
+            retry_counts.next_retry(&rate_limited_err()),
+            ImgRetry::GiveUp
+        );
+    }
+
+    #[test]
+    fn rate_limited_and_transient_retries_are_counted_separately() {
+        let mut retry_counts = ImgRetryCounts::default();
+        let rate_limited_err = || anyhow!("状态码为429").context(ImgRateLimitedError);
+        let timeout_err = || anyhow!("超时").context(NetworkTimeoutError);
+        for _ in 0..ImgRetryCounts::MAX_RETRY_COUNT {
+            assert_eq!(
+                retry_counts.next_retry(&timeout_err()),
+                ImgRetry::AfterRandomDelay
+            );
+        }
+        assert_eq!(
+            retry_counts.next_retry(&rate_limited_err()),
+            ImgRetry::RateLimited {
+                backoff: Duration::from_secs(10)
+            }
+        );
+        assert_eq!(retry_counts.next_retry(&timeout_err()), ImgRetry::GiveUp);
+    }
+
+    #[test]
+    fn other_errors_are_not_retried() {
+        let mut retry_counts = ImgRetryCounts::default();
+        assert_eq!(
+            retry_counts.next_retry(&anyhow!("状态码为404")),
+            ImgRetry::GiveUp
+        );
+    }
+
+    #[test]
+    fn order_placeholder_pads_only_int_part() {
+        assert_eq!(format_order("{order:0>3}", "5.0"), "005");
+        assert_eq!(format_order("{order:0>3}", "5.1"), "005.1");
+        assert_eq!(format_order("{order:0>3}", "10.0"), "010");
+        assert_eq!(format_order("{order:0>3}", "123.5"), "123.5");
+    }
+
+    #[test]
+    fn order_placeholder_handles_whole_numbers_and_float_noise() {
+        assert_eq!(format_order("{order:0>3}", "5"), "005");
+        assert_eq!(format_order("{order:0>3}", "10"), "010");
+        assert_eq!(format_order("{order:0>3}", "5.1000001"), "005.1");
+        assert_eq!(format_order("{order:0>3}", "4.9999999"), "005");
+        assert_eq!(format_order("{order}", "5.1000001"), "5.1");
+    }
+
+    #[test]
+    fn order_placeholder_keeps_other_placeholders_and_escapes() {
+        assert_eq!(
+            format_order("{{{order:0>3}}} {chapter_title}", "5.1"),
+            "{{005.1}} {chapter_title}"
+        );
+    }
+
+    #[test]
+    fn order_placeholder_applies_frac_width_and_separator() {
+        let mut fmt = "{order:0>3}".to_string();
+        let vars = HashMap::from([("order".to_string(), "1.5".to_string())]);
+        ChapterInfo::format_order_placeholder(&mut fmt, &vars, 2, "_").unwrap();
+        assert_eq!(fmt, "001_50");
+    }
+
+    /// 用默认的`export_dir_fmt`计算分组为`group_name`、类型为`chapter_type`的章节的导出目录
+    fn default_export_dir(
+        group_name: &str,
+        chapter_type: i64,
+        separate_chapter_type: bool,
+        extension: Option<&str>,
+    ) -> PathBuf {
+        let chapter_info = ChapterInfo {
+            group_name: group_name.to_string(),
+            chapter_type,
+            ..Default::default()
+        };
+        let vars: HashMap<String, String> = chapter_info
+            .get_chapter_type_vars(separate_chapter_type)
+            .into_iter()
+            .collect();
+        format_chapter_export_dir(
+            Path::new("导出/漫画"),
+            crate::config::DEFAULT_EXPORT_DIR_FMT,
+            &vars,
+            FilenameSanitizeMode::default(),
+            extension,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn export_dir_is_flat_without_separate_chapter_type() {
+        assert_eq!(
+            default_export_dir("默认", 1, false, None),
+            Path::new("导出/漫画")
+        );
+        assert_eq!(
+            default_export_dir("默认", 2, false, Some("cbz")),
+            Path::new("导出/漫画/cbz")
+        );
+    }
+
+    #[test]
+    fn export_dir_has_group_and_type_with_separate_chapter_type() {
+        assert_eq!(
+            default_export_dir("默认", 1, true, None),
+            Path::new("导出/漫画/默认/话")
+        );
+        assert_eq!(
+            default_export_dir("单行本", 2, true, None),
+            Path::new("导出/漫画/单行本/卷")
+        );
+        assert_eq!(
+            default_export_dir("默认", 3, true, Some("pdf")),
+            Path::new("导出/漫画/默认/番外/pdf")
+        );
+        // 章节类型未知时，下载目录中也没有类型文件夹
+        assert_eq!(
+            default_export_dir("默认", 0, true, None),
+            Path::new("导出/漫画")
+        );
+    }
+
+    #[test]
+    fn group_named_like_a_type_dir_is_kept() {
+        assert_eq!(
+            default_export_dir("话", 1, true, None),
+            Path::new("导出/漫画/话/话")
+        );
+        assert_eq!(
+            default_export_dir("番外", 1, true, Some("cbz")),
+            Path::new("导出/漫画/番外/话/cbz")
+        );
+        assert_eq!(
+            default_export_dir("话", 1, false, None),
+            Path::new("导出/漫画")
+        );
+    }
+
+    /// 生成一个有`frame_count`帧的gif，每帧颜色不同，避免编码器合并相同的帧
+    pub(super) fn create_gif(frame_count: u8) -> Vec<u8> {
+        let mut gif_data = Vec::new();
+        let mut encoder = GifEncoder::new(&mut gif_data);
+        let frames = (0..frame_count).map(|i| {
+            let color = Rgba([i.wrapping_mul(80), 255 - i.wrapping_mul(80), 0, 255]);
+            let buffer = RgbaImage::from_pixel(8, 8, color);
+            Frame::from_parts(buffer, 0, 0, Delay::from_numer_denom_ms(50, 1))
+        });
+        encoder.encode_frames(frames).unwrap();
+        drop(encoder);
+        gif_data
+    }
+
+    #[test]
+    fn multi_frame_gif_is_kept_byte_for_byte() {
+        let dir = create_test_dir();
+        let gif_data = Bytes::from(create_gif(3));
+
+        let save_path = save_recognized_img(
+            dir.join("001.webp"),
+            GifSaveMode::KeepAsIs,
+            DownloadFormat::Webp,
+            &gif_data,
+            ImageFormat::Gif,
+        )
+        .unwrap();
+
+        assert_eq!(save_path, dir.join("001.gif"));
+        assert_eq!(std::fs::read(&save_path).unwrap(), gif_data);
+        assert!(!dir.join("001.webp").exists());
+    }
+
+    #[test]
+    fn datetime_updated_is_parsed_into_date_placeholders() {
+        let expected = (
+            "2024".to_string(),
+            "05".to_string(),
+            "2024-05-01".to_string(),
+        );
+        assert_eq!(
+            ComicDirFmtParams::parse_datetime_updated("2024-05-01"),
+            expected
+        );
+        assert_eq!(
+            ComicDirFmtParams::parse_datetime_updated("2024-05-01 12:34:56"),
+            expected
+        );
+        assert_eq!(
+            ComicDirFmtParams::parse_datetime_updated("未知"),
+            (String::new(), String::new(), String::new())
+        );
+    }
+
+    #[test]
+    fn webtoon_skips_hd_upgrade_only_when_enabled() {
+        let mut webtoon = Comic::default();
+        webtoon.comic.reclass.display = "条漫".to_string();
+        let long_strip = Comic::default();
+        let normal = Comic::default();
+
+        assert!(!webtoon.should_upgrade_img_quality(true, false));
+        assert!(!long_strip.should_upgrade_img_quality(true, true));
+        assert!(normal.should_upgrade_img_quality(true, false));
+        // 关闭`skip_hd_upgrade_for_webtoon`时总是升级
+        assert!(webtoon.should_upgrade_img_quality(false, false));
+        assert!(long_strip.should_upgrade_img_quality(false, true));
+    }
+
+    fn url_and_index_pairs(names: &[&str]) -> Vec<(String, i64)> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (format!("https://cdn/{name}.jpg.c800x.webp"), i as i64))
+            .collect()
+    }
+
+    #[test]
+    fn default_image_name_fmt_names_by_page() {
+        let pairs = url_and_index_pairs(&["b", "a", "c"]);
+        let img_stems = format_img_stems(
+            DEFAULT_IMAGE_NAME_FMT,
+            &pairs,
+            FilenameSanitizeMode::default(),
+        )
+        .unwrap();
+        assert_eq!(img_stems, ["001", "002", "003"]);
+    }
+
+    #[test]
+    fn original_name_is_prefixed_with_page_when_out_of_order() {
+        let sanitize_mode = FilenameSanitizeMode::default();
+        let in_order = url_and_index_pairs(&["1", "2", "10"]);
+        let img_stems = format_img_stems("{original_name}", &in_order, sanitize_mode).unwrap();
+        assert_eq!(img_stems, ["1", "2", "10"]);
+
+        let out_of_order = url_and_index_pairs(&["b", "a", "c"]);
+        let img_stems = format_img_stems("{original_name}", &out_of_order, sanitize_mode).unwrap();
+        assert_eq!(img_stems, ["001_b", "002_a", "003_c"]);
+    }
+
+    #[test]
+    fn invalid_image_name_fmt_is_an_error() {
+        let pairs = url_and_index_pairs(&["a"]);
+        let sanitize_mode = FilenameSanitizeMode::default();
+        assert!(format_img_stems("{unknown}", &pairs, sanitize_mode).is_err());
+        assert!(format_img_stems("", &pairs, sanitize_mode).is_err());
+    }
+}
+
+#[cfg(all(test, feature = "animated-webp"))]
+mod animated_webp_tests {
+    use image::{codecs::webp::WebPDecoder, AnimationDecoder};
+
+    use super::{tests::create_gif, *};
+
+    #[test]
+    fn three_frame_gif_converts_to_three_frame_webp() {
+        let gif_data = create_gif(3);
+
+        let webp_data = convert_gif_to_animated_webp(&gif_data).unwrap();
+
+        let decoder = WebPDecoder::new(Cursor::new(webp_data)).unwrap();
+        assert!(decoder.has_animation());
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 3);
+    }
+}
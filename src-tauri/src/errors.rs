@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use anyhow::anyhow;
 use serde::Serialize;
 use specta::Type;
@@ -32,6 +34,8 @@ pub type CopyMangaResult<T> = Result<T, CopyMangaError>;
 pub enum CopyMangaError {
     Anyhow(anyhow::Error),
     RiskControl(RiskControlError), // 风控
+    Timeout(anyhow::Error),        // 网络超时，可以重试
+    Unavailable(anyhow::Error),    // 资源已被删除或屏蔽，重试也没用
 }
 
 impl<E> From<E> for CopyMangaError
@@ -40,14 +44,21 @@ where
     Result<(), E>: anyhow::Context<(), E>,
 {
     fn from(err: E) -> Self {
-        CopyMangaError::Anyhow(err.into())
+        let err = err.into();
+        if err.is::<NetworkTimeoutError>() {
+            CopyMangaError::Timeout(err)
+        } else {
+            CopyMangaError::Anyhow(err)
+        }
     }
 }
 
 impl From<CopyMangaError> for anyhow::Error {
     fn from(err: CopyMangaError) -> Self {
         match err {
-            CopyMangaError::Anyhow(err) => err,
+            CopyMangaError::Anyhow(err)
+            | CopyMangaError::Timeout(err)
+            | CopyMangaError::Unavailable(err) => err,
             CopyMangaError::RiskControl(err) => match err {
                 RiskControlError::Register(err) => anyhow!(err),
                 RiskControlError::Login(err) => anyhow!(err),
@@ -68,6 +79,46 @@ impl From<RiskControlError> for CopyMangaError {
     }
 }
 
+/// 网络请求超时，作为`anyhow::Error`的context使用，以便与其他错误区分
+#[derive(Debug)]
+pub struct NetworkTimeoutError;
+
+impl Display for NetworkTimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "网络连接超时，请使用代理或换条线路重试")
+    }
+}
+
+/// 下载图片时响应的内容不是图片(例如CDN返回了状态码为200的HTML错误页面)，作为`anyhow::Error`的context使用，可以重试
+#[derive(Debug)]
+pub struct NotImageError;
+
+impl Display for NotImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "响应的内容不是图片")
+    }
+}
+
+/// 下载图片时CDN返回了429(请求过于频繁)，作为`anyhow::Error`的context使用，需要等待更久再重试
+#[derive(Debug)]
+pub struct ImgRateLimitedError;
+
+impl Display for ImgRateLimitedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "图片服务器限流，请求过于频繁")
+    }
+}
+
+/// 章节需要登录或VIP才能下载，但还没有登录，作为`anyhow::Error`的context使用
+#[derive(Debug)]
+pub struct LoginRequiredError;
+
+impl Display for LoginRequiredError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "该漫画需要登录或VIP才能下载，请先登录")
+    }
+}
+
 #[derive(Debug)]
 pub enum RiskControlError {
     Register(String),
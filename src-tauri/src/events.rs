@@ -28,6 +28,54 @@ pub struct DownloadControlRiskEvent {
     pub retry_after: u32,
 }
 
+/// 有任务触发注册风控时发送，冷却的`remaining_sec`秒内所有任务都暂停请求章节
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskControlCooldownEvent {
+    pub remaining_sec: u64,
+}
+
+/// 下载图片时CDN返回429，开始等待`wait_sec`秒后重试时发送
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct ImgRateLimitedEvent {
+    pub url: String,
+    pub wait_sec: u64,
+}
+
+/// 漫画这一批的章节全部下载完成时发送，有章节被取消、失败或屏蔽时不发送
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct ComicDownloadCompleteEvent {
+    pub comic_path_word: String,
+}
+
+/// 下载章节前发现磁盘空间不足时发送，任务会被标记为`Failed`
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadInsufficientSpaceEvent {
+    pub chapter_uuid: String,
+    pub available_bytes: u64,
+    pub required_bytes: u64,
+}
+
+/// 扫描下载目录中的漫画元数据时，每扫描完下载目录下的一个条目就发送一次
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryScanEvent {
+    pub scanned: u32,
+    pub total: u32,
+}
+
+/// 获取漫画时，每获取完一个分组的章节就发送一次
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct GetComicProgressEvent {
+    pub comic_path_word: String,
+    pub done: u32,
+    pub total: u32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
 #[serde(tag = "event", content = "data")]
 pub enum DownloadTaskEvent {
@@ -47,6 +95,14 @@ pub enum DownloadTaskEvent {
         downloaded_img_count: u32,
         total_img_count: u32,
     },
+
+    /// 章节已完整保存到`chapter_download_dir`，每个下载任务最多发送一次
+    #[serde(rename_all = "camelCase")]
+    Finished {
+        chapter_uuid: String,
+        chapter_download_dir: PathBuf,
+        img_count: u32,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
@@ -72,6 +128,29 @@ pub enum ExportCbzEvent {
     },
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
+#[serde(tag = "event", content = "data")]
+pub enum ExportZipEvent {
+    #[serde(rename_all = "camelCase")]
+    Start {
+        uuid: String,
+        comic_title: String,
+        total: u32,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    Progress { uuid: String, current: u32 },
+
+    #[serde(rename_all = "camelCase")]
+    Error { uuid: String },
+
+    #[serde(rename_all = "camelCase")]
+    End {
+        uuid: String,
+        chapter_export_dir: PathBuf,
+    },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
 #[serde(tag = "event", content = "data")]
 pub enum ExportPdfEvent {
@@ -108,6 +187,38 @@ pub enum ExportPdfEvent {
     },
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
+#[serde(tag = "event", content = "data")]
+pub enum TranscodeEvent {
+    #[serde(rename_all = "camelCase")]
+    Start {
+        uuid: String,
+        comic_title: String,
+        total: u32,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    Progress { uuid: String, current: u32 },
+
+    #[serde(rename_all = "camelCase")]
+    Cancelled { uuid: String },
+
+    #[serde(rename_all = "camelCase")]
+    Error { uuid: String },
+
+    #[serde(rename_all = "camelCase")]
+    End { uuid: String },
+}
+
+/// 批量下载收藏时，每开始处理一部收藏的漫画就发送一次
+#[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
+#[serde(rename_all = "camelCase")]
+pub struct FavoriteBatchEvent {
+    pub current: u32,
+    pub total: u32,
+    pub comic_name: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Type, Event)]
 #[serde(tag = "event", content = "data")]
 pub enum UpdateDownloadedComicsEvent {
@@ -134,6 +245,12 @@ pub enum UpdateDownloadedComicsEvent {
     #[serde(rename_all = "camelCase")]
     CreateDownloadTasksEnd { comic_path_word: String },
 
+    /// 漫画标题 -> 新章节数，只包含有新章节的漫画
+    #[serde(rename_all = "camelCase")]
+    NewChaptersSummary {
+        new_chapter_counts: HashMap<String, i64>,
+    },
+
     #[serde(rename_all = "camelCase")]
     GetComicEnd,
 }
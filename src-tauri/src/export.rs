@@ -1,32 +1,39 @@
 use std::{
     collections::{BTreeMap, HashMap},
-    io::{Read, Write},
+    io::{BufWriter, Cursor, Read, Seek, Write},
     path::{Path, PathBuf},
     sync::{atomic::AtomicU32, Arc},
 };
 
 use anyhow::{anyhow, Context};
 use float_ord::FloatOrd;
+use image::{ImageFormat, Rgba, RgbaImage};
 use lopdf::{
     content::{Content, Operation},
     dictionary, Bookmark, Document, Object, Stream,
 };
 use parking_lot::Mutex;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use specta::Type;
 use tauri::AppHandle;
 use tauri_specta::Event;
-use zip::{write::SimpleFileOptions, ZipWriter};
+use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
 
 use crate::{
-    events::{ExportCbzEvent, ExportPdfEvent},
-    extensions::{AppHandleExt, PathIsImg},
-    types::{ChapterInfo, Comic, ComicInfo},
+    download_manager,
+    events::{ExportCbzEvent, ExportPdfEvent, ExportZipEvent},
+    extensions::{AnyhowErrorToStringChain, AppHandleExt, PathIsImg},
+    types::{ChapterInfo, Comic, ComicInfo, DownloadFormat},
     utils,
 };
 
-enum Archive {
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Type)]
+pub enum Archive {
+    #[default]
     Cbz,
     Pdf,
+    Zip,
 }
 
 impl Archive {
@@ -34,8 +41,24 @@ impl Archive {
         match self {
             Archive::Cbz => "cbz",
             Archive::Pdf => "pdf",
+            Archive::Zip => "zip",
         }
     }
+
+    /// 获取导出根目录，如果配置了该格式专用的导出目录，则优先使用
+    fn get_export_dir(&self, app: &AppHandle) -> PathBuf {
+        let config = app.get_config();
+        let config = config.read();
+        let archive_export_dir = match self {
+            Archive::Cbz => config.cbz_export_dir.as_ref(),
+            Archive::Pdf => config.pdf_export_dir.as_ref(),
+            Archive::Zip => None,
+        };
+        archive_export_dir
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(&config.export_dir)
+            .clone()
+    }
 }
 
 struct CbzErrorEventGuard {
@@ -55,17 +78,21 @@ impl Drop for CbzErrorEventGuard {
     }
 }
 
-#[allow(clippy::cast_possible_wrap)]
-#[allow(clippy::cast_possible_truncation)]
-#[allow(clippy::too_many_lines)]
+/// 在大小为`export_concurrency`的线程池中导出cbz，避免占满全局线程池
 pub fn cbz(app: &AppHandle, comic: &Comic) -> anyhow::Result<()> {
+    let export_concurrency = app.get_config().read().export_concurrency;
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(export_concurrency)
+        .build()
+        .context("rayon线程池创建失败")?;
+
+    thread_pool.install(|| cbz_in_current_pool(app, comic))
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn cbz_in_current_pool(app: &AppHandle, comic: &Comic) -> anyhow::Result<()> {
     let comic_title = &comic.comic.name;
     let downloaded_chapters = get_downloaded_chapters(comic.comic.groups.clone());
-    // 用于生成格式化的xml
-    let xml_cfg = yaserde::ser::Config {
-        perform_indent: true,
-        ..Default::default()
-    };
     let event_uuid = uuid::Uuid::new_v4().to_string();
     // 发送开始导出cbz事件
     let _ = ExportCbzEvent::Start {
@@ -80,151 +107,394 @@ pub fn cbz(app: &AppHandle, comic: &Comic) -> anyhow::Result<()> {
         app: app.clone(),
         success: false,
     };
-    // 用来记录导出进度
-    let current = Arc::new(AtomicU32::new(0));
 
-    let extension = Archive::Cbz.extension();
+    let cbz_export_dir = zip_chapters(app, comic, downloaded_chapters, Archive::Cbz, |current| {
+        // 发送导出cbz进度事件
+        let _ = ExportCbzEvent::Progress {
+            uuid: event_uuid.clone(),
+            current,
+        }
+        .emit(app);
+    })?;
+    // 标记为成功，后面drop时就不会发送Error事件
+    error_event_guard.success = true;
+    // 发送导出cbz完成事件
+    let _ = ExportCbzEvent::End {
+        uuid: event_uuid,
+        chapter_export_dir: cbz_export_dir,
+    }
+    .emit(app);
+
+    Ok(())
+}
+
+struct ZipErrorEventGuard {
+    uuid: String,
+    app: AppHandle,
+    success: bool,
+}
+
+impl Drop for ZipErrorEventGuard {
+    fn drop(&mut self) {
+        if self.success {
+            return;
+        }
+
+        let uuid = self.uuid.clone();
+        let _ = ExportZipEvent::Error { uuid }.emit(&self.app);
+    }
+}
+
+/// 与`cbz`相同，但导出为普通的`.zip`文件，不包含`ComicInfo.xml`
+///
+/// 适用于无法处理cbz元数据的阅读器
+pub fn raw_zip(app: &AppHandle, comic: &Comic) -> anyhow::Result<()> {
+    let export_concurrency = app.get_config().read().export_concurrency;
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(export_concurrency)
+        .build()
+        .context("rayon线程池创建失败")?;
+
+    thread_pool.install(|| raw_zip_in_current_pool(app, comic))
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn raw_zip_in_current_pool(app: &AppHandle, comic: &Comic) -> anyhow::Result<()> {
+    let comic_title = &comic.comic.name;
+    let downloaded_chapters = get_downloaded_chapters(comic.comic.groups.clone());
+    let event_uuid = uuid::Uuid::new_v4().to_string();
+    // 发送开始导出zip事件
+    let _ = ExportZipEvent::Start {
+        uuid: event_uuid.clone(),
+        comic_title: comic_title.clone(),
+        total: downloaded_chapters.len() as u32,
+    }
+    .emit(app);
+    // 如果success为false，drop时发送Error事件
+    let mut error_event_guard = ZipErrorEventGuard {
+        uuid: event_uuid.clone(),
+        app: app.clone(),
+        success: false,
+    };
+
+    let zip_export_dir = zip_chapters(app, comic, downloaded_chapters, Archive::Zip, |current| {
+        // 发送导出zip进度事件
+        let _ = ExportZipEvent::Progress {
+            uuid: event_uuid.clone(),
+            current,
+        }
+        .emit(app);
+    })?;
+    // 标记为成功，后面drop时就不会发送Error事件
+    error_event_guard.success = true;
+    // 发送导出zip完成事件
+    let _ = ExportZipEvent::End {
+        uuid: event_uuid,
+        chapter_export_dir: zip_export_dir,
+    }
+    .emit(app);
+
+    Ok(())
+}
+
+/// 将`downloaded_chapters`中的每个章节打包为一个压缩包，返回该格式的导出目录
+///
+/// `archive`为`Archive::Cbz`时会写入`ComicInfo.xml`，每导出完一个章节就用已完成的章节数调用`on_progress`
+#[allow(clippy::cast_possible_wrap)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::too_many_lines)]
+fn zip_chapters(
+    app: &AppHandle,
+    comic: &Comic,
+    downloaded_chapters: Vec<ChapterInfo>,
+    archive: Archive,
+    on_progress: impl Fn(u32) + Sync,
+) -> anyhow::Result<PathBuf> {
+    let comic_title = &comic.comic.name;
+
+    // 只有cbz才写入ComicInfo.xml
+    let with_comic_info = archive == Archive::Cbz;
+    // 用于生成格式化的xml
+    let xml_cfg = yaserde::ser::Config {
+        perform_indent: true,
+        ..Default::default()
+    };
+    let extension = archive.extension();
+    let export_dir = archive.get_export_dir(app);
     let comic_export_dir = comic
-        .get_comic_export_dir(app)
+        .get_comic_export_dir(app, &export_dir)
         .context(format!("`{comic_title}` 获取导出目录失败"))?;
-    let cbz_export_dir = comic_export_dir.join(extension);
-
-    let separate_chapter_type = app.get_config().read().separate_chapter_type;
+    // 每个章节格式化导出文件名和目录时都要用，只计算一次
+    let chapter_indexes = comic.get_chapter_indexes();
+    let (
+        export_format_subdir,
+        stamp_page_numbers,
+        verify_exports,
+        normalize_format,
+        use_dedicated_cover,
+        skip_unchanged_exports,
+    ) = {
+        let config = app.get_config();
+        let config = config.read();
+        (
+            config.export_format_subdir,
+            config.export_stamp_page_numbers,
+            config.verify_exports,
+            config.export_normalize_format,
+            config.export_use_dedicated_cover,
+            config.skip_unchanged_exports,
+        )
+    };
+    let cover_path = if use_dedicated_cover {
+        get_dedicated_cover_path(comic)
+    } else {
+        None
+    };
+    let archive_export_dir = if export_format_subdir {
+        comic_export_dir.join(extension)
+    } else {
+        comic_export_dir.clone()
+    };
 
-    // 并发处理
-    let downloaded_chapters = downloaded_chapters.into_par_iter();
-    downloaded_chapters.try_for_each(|chapter_info| -> anyhow::Result<()> {
+    let export_chapter = |chapter_info: ChapterInfo| -> anyhow::Result<()> {
         let chapter_title = &chapter_info.chapter_title;
         let group_name = &chapter_info.group_name;
         let err_prefix = format!("`{comic_title} - {group_name} - {chapter_title}`");
-        // 生成ComicInfo
-        let comic_info = ComicInfo::from(comic, &chapter_info);
-        // 序列化ComicInfo为xml
-        let comic_info_xml = yaserde::ser::to_string_with_config(&comic_info, &xml_cfg)
-            .map_err(|err_msg| anyhow!("{err_prefix} 序列化`ComicInfo.xml`失败: {err_msg}"))?;
-        // 创建cbz文件
+        // 创建压缩包
         let chapter_download_dir = chapter_info
             .chapter_download_dir
             .as_ref()
             .context(format!("{err_prefix} `chapter_download_dir`字段为`None`"))?;
-        let chapter_download_dir_name = chapter_download_dir
-            .file_name()
-            .and_then(|name| name.to_str())
-            .context(format!(
-                "{err_prefix} 获取`{}`的目录名失败",
-                chapter_download_dir.display()
-            ))?;
-        let chapter_relative_dir = chapter_info
-            .get_chapter_relative_dir(comic)
-            .context(format!("{err_prefix} 获取章节相对目录失败"))?;
-        let chapter_relative_dir_parent = chapter_relative_dir.parent().context(format!(
-            "{err_prefix} `{}`没有父目录",
-            chapter_relative_dir.display()
+        let mut image_paths = get_image_paths(chapter_download_dir).context(format!(
+            "{err_prefix} 获取`{}`中的图片失败",
+            chapter_download_dir.display()
         ))?;
-
-        let mut chapter_export_dir = comic_export_dir.clone();
-
-        if separate_chapter_type {
-            let type_dir_name = match chapter_info.chapter_type {
-                1 => "话",
-                2 => "卷",
-                3 => "番外",
-                _ => "",
-            };
-            if !type_dir_name.is_empty() {
-                chapter_export_dir = chapter_export_dir.join(&chapter_info.group_name).join(type_dir_name);
-            }
+        // 专用封面作为第一张图片，这样在ComicInfo中会被标记为`FrontCover`
+        if let Some(cover_path) = &cover_path {
+            image_paths.insert(0, cover_path.clone());
         }
-        
-        chapter_export_dir = chapter_export_dir.join(extension);
-        
-        // 如果相对路径中已经包含类型文件夹（由于之前下载时已经分类），
-        // 那么在chapter_relative_dir_parent中可能会包含这个类型前缀。
-        // 我们需要剥离它，因为上面已经根据配置添加过了
-        let mut final_relative_parent = chapter_relative_dir_parent.to_path_buf();
-        // 剥离分组名
-        if let Ok(stripped) = final_relative_parent.strip_prefix(&chapter_info.group_name) {
-            final_relative_parent = stripped.to_path_buf();
-        }
-        // 剥离章节类型
-        for t in ["话", "卷", "番外"] {
-            if let Ok(stripped) = final_relative_parent.strip_prefix(t) {
-                final_relative_parent = stripped.to_path_buf();
-                break;
-            }
-        }
-        chapter_export_dir = chapter_export_dir.join(final_relative_parent);
+        // 生成ComicInfo并序列化为xml
+        let comic_info_xml = if with_comic_info {
+            let comic_info = ComicInfo::from(
+                comic,
+                &chapter_info,
+                image_paths.len(),
+                cover_path.is_some(),
+            );
+            let comic_info_xml = yaserde::ser::to_string_with_config(&comic_info, &xml_cfg)
+                .map_err(|err_msg| anyhow!("{err_prefix} 序列化`ComicInfo.xml`失败: {err_msg}"))?;
+            Some(comic_info_xml)
+        } else {
+            None
+        };
+        let export_filename = chapter_info
+            .get_export_filename_by_fmt(app, comic, &chapter_indexes)
+            .context(format!("{err_prefix} 获取导出文件名失败"))?;
+        let chapter_export_dir = chapter_info
+            .get_chapter_export_dir_by_fmt(
+                app,
+                comic,
+                &chapter_indexes,
+                &comic_export_dir,
+                extension,
+            )
+            .context(format!("{err_prefix} 获取导出目录失败"))?;
         // 保证导出目录存在
         std::fs::create_dir_all(&chapter_export_dir).context(format!(
             "{err_prefix} 创建目录`{}`失败",
             chapter_export_dir.display()
         ))?;
-        let zip_path = chapter_export_dir.join(format!("{chapter_download_dir_name}.{extension}"));
-        let zip_file = std::fs::File::create(&zip_path)
-            .context(format!("{err_prefix} 创建文件`{}`失败", zip_path.display()))?;
-        let mut zip_writer = ZipWriter::new(zip_file);
-        // 把ComicInfo.xml写入cbz
-        zip_writer
-            .start_file("ComicInfo.xml", SimpleFileOptions::default())
-            .context(format!(
-                "{err_prefix} 在`{}`创建`ComicInfo.xml`失败",
+        let zip_path = chapter_export_dir.join(format!("{export_filename}.{extension}"));
+        if skip_unchanged_exports && is_export_up_to_date(&zip_path, &image_paths) {
+            // 跳过的章节也算导出完成，进度由`export_chapters_with_progress`照常更新
+            return Ok(());
+        }
+        // 无法识别格式的原始数据无法处理，只能原样写入压缩包
+        for image_path in image_paths.iter().filter(|path| path.is_unrecognized_img()) {
+            tracing::warn!(
+                comic_title,
+                "`{}`的图片格式无法识别，原样写入`{}`",
+                image_path.display(),
                 zip_path.display()
+            );
+        }
+        // 先写入临时文件，写入并校验成功后才重命名为`zip_path`
+        // 否则中断或失败的导出会留下不完整的压缩包，而它的修改时间比所有图片都新，会被`is_export_up_to_date`当成最新的
+        utils::write_atomically_with(&zip_path, |temp_path| -> anyhow::Result<()> {
+            let zip_file = std::fs::File::create(temp_path).context(format!(
+                "{err_prefix} 创建文件`{}`失败",
+                temp_path.display()
             ))?;
+            let filenames = write_chapter_zip(
+                zip_file,
+                comic_info_xml.as_deref(),
+                image_paths,
+                cover_path.as_ref(),
+                stamp_page_numbers,
+                normalize_format,
+            )
+            .context(format!("{err_prefix} 写入`{}`失败", zip_path.display()))?;
+            if verify_exports {
+                verify_zip(temp_path, with_comic_info, &filenames)
+                    .context(format!("{err_prefix} 校验`{}`失败", zip_path.display()))?;
+            }
+            Ok(())
+        })
+    };
+    export_chapters_with_progress(downloaded_chapters, export_chapter, on_progress)?;
+
+    Ok(archive_export_dir)
+}
+
+/// 把`comic_info_xml`和`image_paths`中的图片写入压缩包`writer`，返回写入的图片文件名，用于校验
+///
+/// 图片逐个从文件流式写入，只有需要加页码或统一格式时才会把单张图片读进内存，
+/// 所以导出有几百张图片的章节时内存占用也不会随图片数量增长
+pub fn write_chapter_zip<W: Write + Seek>(
+    writer: W,
+    comic_info_xml: Option<&str>,
+    image_paths: Vec<PathBuf>,
+    cover_path: Option<&PathBuf>,
+    stamp_page_numbers: bool,
+    normalize_format: Option<DownloadFormat>,
+) -> anyhow::Result<Vec<String>> {
+    // 用BufWriter包裹，避免写入ComicInfo.xml和图片时产生大量小块写入
+    let mut zip_writer = ZipWriter::new(BufWriter::new(writer));
+    // 把ComicInfo.xml写入cbz
+    if let Some(comic_info_xml) = comic_info_xml {
+        zip_writer
+            .start_file("ComicInfo.xml", SimpleFileOptions::default())
+            .context("创建`ComicInfo.xml`失败")?;
         zip_writer
             .write_all(comic_info_xml.as_bytes())
-            .context(format!("{err_prefix} 写入`ComicInfo.xml`失败"))?;
-
-        let image_paths = get_image_paths(chapter_download_dir).context(format!(
-            "{err_prefix} 获取`{}`中的图片失败",
-            chapter_download_dir.display()
-        ))?;
+            .context("写入`ComicInfo.xml`失败")?;
+    }
 
-        for image_path in image_paths {
-            let filename = image_path
-                .file_name()
-                .and_then(|name| name.to_str())
-                .context(format!(
-                    "{err_prefix} 获取`{}`的目录名失败",
-                    chapter_download_dir.display()
+    // 记录写入压缩包的图片文件名，用于校验
+    let mut filenames = Vec::new();
+    // 专用封面不算页码，正文的第一页仍然是1
+    let mut page_number = 0;
+    for image_path in image_paths {
+        let filename = image_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .context(format!("获取`{}`的文件名失败", image_path.display()))?;
+        let is_cover = cover_path == Some(&image_path);
+        if !is_cover {
+            page_number += 1;
+        }
+        let should_stamp = stamp_page_numbers && !is_cover;
+        // 专用封面的文件名要排在所有图片之前
+        let filename = if is_cover {
+            format!("000_{filename}")
+        } else {
+            filename.to_string()
+        };
+        // 统一图片格式时，压缩包中的扩展名也要随之改变
+        let filename = match normalize_format {
+            Some(format) => Path::new(&filename)
+                .with_extension(format.extension())
+                .to_string_lossy()
+                .to_string(),
+            None => filename,
+        };
+        // 将文件写入压缩包
+        zip_writer
+            .start_file(filename.as_str(), SimpleFileOptions::default())
+            .context(format!("创建`{filename:?}`失败"))?;
+        filenames.push(filename);
+        // 无法识别格式的原始数据无法处理，只能原样写入压缩包
+        if !image_path.is_unrecognized_img() && (should_stamp || normalize_format.is_some()) {
+            // 只在导出时处理图片，下载目录中的图片保持不变
+            let mut img_data = read_image_to_buffer(&image_path)
+                .context(format!("读取`{}`失败", image_path.display()))?;
+            if let Some(format) = normalize_format {
+                img_data = normalize_img_format(img_data, format).context(format!(
+                    "将`{}`转换为`{format:?}`失败",
+                    image_path.display()
                 ))?;
-            // 将文件写入cbz
+            }
+            if should_stamp {
+                img_data = stamp_page_number(img_data, page_number)
+                    .context(format!("给`{}`加页码失败", image_path.display()))?;
+            }
             zip_writer
-                .start_file(filename, SimpleFileOptions::default())
-                .context(format!(
-                    "{err_prefix} 在`{}`创建`{filename:?}`失败",
-                    zip_path.display()
-                ))?;
-            let mut file = std::fs::File::open(&image_path)
-                .context(format!("{err_prefix} 打开`{}`失败", image_path.display()))?;
-            std::io::copy(&mut file, &mut zip_writer).context(format!(
-                "{err_prefix} 将`{}`写入`{}`失败",
-                image_path.display(),
-                zip_path.display()
-            ))?;
+                .write_all(&img_data)
+                .context(format!("写入`{}`失败", image_path.display()))?;
+            continue;
         }
+        let mut file = std::fs::File::open(&image_path)
+            .context(format!("打开`{}`失败", image_path.display()))?;
+        std::io::copy(&mut file, &mut zip_writer)
+            .context(format!("写入`{}`失败", image_path.display()))?;
+    }
 
-        zip_writer
-            .finish()
-            .context(format!("{err_prefix} 关闭`{}`失败", zip_path.display()))?;
-        // 更新导出cbz的进度
-        let current = current.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
-        // 发送导出cbz进度事件
-        let _ = ExportCbzEvent::Progress {
-            uuid: event_uuid.clone(),
-            current,
-        }
-        .emit(app);
+    zip_writer
+        .finish()
+        .context("关闭压缩包失败")?
+        .flush()
+        .context("刷新压缩包失败")?;
 
+    Ok(filenames)
+}
+
+/// 在当前rayon线程池中并发地用`export_chapter`导出`chapters`中的每个章节
+///
+/// 每导出完一个章节就用已完成的章节数调用`on_progress`，线程池只有一个线程时章节会按顺序逐个导出
+fn export_chapters_with_progress(
+    chapters: Vec<ChapterInfo>,
+    export_chapter: impl Fn(ChapterInfo) -> anyhow::Result<()> + Sync + Send,
+    on_progress: impl Fn(u32) + Sync,
+) -> anyhow::Result<()> {
+    // 用来记录导出进度
+    let current = AtomicU32::new(0);
+    chapters.into_par_iter().try_for_each(|chapter_info| {
+        export_chapter(chapter_info)?;
+        // 更新导出进度
+        let current = current.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+        on_progress(current);
         Ok(())
-    })?;
-    // 标记为成功，后面drop时就不会发送Error事件
-    error_event_guard.success = true;
-    // 发送导出cbz完成事件
-    let _ = ExportCbzEvent::End {
-        uuid: event_uuid,
-        chapter_export_dir: cbz_export_dir,
+    })
+}
+
+/// `export_path`已存在，且修改时间比`image_paths`中所有图片都新时返回`true`
+///
+/// 只比较修改时间，修改导出相关的配置(如`export_stamp_page_numbers`)后不会因此重新导出
+fn is_export_up_to_date(export_path: &Path, image_paths: &[PathBuf]) -> bool {
+    let Ok(export_modified) = export_path
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+    else {
+        return false;
+    };
+    image_paths.iter().all(|image_path| {
+        image_path
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified < export_modified)
+    })
+}
+
+/// 重新打开`zip_path`，确认`filenames`中的每张图片都存在且能完整读取，`with_comic_info`为`true`时还要确认`ComicInfo.xml`
+///
+/// 读取完整个条目时zip库会校验CRC，所以也能发现数据损坏
+fn verify_zip(zip_path: &Path, with_comic_info: bool, filenames: &[String]) -> anyhow::Result<()> {
+    let zip_file =
+        std::fs::File::open(zip_path).context(format!("打开`{}`失败", zip_path.display()))?;
+    let mut zip_archive = ZipArchive::new(std::io::BufReader::new(zip_file))
+        .context(format!("`{}`不是有效的zip文件", zip_path.display()))?;
+
+    let comic_info_entry_name = with_comic_info.then_some("ComicInfo.xml");
+    let entry_names = comic_info_entry_name
+        .into_iter()
+        .chain(filenames.iter().map(String::as_str));
+    for entry_name in entry_names {
+        let mut entry = zip_archive
+            .by_name(entry_name)
+            .context(format!("找不到`{entry_name}`"))?;
+        std::io::copy(&mut entry, &mut std::io::sink())
+            .context(format!("读取`{entry_name}`失败"))?;
     }
-    .emit(app);
 
     Ok(())
 }
@@ -263,9 +533,181 @@ impl Drop for PdfMergeErrorEventGuard {
     }
 }
 
+pub fn pdf(app: &AppHandle, comic: &Comic) -> anyhow::Result<()> {
+    let create_pdf_concurrency = app.get_config().read().create_pdf_concurrency;
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(create_pdf_concurrency)
+        .build()
+        .context("rayon线程池创建失败")?;
+
+    thread_pool.install(|| pdf_in_current_pool(app, comic))
+}
+
+/// 按照漫画的`preferred_export_archive`导出，没有设置时使用`config.default_export_archive`
+pub fn comic(app: &AppHandle, comic: &Comic) -> anyhow::Result<()> {
+    match get_preferred_archive(app, comic) {
+        Archive::Cbz => cbz(app, comic),
+        Archive::Pdf => pdf(app, comic),
+        Archive::Zip => raw_zip(app, comic),
+    }
+}
+
+/// 根据已下载的元数据和图片重新导出漫画，不需要重新下载
+///
+/// `chapter_download_dir`不存在的章节会被跳过
+pub fn reexport(app: &AppHandle, comic_path_word: &str, archive: Archive) -> anyhow::Result<()> {
+    let path_word_to_dir_map =
+        utils::create_path_word_to_dir_map(app).context("创建漫画路径词到下载目录映射失败")?;
+    let comic_download_dir = path_word_to_dir_map
+        .get(comic_path_word)
+        .and_then(|dirs| dirs.first())
+        .context(format!("未找到路径词为`{comic_path_word}`的已下载漫画"))?;
+    let metadata_path = comic_download_dir.join("元数据.json");
+    let mut comic = Comic::from_metadata(&metadata_path)?;
+
+    for chapter_info in comic.comic.groups.values_mut().flatten() {
+        let chapter_dir_exists = chapter_info
+            .chapter_download_dir
+            .as_ref()
+            .is_some_and(|dir| dir.exists());
+        if !chapter_dir_exists {
+            chapter_info.is_downloaded = Some(false);
+        }
+    }
+
+    match archive {
+        Archive::Cbz => cbz(app, &comic),
+        Archive::Pdf => pdf(app, &comic),
+        Archive::Zip => raw_zip(app, &comic),
+    }
+}
+
+fn get_preferred_archive(app: &AppHandle, comic: &Comic) -> Archive {
+    comic
+        .preferred_export_archive
+        .unwrap_or_else(|| app.get_config().read().default_export_archive)
+}
+
+/// 批量导出多部漫画，`archive`为`None`时按照每部漫画的`preferred_export_archive`导出
+///
+/// 所有漫画及其章节共用一个大小为`export_concurrency`的线程池，以限制总并发数
+pub fn batch(app: &AppHandle, comics: &[Comic], archive: Option<Archive>) -> anyhow::Result<()> {
+    let export_concurrency = app.get_config().read().export_concurrency;
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(export_concurrency)
+        .build()
+        .context("rayon线程池创建失败")?;
+
+    thread_pool.install(|| {
+        comics.par_iter().for_each(|comic| {
+            let comic_title = &comic.comic.name;
+            let archive = archive.unwrap_or_else(|| get_preferred_archive(app, comic));
+            let extension = archive.extension();
+            let result = match archive {
+                Archive::Cbz => cbz_in_current_pool(app, comic),
+                Archive::Pdf => pdf_in_current_pool(app, comic),
+                Archive::Zip => raw_zip_in_current_pool(app, comic),
+            };
+            // 某部漫画导出失败不影响其他漫画
+            if let Err(err) = result.context(format!("漫画`{comic_title}`导出{extension}失败"))
+            {
+                let err_title = "批量导出的过程中遇到错误，已跳过";
+                let string_chain = err.to_string_chain();
+                tracing::error!(err_title, message = string_chain);
+            }
+        });
+    });
+
+    Ok(())
+}
+
+/// 将多部漫画的已下载章节合并为一个PDF，保存到导出目录下的`{title}.pdf`，返回PDF的路径
+///
+/// 每部漫画是一级书签，每个章节是二级书签
+pub fn combined_pdf(app: &AppHandle, comics: &[Comic], title: &str) -> anyhow::Result<PathBuf> {
+    let (create_pdf_concurrency, stamp_page_numbers, normalize_format) = {
+        let config = app.get_config();
+        let config = config.read();
+        (
+            config.create_pdf_concurrency,
+            config.export_stamp_page_numbers,
+            config.export_normalize_format,
+        )
+    };
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(create_pdf_concurrency)
+        .build()
+        .context("rayon线程池创建失败")?;
+
+    let export_dir = Archive::Pdf.get_export_dir(app);
+    let sanitize_mode = app.get_config().read().filename_sanitize_mode;
+    let title = utils::filename_filter(title, sanitize_mode);
+    let pdf_path = export_dir.join(format!("{title}.{}", Archive::Pdf.extension()));
+    // 章节PDF只是中间产物，放在临时目录中，合并后删除
+    let temp_dir = export_dir.join(format!(".{title}.{}.tmp", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&temp_dir).context(format!("创建目录`{}`失败", temp_dir.display()))?;
+
+    let result = thread_pool.install(|| -> anyhow::Result<()> {
+        let mut sections = Vec::new();
+        for (comic_index, comic) in comics.iter().enumerate() {
+            let comic_title = &comic.comic.name;
+            let mut downloaded_chapters = get_downloaded_chapters(comic.comic.groups.clone());
+            downloaded_chapters
+                .sort_by_key(|chapter_info| FloatOrd(comic.get_chapter_order(chapter_info)));
+
+            let chapter_pdf_paths = downloaded_chapters
+                .into_par_iter()
+                .enumerate()
+                .map(|(chapter_index, chapter_info)| -> anyhow::Result<PathBuf> {
+                    let chapter_title = &chapter_info.chapter_title;
+                    let err_prefix = format!("`{comic_title} - {chapter_title}`");
+                    let chapter_download_dir = chapter_info
+                        .chapter_download_dir
+                        .as_ref()
+                        .context(format!("{err_prefix} `chapter_download_dir`字段为`None`"))?;
+                    let image_paths = get_image_paths(chapter_download_dir).context(format!(
+                        "{err_prefix} 获取`{}`中的图片失败",
+                        chapter_download_dir.display()
+                    ))?;
+                    // 合并时用文件名作为书签标题，所以文件名用章节名，前缀只用来避免重名
+                    let chapter_pdf_dir = temp_dir.join(format!("{comic_index}-{chapter_index}"));
+                    std::fs::create_dir_all(&chapter_pdf_dir).context(format!(
+                        "{err_prefix} 创建目录`{}`失败",
+                        chapter_pdf_dir.display()
+                    ))?;
+                    let chapter_pdf_path = chapter_pdf_dir.join(format!(
+                        "{}.{}",
+                        utils::filename_filter(chapter_title, sanitize_mode),
+                        Archive::Pdf.extension()
+                    ));
+                    create_pdf(
+                        image_paths,
+                        &chapter_pdf_path,
+                        None,
+                        stamp_page_numbers,
+                        normalize_format,
+                    )
+                    .context(format!("{err_prefix} 创建pdf失败"))?;
+                    Ok(chapter_pdf_path)
+                })
+                .collect::<anyhow::Result<Vec<PathBuf>>>()?;
+
+            sections.push((Some(comic_title.clone()), chapter_pdf_paths));
+        }
+        // 合并PDF很吃内存，和单部漫画的合并一样逐个加载章节PDF
+        merge_pdf_sections(sections, &pdf_path).context("合并pdf失败")
+    });
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    result?;
+
+    Ok(pdf_path)
+}
+
+/// 在当前的rayon线程池中导出pdf
 #[allow(clippy::cast_possible_truncation)]
 #[allow(clippy::too_many_lines)]
-pub fn pdf(app: &AppHandle, comic: &Comic) -> anyhow::Result<()> {
+fn pdf_in_current_pool(app: &AppHandle, comic: &Comic) -> anyhow::Result<()> {
     let comic_title = &comic.comic.name;
     let downloaded_chapters = get_downloaded_chapters(comic.comic.groups.clone());
     let create_event_uuid = uuid::Uuid::new_v4().to_string();
@@ -286,104 +728,105 @@ pub fn pdf(app: &AppHandle, comic: &Comic) -> anyhow::Result<()> {
     let created_count = Arc::new(AtomicU32::new(0));
 
     let extension = Archive::Pdf.extension();
+    let export_dir = Archive::Pdf.get_export_dir(app);
     let comic_export_dir = comic
-        .get_comic_export_dir(app)
+        .get_comic_export_dir(app, &export_dir)
         .context(format!("`{comic_title}` 获取导出目录失败"))?;
-    let pdf_export_dir = comic_export_dir.join(extension);
+    // 每个章节格式化导出文件名和目录时都要用，只计算一次
+    let chapter_indexes = comic.get_chapter_indexes();
+    let (
+        export_format_subdir,
+        stamp_page_numbers,
+        normalize_format,
+        use_dedicated_cover,
+        skip_unchanged_exports,
+    ) = {
+        let config = app.get_config();
+        let config = config.read();
+        (
+            config.export_format_subdir,
+            config.export_stamp_page_numbers,
+            config.export_normalize_format,
+            config.export_use_dedicated_cover,
+            config.skip_unchanged_exports,
+        )
+    };
+    let cover_path = if use_dedicated_cover {
+        get_dedicated_cover_path(comic)
+    } else {
+        None
+    };
+    let pdf_export_dir = if export_format_subdir {
+        comic_export_dir.join(extension)
+    } else {
+        comic_export_dir.clone()
+    };
     // 章节和他们对应的pdf路径
     let chapter_and_pdf_path_pairs = Mutex::new(Vec::new());
     // 并发处理
-    let separate_chapter_type = app.get_config().read().separate_chapter_type;
-    let create_pdf_concurrency = app.get_config().read().create_pdf_concurrency;
-    let thread_pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(create_pdf_concurrency)
-        .build()
-        .context("rayon线程池创建失败")?;
-
-    thread_pool.install(|| {
-        let downloaded_chapters = downloaded_chapters.into_par_iter();
-        downloaded_chapters.try_for_each(|chapter_info| -> anyhow::Result<()> {
-            let chapter_title = &chapter_info.chapter_title;
-            let group_name = &chapter_info.group_name;
-            let err_prefix = format!("`{comic_title} - {group_name} - {chapter_title}`");
-            // 创建pdf文件
-            let chapter_download_dir = chapter_info
-                .chapter_download_dir
-                .as_ref()
-                .context(format!("{err_prefix} `chapter_download_dir`字段为`None`"))?;
-            let chapter_download_dir_name = chapter_download_dir
-                .file_name()
-                .and_then(|name| name.to_str())
-                .context(format!(
-                    "{err_prefix} 获取`{}`的目录名失败",
-                    chapter_download_dir.display()
-                ))?;
-            let chapter_relative_dir = chapter_info
-                .get_chapter_relative_dir(comic)
-                .context(format!("{err_prefix} 获取章节相对目录失败"))?;
-            let chapter_relative_dir_parent = chapter_relative_dir.parent().context(format!(
-                "{err_prefix} `{}`没有父目录",
-                chapter_relative_dir.display()
-            ))?;
-
-            let mut chapter_export_dir = comic_export_dir.clone();
+    let downloaded_chapters = downloaded_chapters.into_par_iter();
+    downloaded_chapters.try_for_each(|chapter_info| -> anyhow::Result<()> {
+        let chapter_title = &chapter_info.chapter_title;
+        let group_name = &chapter_info.group_name;
+        let err_prefix = format!("`{comic_title} - {group_name} - {chapter_title}`");
+        // 创建pdf文件
+        let chapter_download_dir = chapter_info
+            .chapter_download_dir
+            .as_ref()
+            .context(format!("{err_prefix} `chapter_download_dir`字段为`None`"))?;
+        let export_filename = chapter_info
+            .get_export_filename_by_fmt(app, comic, &chapter_indexes)
+            .context(format!("{err_prefix} 获取导出文件名失败"))?;
+        let chapter_export_dir = chapter_info
+            .get_chapter_export_dir_by_fmt(
+                app,
+                comic,
+                &chapter_indexes,
+                &comic_export_dir,
+                extension,
+            )
+            .context(format!("{err_prefix} 获取导出目录失败"))?;
+        // 保证导出目录存在
+        std::fs::create_dir_all(&chapter_export_dir).context(format!(
+            "{err_prefix} 创建目录`{}`失败",
+            chapter_export_dir.display()
+        ))?;
 
-            if separate_chapter_type {
-                let type_dir_name = match chapter_info.chapter_type {
-                    1 => "话",
-                    2 => "卷",
-                    3 => "番外",
-                    _ => "",
-                };
-                if !type_dir_name.is_empty() {
-                    chapter_export_dir = chapter_export_dir.join(&chapter_info.group_name).join(type_dir_name);
-                }
-            }
-            
-            chapter_export_dir = chapter_export_dir.join(extension);
-            
-            let mut final_relative_parent = chapter_relative_dir_parent.to_path_buf();
-            // 剥离分组名
-            if let Ok(stripped) = final_relative_parent.strip_prefix(&chapter_info.group_name) {
-                final_relative_parent = stripped.to_path_buf();
-            }
-            // 剥离章节类型
-            for t in ["话", "卷", "番外"] {
-                if let Ok(stripped) = final_relative_parent.strip_prefix(t) {
-                    final_relative_parent = stripped.to_path_buf();
-                    break;
-                }
-            }
-            chapter_export_dir = chapter_export_dir.join(final_relative_parent);
-            // 保证导出目录存在
-            std::fs::create_dir_all(&chapter_export_dir).context(format!(
-                "{err_prefix} 创建目录`{}`失败",
-                chapter_export_dir.display()
-            ))?;
+        let pdf_path = chapter_export_dir.join(format!("{export_filename}.{extension}"));
 
-            let pdf_path =
-                chapter_export_dir.join(format!("{chapter_download_dir_name}.{extension}"));
+        let mut image_paths = get_image_paths(chapter_download_dir).context(format!(
+            "{err_prefix} 获取`{}`中的图片失败",
+            chapter_download_dir.display()
+        ))?;
+        // 专用封面作为第一页
+        if let Some(cover_path) = &cover_path {
+            image_paths.insert(0, cover_path.clone());
+        }
 
-            let image_paths = get_image_paths(chapter_download_dir).context(format!(
-                "{err_prefix} 获取`{}`中的图片失败",
-                chapter_download_dir.display()
-            ))?;
+        // 跳过的章节PDF仍然要参与后面的合并
+        if !skip_unchanged_exports || !is_export_up_to_date(&pdf_path, &image_paths) {
+            create_pdf(
+                image_paths,
+                &pdf_path,
+                cover_path.as_ref(),
+                stamp_page_numbers,
+                normalize_format,
+            )
+            .context(format!("{err_prefix} 创建pdf失败"))?;
+        }
 
-            create_pdf(image_paths, &pdf_path).context(format!("{err_prefix} 创建pdf失败"))?;
-
-            chapter_and_pdf_path_pairs
-                .lock()
-                .push((chapter_info, pdf_path));
-            // 更新创建pdf的进度
-            let current = created_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
-            // 发送创建pdf进度事件
-            let _ = ExportPdfEvent::CreateProgress {
-                uuid: create_event_uuid.clone(),
-                current,
-            }
-            .emit(app);
-            Ok(())
-        })
+        chapter_and_pdf_path_pairs
+            .lock()
+            .push((chapter_info, pdf_path));
+        // 更新创建pdf的进度
+        let current = created_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+        // 发送创建pdf进度事件
+        let _ = ExportPdfEvent::CreateProgress {
+            uuid: create_event_uuid.clone(),
+            current,
+        }
+        .emit(app);
+        Ok(())
     })?;
     // 标记为成功，后面drop时就不会发送CreateError事件
     create_error_event_guard.success = true;
@@ -400,7 +843,8 @@ pub fn pdf(app: &AppHandle, comic: &Comic) -> anyhow::Result<()> {
     }
 
     let mut chapter_and_pdf_path_pairs = std::mem::take(&mut *chapter_and_pdf_path_pairs.lock());
-    chapter_and_pdf_path_pairs.sort_by_key(|(chapter_info, _)| FloatOrd(chapter_info.order));
+    chapter_and_pdf_path_pairs
+        .sort_by_key(|(chapter_info, _)| FloatOrd(comic.get_chapter_order(chapter_info)));
     let chapter_pdf_paths: Vec<PathBuf> = chapter_and_pdf_path_pairs
         .into_iter()
         .map(|(_, pdf_path)| pdf_path)
@@ -473,18 +917,47 @@ pub fn pdf(app: &AppHandle, comic: &Comic) -> anyhow::Result<()> {
 /// 用`image_paths`中的图片创建PDF文件，保存到`pdf_path`
 #[allow(clippy::similar_names)]
 #[allow(clippy::cast_possible_truncation)]
-fn create_pdf(image_paths: Vec<PathBuf>, pdf_path: &Path) -> anyhow::Result<()> {
+fn create_pdf(
+    image_paths: Vec<PathBuf>,
+    pdf_path: &Path,
+    cover_path: Option<&PathBuf>,
+    stamp_page_numbers: bool,
+    normalize_format: Option<DownloadFormat>,
+) -> anyhow::Result<()> {
     let mut doc = Document::with_version("1.5");
     let pages_id = doc.new_object_id();
     let mut page_ids = vec![];
+    // 页码使用PDF的标准字体，无需嵌入字体文件
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
 
+    // 专用封面不算页码，正文的第一页仍然是1
+    let mut page_number = 0;
     for image_path in image_paths {
         if !image_path.is_file() {
             continue;
         }
+        // 无法识别格式的原始数据无法嵌入PDF，跳过这一页
+        if image_path.is_unrecognized_img() {
+            tracing::warn!(
+                "`{}`的图片格式无法识别，创建`{}`时跳过这一页",
+                image_path.display(),
+                pdf_path.display()
+            );
+            continue;
+        }
 
-        let buffer = read_image_to_buffer(&image_path)
+        let mut buffer = read_image_to_buffer(&image_path)
             .context(format!("将`{}`读取到buffer失败", image_path.display()))?;
+        if let Some(format) = normalize_format {
+            buffer = normalize_img_format(buffer, format).context(format!(
+                "将`{}`转换为`{format:?}`失败",
+                image_path.display()
+            ))?;
+        }
         let (width, height) = utils::get_dimensions(&buffer)
             .context(format!("获取`{}`的尺寸失败", image_path.display()))?;
         let image_stream = lopdf::xobject::image_from(buffer)
@@ -509,15 +982,29 @@ fn create_pdf(image_paths: Vec<PathBuf>, pdf_path: &Path) -> anyhow::Result<()>
         let do_operation = Operation::new("Do", vec![Object::Name(img_name.as_bytes().to_vec())]);
         // 创建页面，设置图片的位置和大小，然后显示图片
         // 因为是从零开始创建PDF，所以没必要用 q 和 Q 操作保存和恢复图形状态
-        let content = Content {
-            operations: vec![cm_operation, do_operation],
-        };
+        let mut operations = vec![cm_operation, do_operation];
+        let is_cover = cover_path == Some(&image_path);
+        if !is_cover {
+            page_number += 1;
+        }
+        if stamp_page_numbers && !is_cover {
+            // 页码需要在未经 cm 变换的坐标系中绘制，所以此时要用 q 和 Q 包裹图片的绘制
+            operations.insert(0, Operation::new("q", vec![]));
+            operations.push(Operation::new("Q", vec![]));
+            operations.extend(page_number_operations(page_number, width, height));
+        }
+        let content = Content { operations };
         let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode()?));
         let page_id = doc.add_object(dictionary! {
             "Type" => "Page",
             "Parent" => pages_id,
             "Contents" => content_id,
             "MediaBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+            "Resources" => dictionary! {
+                "Font" => dictionary! {
+                    "F1" => font_id,
+                },
+            },
         });
         // 将图片以 XObject 的形式添加到文档中
         // Do 操作只能引用 XObject(所以前面定义的 Do 操作的参数是 img_name, 而不是 img_id)
@@ -541,9 +1028,142 @@ fn create_pdf(image_paths: Vec<PathBuf>, pdf_path: &Path) -> anyhow::Result<()>
 
     doc.compress();
 
-    doc.save(pdf_path)
-        .context(format!("保存`{}`失败", pdf_path.display()))?;
-    Ok(())
+    // 先保存到临时文件再重命名，中断或失败时不会留下不完整的pdf
+    utils::write_atomically_with(pdf_path, |temp_path| {
+        doc.save(temp_path)
+            .context(format!("保存`{}`失败", pdf_path.display()))?;
+        Ok(())
+    })
+}
+
+/// 生成在页面右下角绘制页码的操作(白底黑字)
+#[allow(clippy::cast_precision_loss)]
+fn page_number_operations(page_number: usize, width: u32, height: u32) -> Vec<Operation> {
+    let text = page_number.to_string();
+    let font_size = (height as f32 / 50.0).max(12.0);
+    let margin = font_size / 2.0;
+    // Helvetica数字的宽度为字号的0.556倍
+    let text_width = font_size * 0.556 * text.len() as f32;
+    let x = width as f32 - margin - text_width;
+    let y = margin;
+
+    vec![
+        // 白色背景
+        Operation::new("g", vec![1.into()]),
+        Operation::new(
+            "re",
+            vec![
+                (x - margin / 2.0).into(),
+                (y - margin / 2.0).into(),
+                (text_width + margin).into(),
+                (font_size + margin / 2.0).into(),
+            ],
+        ),
+        Operation::new("f", vec![]),
+        // 黑色页码
+        Operation::new("g", vec![0.into()]),
+        Operation::new("BT", vec![]),
+        Operation::new("Tf", vec!["F1".into(), font_size.into()]),
+        Operation::new("Td", vec![x.into(), y.into()]),
+        Operation::new("Tj", vec![Object::string_literal(text)]),
+        Operation::new("ET", vec![]),
+    ]
+}
+
+/// 3x5点阵数字字体，每行用低3位表示
+const DIGIT_GLYPHS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// 在图片数据`buffer`的右下角绘制页码后重新编码
+///
+/// `image`库无法编码的格式(例如jxl)会原样返回
+fn stamp_page_number(buffer: Vec<u8>, page_number: usize) -> anyhow::Result<Vec<u8>> {
+    let format = match image::guess_format(&buffer) {
+        Ok(format @ (ImageFormat::Jpeg | ImageFormat::WebP)) => format,
+        _ => return Ok(buffer),
+    };
+    let mut img = image::load_from_memory_with_format(&buffer, format)
+        .context("解码图片失败")?
+        .to_rgba8();
+
+    draw_page_number(&mut img, page_number);
+
+    let mut stamped_data = Vec::new();
+    let mut cursor = Cursor::new(&mut stamped_data);
+    match format {
+        ImageFormat::Jpeg => image::DynamicImage::ImageRgba8(img)
+            .to_rgb8()
+            .write_to(&mut cursor, ImageFormat::Jpeg),
+        _ => img.write_to(&mut cursor, format),
+    }
+    .context("编码图片失败")?;
+
+    Ok(stamped_data)
+}
+
+/// 将图片数据`buffer`转换为`target_format`格式，已经是`target_format`格式的原样返回
+fn normalize_img_format(buffer: Vec<u8>, target_format: DownloadFormat) -> anyhow::Result<Vec<u8>> {
+    let src_format = image::guess_format(&buffer).context("无法识别图片格式")?;
+    if target_format.to_image_format() == Some(src_format) {
+        return Ok(buffer);
+    }
+    download_manager::convert_img(&buffer, src_format, target_format)
+}
+
+/// 用`DIGIT_GLYPHS`在`img`的右下角绘制页码(白底黑字)
+fn draw_page_number(img: &mut RgbaImage, page_number: usize) {
+    let digits: Vec<usize> = page_number
+        .to_string()
+        .bytes()
+        .map(|b| usize::from(b - b'0'))
+        .collect();
+    // 每个点阵像素的边长
+    let scale = (img.height() / 200).max(2);
+    let digit_count = u32::try_from(digits.len()).unwrap_or(u32::MAX);
+    // 每个数字宽3格，数字之间空1格，四周留2格边距
+    let box_width = (digit_count * 4 + 3) * scale;
+    let box_height = 9 * scale;
+    if box_width > img.width() || box_height > img.height() {
+        return;
+    }
+    let box_x = img.width() - box_width;
+    let box_y = img.height() - box_height;
+
+    for y in box_y..img.height() {
+        for x in box_x..img.width() {
+            img.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+        }
+    }
+
+    for (i, digit) in digits.into_iter().enumerate() {
+        let i = u32::try_from(i).unwrap_or(u32::MAX);
+        let digit_x = box_x + (2 + i * 4) * scale;
+        let digit_y = box_y + 2 * scale;
+        for (row, bits) in (0u32..).zip(DIGIT_GLYPHS[digit]) {
+            for col in 0..3 {
+                if bits & (0b100 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let x = digit_x + col * scale + dx;
+                        let y = digit_y + row * scale + dy;
+                        img.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+                    }
+                }
+            }
+        }
+    }
 }
 
 /// 读取`image_path`中的图片数据到buffer中
@@ -559,36 +1179,55 @@ fn read_image_to_buffer(image_path: &Path) -> anyhow::Result<Vec<u8>> {
 }
 
 /// 将`pdf_dir`中的PDF合并到`pdf_path`中
-#[allow(clippy::cast_possible_truncation)]
 fn merge_pdf_file(chapter_pdf_paths: Vec<PathBuf>, pdf_path: &Path) -> anyhow::Result<()> {
+    merge_pdf_sections(vec![(None, chapter_pdf_paths)], pdf_path)
+}
+
+/// 将多组PDF合并到`pdf_path`中，每个PDF的第一页会添加以文件名为标题的书签
+///
+/// 组的标题不为`None`时，会为这一组创建一级书签，组内PDF的书签作为它的二级书签
+#[allow(clippy::cast_possible_truncation)]
+fn merge_pdf_sections(
+    sections: Vec<(Option<String>, Vec<PathBuf>)>,
+    pdf_path: &Path,
+) -> anyhow::Result<()> {
     let mut doc = Document::with_version("1.5");
     let mut doc_page_ids = vec![];
     let mut doc_objects = BTreeMap::new();
 
-    for chapter_pdf_path in chapter_pdf_paths {
-        let mut chapter_doc = Document::load(&chapter_pdf_path)
-            .context(format!("加载`{}`失败", chapter_pdf_path.display()))?;
-        // 重新编号这个章节PDF的对象，避免与doc的对象编号冲突
-        chapter_doc.renumber_objects_with(doc.max_id);
-        doc.max_id = chapter_doc.max_id + 1;
-        // 获取这个章节PDF中的所有页面，并给第一个页面添加书签
-        let mut chapter_page_ids = vec![];
-        for (page_num, object_id) in chapter_doc.get_pages() {
-            // 第一个页面需要添加书签
-            if page_num == 1 {
-                let chapter_title = chapter_pdf_path
-                    .file_stem()
-                    .and_then(|file_stem| file_stem.to_str())
-                    .context(format!("获取`{}`的文件名失败", chapter_pdf_path.display()))?
-                    .to_string();
-                let bookmark = Bookmark::new(chapter_title, [0.0, 0.0, 1.0], 0, object_id);
-                doc.add_bookmark(bookmark, None);
+    for (section_title, chapter_pdf_paths) in sections {
+        let mut section_bookmark_id = None;
+        for chapter_pdf_path in chapter_pdf_paths {
+            let mut chapter_doc = Document::load(&chapter_pdf_path)
+                .context(format!("加载`{}`失败", chapter_pdf_path.display()))?;
+            // 重新编号这个章节PDF的对象，避免与doc的对象编号冲突
+            chapter_doc.renumber_objects_with(doc.max_id);
+            doc.max_id = chapter_doc.max_id + 1;
+            // 获取这个章节PDF中的所有页面，并给第一个页面添加书签
+            let mut chapter_page_ids = vec![];
+            for (page_num, object_id) in chapter_doc.get_pages() {
+                // 第一个页面需要添加书签
+                if page_num == 1 {
+                    // 组的一级书签指向组内第一个PDF的第一页
+                    if let (Some(section_title), None) = (&section_title, section_bookmark_id) {
+                        let bookmark =
+                            Bookmark::new(section_title.clone(), [0.0, 0.0, 1.0], 0, object_id);
+                        section_bookmark_id = Some(doc.add_bookmark(bookmark, None));
+                    }
+                    let chapter_title = chapter_pdf_path
+                        .file_stem()
+                        .and_then(|file_stem| file_stem.to_str())
+                        .context(format!("获取`{}`的文件名失败", chapter_pdf_path.display()))?
+                        .to_string();
+                    let bookmark = Bookmark::new(chapter_title, [0.0, 0.0, 1.0], 0, object_id);
+                    doc.add_bookmark(bookmark, section_bookmark_id);
+                }
+                chapter_page_ids.push(object_id);
             }
-            chapter_page_ids.push(object_id);
-        }
 
-        doc_page_ids.extend(chapter_page_ids);
-        doc_objects.extend(chapter_doc.objects);
+            doc_page_ids.extend(chapter_page_ids);
+            doc_objects.extend(chapter_doc.objects);
+        }
     }
     // 在doc中新建一个"Pages"对象，将所有章节的页面添加到这个"Pages"对象中
     let pages_id = doc.add_object(dictionary! {
@@ -633,9 +1272,12 @@ fn merge_pdf_file(chapter_pdf_paths: Vec<PathBuf>, pdf_path: &Path) -> anyhow::R
 
     doc.compress();
 
-    doc.save(pdf_path)
-        .context(format!("保存`{}`失败", pdf_path.display()))?;
-    Ok(())
+    // 先保存到临时文件再重命名，中断或失败时不会留下不完整的pdf
+    utils::write_atomically_with(pdf_path, |temp_path| {
+        doc.save(temp_path)
+            .context(format!("保存`{}`失败", pdf_path.display()))?;
+        Ok(())
+    })
 }
 
 /// 获取已下载的章节
@@ -647,13 +1289,154 @@ fn get_downloaded_chapters(groups: HashMap<String, Vec<ChapterInfo>>) -> Vec<Cha
         .collect()
 }
 
+/// 漫画下载目录中的专用封面`cover.<ext>`，不存在时返回`None`
+fn get_dedicated_cover_path(comic: &Comic) -> Option<PathBuf> {
+    let comic_download_dir = comic.comic_download_dir.as_ref()?;
+    Comic::get_cover_path(comic_download_dir)
+}
+
+/// 获取`images_dir`中的所有图片，按文件名开头的整数排序(见`utils::natural_cmp`)
+///
+/// 只保留`is_img`或`is_unrecognized_img`为`true`的文件，`章节元数据.json`、`progress.json`等非图片文件都会被排除
+///
+/// 无论`image_name_fmt`是什么，下载时都保证了文件名的顺序与页码顺序一致
 fn get_image_paths(images_dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
     let mut image_paths: Vec<PathBuf> = std::fs::read_dir(images_dir)
         .context(format!("读取目录`{}`失败", images_dir.display()))?
         .filter_map(Result::ok)
         .map(|entry| entry.path())
-        .filter(|path| path.is_img())
+        .filter(|path| path.is_img() || path.is_unrecognized_img())
         .collect();
-    image_paths.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    image_paths.sort_by(|a, b| {
+        let a_filename = a.file_name().unwrap_or_default().to_string_lossy();
+        let b_filename = b.file_name().unwrap_or_default().to_string_lossy();
+        utils::natural_cmp(&a_filename, &b_filename)
+    });
     Ok(image_paths)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_dir;
+
+    /// 在`path`创建一张纯色的jpg，返回它的数据
+    fn create_jpg(path: &Path, color: [u8; 3]) -> Vec<u8> {
+        let img = image::RgbImage::from_pixel(64, 64, image::Rgb(color));
+        img.save_with_format(path, ImageFormat::Jpeg).unwrap();
+        std::fs::read(path).unwrap()
+    }
+
+    /// 读取压缩包`zip_data`中名为`filename`的文件
+    fn read_zip_entry(zip_data: &[u8], filename: &str) -> Vec<u8> {
+        let mut zip_archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        let mut entry = zip_archive.by_name(filename).unwrap();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    fn create_chapters(count: usize) -> Vec<ChapterInfo> {
+        (0..count)
+            .map(|i| ChapterInfo {
+                chapter_title: format!("第{}话", i + 1),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_thread_pool_exports_chapters_in_order() {
+        let thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let chapters = create_chapters(5);
+        let expected_titles: Vec<String> = chapters
+            .iter()
+            .map(|chapter_info| chapter_info.chapter_title.clone())
+            .collect();
+        // 按发生顺序记录导出和进度事件，用来检查它们没有交错
+        let events = Mutex::new(Vec::new());
+
+        thread_pool
+            .install(|| {
+                export_chapters_with_progress(
+                    chapters,
+                    |chapter_info| {
+                        let title = &chapter_info.chapter_title;
+                        events.lock().push(format!("start {title}"));
+                        events.lock().push(format!("end {title}"));
+                        Ok(())
+                    },
+                    |current| events.lock().push(format!("progress {current}")),
+                )
+            })
+            .unwrap();
+
+        let expected_events: Vec<String> = expected_titles
+            .iter()
+            .enumerate()
+            .flat_map(|(i, title)| {
+                [
+                    format!("start {title}"),
+                    format!("end {title}"),
+                    format!("progress {}", i + 1),
+                ]
+            })
+            .collect();
+        assert_eq!(events.into_inner(), expected_events);
+    }
+
+    #[test]
+    fn json_sidecars_are_not_image_paths() {
+        let chapter_dir = create_test_dir();
+        for filename in ["10.webp", "2.jpg", "1.bin"] {
+            std::fs::write(chapter_dir.join(filename), b"").unwrap();
+        }
+        for filename in ["章节元数据.json", "progress.json", "001.json"] {
+            std::fs::write(chapter_dir.join(filename), b"{}").unwrap();
+        }
+
+        let image_paths = get_image_paths(&chapter_dir).unwrap();
+        let filenames: Vec<String> = image_paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(filenames, ["1.bin", "2.jpg", "10.webp"]);
+    }
+
+    #[test]
+    fn dedicated_cover_is_not_stamped_and_pages_start_at_one() {
+        let dir = create_test_dir();
+        let cover_path = dir.join("cover.jpg");
+        let cover_data = create_jpg(&cover_path, [200, 0, 0]);
+        let page_paths = [dir.join("001.jpg"), dir.join("002.jpg")];
+        let page_data = [
+            create_jpg(&page_paths[0], [0, 200, 0]),
+            create_jpg(&page_paths[1], [0, 0, 200]),
+        ];
+        let mut image_paths = page_paths.to_vec();
+        image_paths.insert(0, cover_path.clone());
+
+        let mut zip_data = Cursor::new(Vec::new());
+        let filenames = write_chapter_zip(
+            &mut zip_data,
+            None,
+            image_paths,
+            Some(&cover_path),
+            true,
+            None,
+        )
+        .unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let zip_data = zip_data.into_inner();
+        assert_eq!(filenames, ["000_cover.jpg", "001.jpg", "002.jpg"]);
+        assert_eq!(read_zip_entry(&zip_data, "000_cover.jpg"), cover_data);
+        for (i, data) in page_data.into_iter().enumerate() {
+            let expected = stamp_page_number(data, i + 1).unwrap();
+            assert_eq!(read_zip_entry(&zip_data, &filenames[i + 1]), expected);
+        }
+    }
+}
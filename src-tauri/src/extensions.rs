@@ -5,7 +5,8 @@ use tauri::{AppHandle, Manager, State};
 
 use crate::{
     account_pool::AccountPool, config::Config, copy_client::CopyClient,
-    download_manager::DownloadManager, types::AsyncRwLock,
+    download_manager::DownloadManager, errors::NetworkTimeoutError, library_cache::LibraryCache,
+    types::AsyncRwLock,
 };
 
 pub trait AnyhowErrorToStringChain {
@@ -32,7 +33,7 @@ impl AnyhowErrorToStringChain for anyhow::Error {
 pub trait SendWithTimeoutMsg {
     /// 发送请求并处理超时错误
     ///
-    /// - 如果遇到超时错误，返回带有用户友好信息的错误
+    /// - 如果遇到超时错误，返回以`NetworkTimeoutError`为context的错误
     /// - 否则返回原始错误
     async fn send_with_timeout_msg(self) -> anyhow::Result<Response>;
 }
@@ -41,7 +42,7 @@ impl SendWithTimeoutMsg for RequestBuilder {
     async fn send_with_timeout_msg(self) -> anyhow::Result<Response> {
         self.send().await.map_err(|e| {
             if e.is_timeout() || e.is_middleware() {
-                anyhow::Error::from(e).context("网络连接超时，请使用代理或换条线路重试")
+                anyhow::Error::from(e).context(NetworkTimeoutError)
             } else {
                 anyhow::Error::from(e)
             }
@@ -52,6 +53,7 @@ impl SendWithTimeoutMsg for RequestBuilder {
 pub trait WalkDirEntryExt {
     fn is_comic_metadata(&self) -> bool;
     fn is_chapter_metadata(&self) -> bool;
+    fn is_temp_download_dir(&self) -> bool;
 }
 impl WalkDirEntryExt for walkdir::DirEntry {
     fn is_comic_metadata(&self) -> bool {
@@ -75,11 +77,23 @@ impl WalkDirEntryExt for walkdir::DirEntry {
 
         true
     }
+
+    /// 判断是否为下载章节时的临时目录(`.下载中-`开头)
+    fn is_temp_download_dir(&self) -> bool {
+        if !self.file_type().is_dir() {
+            return false;
+        }
+        self.file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with(".下载中-"))
+    }
 }
 
 pub trait PathIsImg {
     /// 判断路径是否为图片文件
     fn is_img(&self) -> bool;
+    /// 判断路径是否为无法识别格式时保存的原始图片数据(`.bin`)
+    fn is_unrecognized_img(&self) -> bool;
 }
 
 impl PathIsImg for std::path::Path {
@@ -87,7 +101,13 @@ impl PathIsImg for std::path::Path {
         self.extension()
             .and_then(|ext| ext.to_str())
             .map(str::to_lowercase)
-            .is_some_and(|ext| matches!(ext.as_str(), "jpg" | "webp"))
+            .is_some_and(|ext| matches!(ext.as_str(), "jpg" | "webp" | "jxl" | "gif"))
+    }
+
+    fn is_unrecognized_img(&self) -> bool {
+        self.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("bin"))
     }
 }
 
@@ -96,6 +116,7 @@ pub trait AppHandleExt {
     fn get_copy_client(&self) -> State<CopyClient>;
     fn get_download_manager(&self) -> State<DownloadManager>;
     fn get_account_pool(&self) -> State<AsyncRwLock<AccountPool>>;
+    fn get_library_cache(&self) -> State<LibraryCache>;
 }
 
 impl AppHandleExt for AppHandle {
@@ -111,4 +132,7 @@ impl AppHandleExt for AppHandle {
     fn get_account_pool(&self) -> State<AsyncRwLock<AccountPool>> {
         self.state::<AsyncRwLock<AccountPool>>()
     }
+    fn get_library_cache(&self) -> State<LibraryCache> {
+        self.state::<LibraryCache>()
+    }
 }
@@ -7,8 +7,11 @@ mod errors;
 mod events;
 mod export;
 mod extensions;
+mod library_cache;
 mod logger;
 mod responses;
+#[cfg(test)]
+mod test_utils;
 mod types;
 mod utils;
 
@@ -16,16 +19,25 @@ use account_pool::AccountPool;
 use anyhow::Context;
 use copy_client::CopyClient;
 use download_manager::DownloadManager;
-use events::{ExportCbzEvent, ExportPdfEvent, UpdateDownloadedComicsEvent};
+use events::{
+    ExportCbzEvent, ExportPdfEvent, ExportZipEvent, TranscodeEvent, UpdateDownloadedComicsEvent,
+};
+use library_cache::LibraryCache;
 use parking_lot::RwLock;
 use tauri::{Manager, Wry};
 use types::AsyncRwLock;
 
+// 只为了让集成测试能调用，不是对外的API
+#[doc(hidden)]
+pub use export::write_chapter_zip;
+
 use crate::commands::*;
 use crate::config::Config;
 use crate::events::{
-    DownloadControlRiskEvent, DownloadSleepingEvent, DownloadSpeedEvent, DownloadTaskEvent,
-    LogEvent,
+    ComicDownloadCompleteEvent, DownloadControlRiskEvent, DownloadInsufficientSpaceEvent,
+    DownloadSleepingEvent, DownloadSpeedEvent, DownloadTaskEvent, FavoriteBatchEvent,
+    GetComicProgressEvent, ImgRateLimitedEvent, LibraryScanEvent, LogEvent,
+    RiskControlCooldownEvent,
 };
 
 fn generate_context() -> tauri::Context<Wry> {
@@ -42,33 +54,83 @@ pub fn run() {
             register,
             login,
             get_user_profile,
+            check_connection,
             search,
             get_comic,
+            get_comic_groups,
             get_group_chapters,
             get_chapter,
+            estimate_download_size,
             get_favorite,
+            get_all_favorites,
             create_download_task,
+            download_newest_chapters,
+            download_latest_chapters_in_group,
             pause_download_task,
             resume_download_task,
             cancel_download_task,
+            clear_finished_download_tasks,
+            get_overall_download_progress,
+            set_download_paused_global,
+            get_risk_control_count,
+            reset_risk_control_count,
+            transcode_downloaded_comic,
+            cancel_transcode,
+            get_group_download_counts,
             save_metadata,
+            set_chapter_order_override,
+            set_preferred_export_archive,
+            migrate_old_version_chapters,
+            rename_comic_download_dir,
+            repair_comic_metadata,
+            delete_chapter_download,
+            delete_comic_chapter_download,
+            delete_comic_download,
+            create_comic_download_dirs,
+            redownload_incomplete_chapters,
+            find_chapter_dirs_without_metadata,
+            regenerate_chapter_metadata,
+            get_orphaned_chapters,
             get_downloaded_comics,
+            search_downloaded_comics,
+            list_downloaded_comics,
             export_cbz,
             export_pdf,
+            export_comics,
+            export_comic,
+            export_comics_by_preference,
+            reexport_comic,
+            export_combined_pdf,
+            export_comic_catalog,
             update_downloaded_comics,
+            download_all_favorites,
             get_logs_dir_size,
-            show_path_in_file_manager,
+            get_log_tail,
+            get_diagnostics,
+            reveal_path,
+            reveal_comic_dir,
+            reveal_chapter_dir,
             get_synced_comic,
             get_synced_comic_in_favorite,
             get_synced_comic_in_search,
+            get_downloaded_status,
         ])
         .events(tauri_specta::collect_events![
             DownloadTaskEvent,
+            ComicDownloadCompleteEvent,
             DownloadControlRiskEvent,
+            RiskControlCooldownEvent,
+            ImgRateLimitedEvent,
+            DownloadInsufficientSpaceEvent,
             DownloadSpeedEvent,
             DownloadSleepingEvent,
+            GetComicProgressEvent,
+            LibraryScanEvent,
+            FavoriteBatchEvent,
             ExportCbzEvent,
+            ExportZipEvent,
             ExportPdfEvent,
+            TranscodeEvent,
             UpdateDownloadedComicsEvent,
             LogEvent,
         ]);
@@ -100,8 +162,8 @@ pub fn run() {
                 .context(format!("failed to create app data dir: {app_data_dir:?}"))?;
             println!("app data dir: {app_data_dir:?}");
 
-            let config = RwLock::new(Config::new(app.handle())?);
-            app.manage(config);
+            let (config, config_reset_reasons) = Config::new(app.handle())?;
+            app.manage(RwLock::new(config));
 
             let copy_client = CopyClient::new(app.handle().clone());
             app.manage(copy_client);
@@ -109,10 +171,16 @@ pub fn run() {
             let download_manager = DownloadManager::new(app.handle());
             app.manage(download_manager);
 
+            app.manage(LibraryCache::default());
+
             let account_pool = AsyncRwLock::new(AccountPool::new(app.handle())?);
             app.manage(account_pool);
 
             logger::init(app.handle())?;
+            // 日志初始化后才能记录加载配置时重置的配置项
+            for reset_reason in config_reset_reasons {
+                tracing::warn!(message = reset_reason, "配置项不合法，已重置为默认值");
+            }
 
             Ok(())
         })
@@ -0,0 +1,82 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+use parking_lot::RwLock;
+use tauri::AppHandle;
+
+use crate::{extensions::AppHandleExt, utils};
+
+/// 缓存`path_word`到漫画下载目录的映射，避免每次搜索都重新扫描整个下载目录
+///
+/// 下载目录或其修改时间变化时会重新扫描，下载完成、删除或移动漫画目录后需要调用`invalidate`
+#[derive(Default)]
+pub struct LibraryCache {
+    cached: RwLock<Option<CachedLibrary>>,
+    /// 每次`invalidate`都会加一，用于丢弃在失效前就开始扫描的结果
+    generation: AtomicU64,
+}
+
+struct CachedLibrary {
+    download_dir: PathBuf,
+    download_dir_mtime: SystemTime,
+    built_at: SystemTime,
+    path_word_to_dir_map: HashMap<String, Vec<PathBuf>>,
+}
+
+impl LibraryCache {
+    pub fn get_path_word_to_dir_map(
+        &self,
+        app: &AppHandle,
+    ) -> anyhow::Result<HashMap<String, Vec<PathBuf>>> {
+        let download_dir = app.get_config().read().download_dir.clone();
+        let download_dir_mtime = std::fs::metadata(&download_dir)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+
+        if let Some(download_dir_mtime) = download_dir_mtime {
+            let cached = self.cached.read();
+            if let Some(cached) = cached.as_ref().filter(|cached| {
+                cached.download_dir == download_dir
+                    && cached.download_dir_mtime == download_dir_mtime
+            }) {
+                tracing::debug!(built_at = ?cached.built_at, "使用缓存的漫画目录映射");
+                // 删除漫画子目录中的内容不会改变下载目录的修改时间，过滤掉已经不存在的目录，
+                // 保证缓存不会把已删除的漫画当成已下载
+                let mut path_word_to_dir_map = cached.path_word_to_dir_map.clone();
+                path_word_to_dir_map.retain(|_path_word, dirs| {
+                    dirs.retain(|dir| dir.join("元数据.json").exists());
+                    !dirs.is_empty()
+                });
+                return Ok(path_word_to_dir_map);
+            }
+        }
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        let path_word_to_dir_map = utils::scan_path_word_to_dir_map(app)?;
+
+        if let Some(download_dir_mtime) = download_dir_mtime {
+            let mut cached = self.cached.write();
+            // 扫描期间缓存被标记为失效，扫描结果可能已经过时，不写入缓存
+            if self.generation.load(Ordering::SeqCst) == generation {
+                *cached = Some(CachedLibrary {
+                    download_dir,
+                    download_dir_mtime,
+                    built_at: SystemTime::now(),
+                    path_word_to_dir_map: path_word_to_dir_map.clone(),
+                });
+            }
+        }
+
+        Ok(path_word_to_dir_map)
+    }
+
+    pub fn invalidate(&self) {
+        let mut cached = self.cached.write();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *cached = None;
+    }
+}
@@ -1,4 +1,10 @@
-use std::{io::Write, sync::OnceLock};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 
 use anyhow::Context;
 use notify::{RecommendedWatcher, Watcher};
@@ -48,6 +54,97 @@ impl Write for LogEventWriter {
     }
 }
 
+/// 按大小滚动的日志文件writer
+///
+/// 当前文件超过`max_size`字节后新建一个文件继续写，并删除最旧的文件，只保留最近的`max_files`个(为0时不限制)
+///
+/// 配置只在创建writer时读取，修改后下次启动才生效，已经写入的日志文件不会被截断
+struct SizeRollingWriter {
+    logs_dir: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl SizeRollingWriter {
+    fn new(logs_dir: PathBuf, max_size: u64, max_files: usize) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&logs_dir)
+            .context(format!("创建日志目录`{}`失败", logs_dir.display()))?;
+        // 最新的日志文件还没写满就接着写，否则新建一个
+        let latest_log_file = list_log_files(&logs_dir)?
+            .pop()
+            .and_then(|path| Some((path.metadata().ok()?.len(), path)))
+            .filter(|(size, _)| *size < max_size);
+        let (size, path) = latest_log_file.unwrap_or_else(|| (0, new_log_file_path(&logs_dir)));
+        let file = open_log_file(&path)?;
+
+        let writer = Self {
+            logs_dir,
+            max_size,
+            max_files,
+            file,
+            size,
+        };
+        writer.remove_old_files();
+        Ok(writer)
+    }
+
+    fn roll(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+        let path = new_log_file_path(&self.logs_dir);
+        self.file = open_log_file(&path).map_err(std::io::Error::other)?;
+        self.size = 0;
+        self.remove_old_files();
+        Ok(())
+    }
+
+    fn remove_old_files(&self) {
+        if self.max_files == 0 {
+            return;
+        }
+        // 这里是日志的writer，出错时不能再记录日志，否则可能无限递归，所以直接忽略错误
+        let Ok(log_files) = list_log_files(&self.logs_dir) else {
+            return;
+        };
+        let remove_count = log_files.len().saturating_sub(self.max_files);
+        for log_file in log_files.into_iter().take(remove_count) {
+            let _ = std::fs::remove_file(log_file);
+        }
+    }
+}
+
+impl Write for SizeRollingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.roll()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn new_log_file_path(logs_dir: &Path) -> PathBuf {
+    let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
+    logs_dir.join(format!("{LOG_FILE_PREFIX}.{timestamp}.{LOG_FILE_SUFFIX}"))
+}
+
+fn open_log_file(path: &Path) -> anyhow::Result<File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context(format!("打开日志文件`{}`失败", path.display()))
+}
+
+const LOG_FILE_PREFIX: &str = "picacomic-downloader";
+const LOG_FILE_SUFFIX: &str = "log";
+
 static RELOAD_FN: OnceLock<Box<dyn Fn() -> anyhow::Result<()> + Send + Sync>> = OnceLock::new();
 static GUARD: OnceLock<parking_lot::Mutex<Option<WorkerGuard>>> = OnceLock::new();
 
@@ -130,14 +227,31 @@ where
             .with_line_number(true);
         return Ok((Box::new(sink_layer), None));
     }
+    let (log_max_size_mb, log_max_files) = {
+        let config = app.get_config();
+        let config = config.read();
+        (config.log_max_size_mb, config.log_max_files as usize)
+    };
     let logs_dir = logs_dir(app).context("获取日志目录失败")?;
-    let file_appender = RollingFileAppender::builder()
-        .filename_prefix("picacomic-downloader")
-        .filename_suffix("log")
-        .rotation(Rotation::DAILY)
-        .build(&logs_dir)
-        .context("创建RollingFileAppender失败")?;
-    let (non_blocking_appender, guard) = tracing_appender::non_blocking(file_appender);
+    // `log_max_size_mb`为0时不限制单个文件的大小，按天滚动
+    let (non_blocking_appender, guard) = if log_max_size_mb == 0 {
+        let mut builder = RollingFileAppender::builder()
+            .filename_prefix(LOG_FILE_PREFIX)
+            .filename_suffix(LOG_FILE_SUFFIX)
+            .rotation(Rotation::DAILY);
+        if log_max_files > 0 {
+            builder = builder.max_log_files(log_max_files);
+        }
+        let file_appender = builder
+            .build(&logs_dir)
+            .context("创建RollingFileAppender失败")?;
+        tracing_appender::non_blocking(file_appender)
+    } else {
+        let max_size = log_max_size_mb * 1024 * 1024;
+        let file_appender = SizeRollingWriter::new(logs_dir, max_size, log_max_files)
+            .context("创建SizeRollingWriter失败")?;
+        tracing_appender::non_blocking(file_appender)
+    };
     let file_layer = layer()
         .with_writer(non_blocking_appender)
         .with_timer(LocalTime::rfc_3339())
@@ -219,6 +333,64 @@ async fn file_log_watcher(app: AppHandle) {
     }
 }
 
+/// 获取当前正在写入的日志文件(日志目录中最近修改的日志文件)
+///
+/// 日志目录不存在或其中没有日志文件时(例如从未启用过文件日志)返回`None`
+pub fn current_log_file(app: &AppHandle) -> anyhow::Result<Option<PathBuf>> {
+    let logs_dir = logs_dir(app).context("获取日志目录失败")?;
+    if !logs_dir.exists() {
+        return Ok(None);
+    }
+
+    let current_log_file = list_log_files(&logs_dir)?.pop();
+    Ok(current_log_file)
+}
+
+/// 获取`logs_dir`中的所有日志文件，按修改时间从旧到新排序
+fn list_log_files(logs_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut log_files: Vec<(PathBuf, std::time::SystemTime)> = std::fs::read_dir(logs_dir)
+        .context(format!("读取日志目录`{}`失败", logs_dir.display()))?
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let filename = entry.file_name();
+            let filename = filename.to_string_lossy();
+            filename.starts_with(LOG_FILE_PREFIX)
+                && filename.ends_with(&format!(".{LOG_FILE_SUFFIX}"))
+        })
+        .filter_map(|entry| {
+            let modified_time = entry.metadata().ok()?.modified().ok()?;
+            Some((entry.path(), modified_time))
+        })
+        .collect();
+    log_files.sort_by(|(a_path, a_time), (b_path, b_time)| {
+        a_time.cmp(b_time).then_with(|| a_path.cmp(b_path))
+    });
+    Ok(log_files.into_iter().map(|(path, _)| path).collect())
+}
+
+/// 读取当前日志文件的最后`line_count`行，没有日志文件时返回空列表
+pub fn tail_log_file(app: &AppHandle, line_count: usize) -> anyhow::Result<Vec<String>> {
+    let Some(log_file) = current_log_file(app)? else {
+        return Ok(Vec::new());
+    };
+
+    let file = std::fs::File::open(&log_file)
+        .context(format!("打开日志文件`{}`失败", log_file.display()))?;
+    // 逐行读取，只保留最后`line_count`行，避免日志文件很大时占用过多内存
+    let mut lines = VecDeque::with_capacity(line_count);
+    for line in BufReader::new(file).split(b'\n') {
+        let line = line.context(format!("读取日志文件`{}`失败", log_file.display()))?;
+        if lines.len() == line_count {
+            lines.pop_front();
+        }
+        if line_count > 0 {
+            lines.push_back(String::from_utf8_lossy(&line).trim_end().to_string());
+        }
+    }
+
+    Ok(lines.into())
+}
+
 pub fn logs_dir(app: &AppHandle) -> anyhow::Result<std::path::PathBuf> {
     let app_data_dir = app
         .path()
@@ -25,10 +25,13 @@ impl DerefMut for GetChaptersRespData {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 #[serde(rename_all = "camelCase")]
 pub struct ChapterInGetChaptersRespData {
+    #[serde(deserialize_with = "super::deserialize_i64_lenient")]
     pub index: i64,
     pub uuid: String,
     pub count: i64,
+    #[serde(deserialize_with = "super::deserialize_i64_lenient")]
     pub ordered: i64,
+    #[serde(deserialize_with = "super::deserialize_i64_lenient")]
     pub size: i64,
     pub name: String,
     #[serde(rename = "comic_id")]
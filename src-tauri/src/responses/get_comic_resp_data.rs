@@ -19,6 +19,7 @@ pub struct GetComicRespData {
     #[serde(rename = "is_vip")]
     pub is_vip: bool,
     pub comic: ComicInGetComicRespData,
+    #[serde(deserialize_with = "super::deserialize_i64_lenient")]
     pub popular: i64,
     pub groups: HashMap<String, GroupRespData>,
 }
@@ -59,6 +60,7 @@ pub struct ComicInGetComicRespData {
     pub cover: String,
     #[serde(rename = "last_chapter")]
     pub last_chapter: LastChapterRespData,
+    #[serde(deserialize_with = "super::deserialize_i64_lenient")]
     pub popular: i64,
 }
 
@@ -43,6 +43,7 @@ pub struct ComicInGetFavoriteRespData {
     pub author: Vec<AuthorRespData>,
     pub cover: String,
     pub status: i64,
+    #[serde(deserialize_with = "super::deserialize_i64_lenient")]
     pub popular: i64,
     #[serde(rename = "datetime_updated")]
     pub datetime_updated: String,
@@ -16,9 +16,28 @@ pub use search_resp_data::*;
 pub use user_profile_resp_data::*;
 pub use get_favorite_resp_data::*;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use specta::Type;
 
+/// 拷贝漫画偶尔会把数字字段返回为字符串，这里同时接受数字和数字字符串
+/// 以免一个字段的格式问题导致整个请求失败
+pub fn deserialize_i64_lenient<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString {
+        Number(i64),
+        String(String),
+    }
+
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(n) => Ok(n),
+        NumberOrString::String(s) => s.trim().parse().map_err(serde::de::Error::custom),
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 #[serde(default, rename_all = "camelCase")]
 pub struct Pagination<T> {
@@ -36,3 +55,37 @@ pub struct AuthorRespData {
     #[serde(rename = "path_word")]
     pub path_word: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Lenient {
+        #[serde(deserialize_with = "deserialize_i64_lenient")]
+        value: i64,
+    }
+
+    fn parse(json: &str) -> serde_json::Result<i64> {
+        serde_json::from_str::<Lenient>(json).map(|lenient| lenient.value)
+    }
+
+    #[test]
+    fn accepts_number() {
+        assert_eq!(parse(r#"{"value": 42}"#).unwrap(), 42);
+        assert_eq!(parse(r#"{"value": -7}"#).unwrap(), -7);
+    }
+
+    #[test]
+    fn accepts_numeric_string() {
+        assert_eq!(parse(r#"{"value": "42"}"#).unwrap(), 42);
+        assert_eq!(parse(r#"{"value": " 42 "}"#).unwrap(), 42);
+    }
+
+    #[test]
+    fn rejects_non_numeric_string() {
+        assert!(parse(r#"{"value": "abc"}"#).is_err());
+        assert!(parse(r#"{"value": ""}"#).is_err());
+        assert!(parse(r#"{"value": "4.2"}"#).is_err());
+    }
+}
@@ -32,5 +32,6 @@ pub struct ComicInSearchRespData {
     pub cover: String,
     pub ban: i64,
     pub author: Vec<AuthorRespData>,
+    #[serde(deserialize_with = "super::deserialize_i64_lenient")]
     pub popular: i64,
 }
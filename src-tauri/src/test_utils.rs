@@ -0,0 +1,10 @@
+//! 单元测试和集成测试共用的辅助函数
+
+use std::path::PathBuf;
+
+/// 在系统临时目录下创建一个只属于当前测试的目录
+pub fn create_test_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("copymanga-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -3,8 +3,9 @@ use std::path::PathBuf;
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
 use specta::Type;
+use tauri::AppHandle;
 
-use crate::types::Comic;
+use crate::{types::Comic, utils};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 #[serde(rename_all = "camelCase")]
@@ -52,12 +53,28 @@ impl ChapterInfo {
         let chapter_json =
             serde_json::to_string_pretty(&chapter_info).context("将ChapterInfo序列化为json失败")?;
 
-        std::fs::write(&metadata_path, chapter_json)
+        // 用原子写入，避免进程中途被杀导致`章节元数据.json`被截断
+        utils::write_atomically(&metadata_path, chapter_json)
             .context(format!("写入文件`{}`失败", metadata_path.display()))?;
 
         Ok(())
     }
 
+    /// 删除已下载的章节目录(包括`章节元数据.json`)，目录不存在时什么都不做
+    ///
+    /// 只能删除`download_dir`之下的目录
+    pub fn delete_download(&self, app: &AppHandle) -> anyhow::Result<()> {
+        let chapter_download_dir = self
+            .chapter_download_dir
+            .as_ref()
+            .context("`chapter_download_dir`字段为`None`")?;
+        if !chapter_download_dir.exists() {
+            return Ok(());
+        }
+
+        utils::remove_dir_in_download_dir(app, chapter_download_dir)
+    }
+
     pub fn get_temp_download_dir(&self) -> anyhow::Result<PathBuf> {
         let chapter_download_dir = self
             .chapter_download_dir
@@ -1,16 +1,20 @@
 use std::{
     collections::{HashMap, HashSet},
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use anyhow::{anyhow, Context};
+use float_ord::FloatOrd;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use tauri::AppHandle;
 use walkdir::WalkDir;
 
 use crate::{
-    extensions::{AppHandleExt, WalkDirEntryExt},
+    config::FilenameSanitizeMode,
+    export::Archive,
+    extensions::{AnyhowErrorToStringChain, AppHandleExt, PathIsImg, WalkDirEntryExt},
     responses::{
         AuthorRespData, ChapterInGetChaptersRespData, GetComicRespData, GroupRespData,
         LabeledValueRespData, LastChapterRespData, ThemeRespData,
@@ -41,6 +45,15 @@ pub struct Comic {
     pub is_downloaded: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub comic_download_dir: Option<PathBuf>,
+    /// 漫画目录中的封面`cover.<ext>`，不保存到元数据中
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cover_path: Option<PathBuf>,
+    /// `chapter_uuid` -> 手动指定的`order`，用于修正API返回的错误顺序
+    #[serde(default)]
+    pub order_overrides: HashMap<String, f64>,
+    /// 这部漫画偏好的导出格式，为`None`时使用`config.default_export_archive`
+    #[serde(default)]
+    pub preferred_export_archive: Option<Archive>,
 }
 impl Comic {
     pub fn from_resp_data(
@@ -68,6 +81,9 @@ impl Comic {
             groups,
             is_downloaded: None,
             comic_download_dir: None,
+            cover_path: None,
+            order_overrides: HashMap::new(),
+            preferred_export_archive: None,
         };
 
         let path_word_to_dir_map =
@@ -83,6 +99,14 @@ impl Comic {
             }
         }
 
+        // 手动指定的order和偏好的导出格式只保存在元数据中，需要从元数据中恢复
+        if let Some(comic_download_dir) = path_word_to_dir_map
+            .get(&comic.comic.path_word)
+            .and_then(|dirs| dirs.first())
+        {
+            comic.restore_fields_from_metadata(comic_download_dir);
+        }
+
         comic
             .update_fields(&path_word_to_dir_map)
             .context(format!("`{}`更新Comic的字段失败", comic.comic.name))?;
@@ -90,6 +114,266 @@ impl Comic {
         Ok(comic)
     }
 
+    /// 漫画目录中的封面`cover.<ext>`，不存在时返回`None`
+    pub fn get_cover_path(comic_download_dir: &Path) -> Option<PathBuf> {
+        std::fs::read_dir(comic_download_dir)
+            .ok()?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .find(|path| is_comic_cover(comic_download_dir, path))
+    }
+
+    /// 从元数据中恢复`order_overrides`和`preferred_export_archive`，元数据只读取一次
+    ///
+    /// 元数据损坏或字段格式错误时只记录警告并使用默认值，不影响获取漫画
+    fn restore_fields_from_metadata(&mut self, comic_download_dir: &Path) {
+        let comic_title = &self.comic.name;
+        let comic_json = match Comic::read_metadata_json(comic_download_dir) {
+            Ok(comic_json) => comic_json,
+            Err(err) => {
+                let string_chain = err.to_string_chain();
+                tracing::warn!(
+                    comic_title,
+                    message = string_chain,
+                    "读取元数据失败，`orderOverrides`和`preferredExportArchive`将使用默认值"
+                );
+                return;
+            }
+        };
+
+        self.order_overrides = match Comic::read_order_overrides(&comic_json) {
+            Ok(order_overrides) => order_overrides,
+            Err(err) => {
+                let string_chain = err.to_string_chain();
+                tracing::warn!(
+                    comic_title,
+                    message = string_chain,
+                    "从元数据读取`orderOverrides`失败，将使用默认值"
+                );
+                HashMap::new()
+            }
+        };
+        self.preferred_export_archive = match Comic::read_preferred_export_archive(&comic_json) {
+            Ok(preferred_export_archive) => preferred_export_archive,
+            Err(err) => {
+                let string_chain = err.to_string_chain();
+                tracing::warn!(
+                    comic_title,
+                    message = string_chain,
+                    "从元数据读取`preferredExportArchive`失败，将使用默认值"
+                );
+                None
+            }
+        };
+    }
+
+    fn read_metadata_json(comic_download_dir: &Path) -> anyhow::Result<serde_json::Value> {
+        let metadata_path = comic_download_dir.join("元数据.json");
+        let metadata_str = std::fs::read_to_string(&metadata_path)
+            .context(format!("读取`{}`失败", metadata_path.display()))?;
+        let comic_json: serde_json::Value =
+            serde_json::from_str(&metadata_str).context(format!(
+                "将`{}`反序列化为serde_json::Value失败",
+                metadata_path.display()
+            ))?;
+
+        Ok(comic_json)
+    }
+
+    fn read_order_overrides(
+        comic_json: &serde_json::Value,
+    ) -> anyhow::Result<HashMap<String, f64>> {
+        let Some(order_overrides_json) = comic_json.get("orderOverrides") else {
+            return Ok(HashMap::new());
+        };
+        let order_overrides = serde_json::from_value(order_overrides_json.clone())
+            .context("`orderOverrides`字段格式错误")?;
+
+        Ok(order_overrides)
+    }
+
+    fn read_preferred_export_archive(
+        comic_json: &serde_json::Value,
+    ) -> anyhow::Result<Option<Archive>> {
+        let Some(archive_json) = comic_json.get("preferredExportArchive") else {
+            return Ok(None);
+        };
+        let preferred_export_archive = serde_json::from_value(archive_json.clone())
+            .context("`preferredExportArchive`字段格式错误")?;
+
+        Ok(preferred_export_archive)
+    }
+
+    /// 删除整个漫画下载目录
+    ///
+    /// 会先取消这部漫画所有未结束的下载任务并等待它们停止，避免删除时还有任务在写入，
+    /// 有任务没能及时停止时返回错误，不会删除任何文件
+    pub async fn delete_download(&self, app: &AppHandle) -> anyhow::Result<()> {
+        const CANCEL_TIMEOUT: Duration = Duration::from_secs(10);
+
+        let comic_title = &self.comic.name;
+        let comic_download_dir = self
+            .comic_download_dir
+            .as_ref()
+            .context("`comic_download_dir`字段为`None`")?;
+
+        app.get_download_manager()
+            .cancel_comic_download_tasks(&self.comic.uuid, CANCEL_TIMEOUT)
+            .await
+            .context(format!("取消`{comic_title}`的下载任务失败"))?;
+
+        if !comic_download_dir.exists() {
+            return Ok(());
+        }
+        utils::remove_dir_in_download_dir(app, comic_download_dir)
+    }
+
+    /// 删除章节ID为`chapter_uuid`的已下载章节，并更新`is_downloaded`字段
+    ///
+    /// 删除后没有已下载的章节时，漫画的`is_downloaded`也会被设为`false`
+    pub fn delete_chapter_download(
+        &mut self,
+        app: &AppHandle,
+        chapter_uuid: &str,
+    ) -> anyhow::Result<()> {
+        let chapter_info = self
+            .comic
+            .groups
+            .values_mut()
+            .flatten()
+            .find(|chapter_info| chapter_info.chapter_uuid == chapter_uuid)
+            .context(format!("找不到章节ID为`{chapter_uuid}`的章节"))?;
+        chapter_info.delete_download(app)?;
+        chapter_info.is_downloaded = Some(false);
+
+        let has_downloaded_chapter = self
+            .comic
+            .groups
+            .values()
+            .flatten()
+            .any(|chapter_info| chapter_info.is_downloaded == Some(true));
+        self.is_downloaded = Some(has_downloaded_chapter);
+
+        Ok(())
+    }
+
+    /// 这部漫画是否被锁定、需要登录或需要VIP才能阅读
+    pub fn requires_login(&self) -> bool {
+        self.is_lock || self.is_login || self.is_vip
+    }
+
+    /// 获取章节的`order`，如果有手动指定的`order`则优先使用
+    pub fn get_chapter_order(&self, chapter_info: &ChapterInfo) -> f64 {
+        self.order_overrides
+            .get(&chapter_info.chapter_uuid)
+            .copied()
+            .unwrap_or(chapter_info.order)
+    }
+
+    /// 获取`order`最大的`n`个章节，`order`相同的章节只保留一个(优先保留默认分组的)
+    ///
+    /// 章节数不足`n`时返回所有章节
+    pub fn get_newest_chapters(&self, n: usize) -> Vec<&ChapterInfo> {
+        let mut chapter_infos: Vec<&ChapterInfo> = self.comic.groups.values().flatten().collect();
+        chapter_infos.sort_by_key(|chapter_info| {
+            (
+                std::cmp::Reverse(FloatOrd(self.get_chapter_order(chapter_info))),
+                chapter_info.group_path_word != "default",
+            )
+        });
+        chapter_infos.dedup_by_key(|chapter_info| FloatOrd(self.get_chapter_order(chapter_info)));
+        chapter_infos.truncate(n);
+        chapter_infos
+    }
+
+    /// 获取每个章节在其分组中按`order`排序后的位置(从1开始)和分组的章节总数
+    ///
+    /// 返回`chapter_uuid` -> `(chapter_index, total_chapters)`
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn get_chapter_indexes(&self) -> HashMap<String, (u32, u32)> {
+        let mut chapter_indexes = HashMap::new();
+        for chapter_infos in self.comic.groups.values() {
+            let mut chapter_infos: Vec<&ChapterInfo> = chapter_infos.iter().collect();
+            chapter_infos
+                .sort_by_key(|chapter_info| FloatOrd(self.get_chapter_order(chapter_info)));
+            let total_chapters = chapter_infos.len() as u32;
+            for (i, chapter_info) in chapter_infos.into_iter().enumerate() {
+                let chapter_index = (i + 1) as u32;
+                chapter_indexes.insert(
+                    chapter_info.chapter_uuid.clone(),
+                    (chapter_index, total_chapters),
+                );
+            }
+        }
+        chapter_indexes
+    }
+
+    /// 统计每个分组的章节数和已下载章节数，按服务器给出的章节数从多到少排序
+    ///
+    /// 依赖`update_chapter_infos_fields`更新后的`is_downloaded`字段
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn get_group_download_counts(&self) -> Vec<GroupDownloadCount> {
+        let group_path_words: HashSet<&String> =
+            self.groups.keys().chain(self.comic.groups.keys()).collect();
+
+        let mut counts: Vec<GroupDownloadCount> = group_path_words
+            .into_iter()
+            .map(|group_path_word| {
+                let group = self.groups.get(group_path_word);
+                let chapter_infos = self
+                    .comic
+                    .groups
+                    .get(group_path_word)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                let group_name = match (group, chapter_infos.first()) {
+                    (Some(group), _) => group.name.clone(),
+                    (None, Some(chapter_info)) => chapter_info.group_name.clone(),
+                    (None, None) => group_path_word.clone(),
+                };
+                let downloaded_count = chapter_infos
+                    .iter()
+                    .filter(|chapter_info| chapter_info.is_downloaded == Some(true))
+                    .count();
+
+                GroupDownloadCount {
+                    group_path_word: group_path_word.clone(),
+                    group_name,
+                    server_count: group.map(|group| group.count),
+                    chapter_count: chapter_infos.len() as u32,
+                    downloaded_count: downloaded_count as u32,
+                }
+            })
+            .collect();
+        counts.sort_by(|a, b| {
+            b.server_count
+                .cmp(&a.server_count)
+                .then_with(|| a.group_name.cmp(&b.group_name))
+        });
+        counts
+    }
+
+    /// 找出已下载、但`fresh_comic`的章节列表中已经不存在的章节(被替换或删除)
+    pub fn get_orphaned_chapters(&self, fresh_comic: &Comic) -> Vec<&ChapterInfo> {
+        let fresh_chapter_uuids: HashSet<&str> = fresh_comic
+            .comic
+            .groups
+            .values()
+            .flatten()
+            .map(|chapter_info| chapter_info.chapter_uuid.as_str())
+            .collect();
+
+        self.comic
+            .groups
+            .values()
+            .flatten()
+            .filter(|chapter_info| chapter_info.is_downloaded == Some(true))
+            .filter(|chapter_info| {
+                !fresh_chapter_uuids.contains(chapter_info.chapter_uuid.as_str())
+            })
+            .collect()
+    }
+
     pub fn from_metadata(metadata_path: &Path) -> anyhow::Result<Comic> {
         let comic_json = std::fs::read_to_string(metadata_path).context(format!(
             "从元数据转为Comic失败，读取元数据文件`{}`失败",
@@ -109,6 +393,7 @@ impl Comic {
             .create_chapter_metadata_for_old_version(&comic_download_dir)
             .context("为旧版本创建章节元数据失败")?;
 
+        comic.cover_path = Comic::get_cover_path(&comic_download_dir);
         comic.comic_download_dir = Some(comic_download_dir.clone());
         comic.is_downloaded = Some(true);
 
@@ -127,6 +412,7 @@ impl Comic {
     ) -> anyhow::Result<()> {
         if let Some(comic_download_dirs) = path_word_to_dir_map.get(&self.comic.path_word) {
             if let Some(first_dir) = comic_download_dirs.first() {
+                self.cover_path = Comic::get_cover_path(first_dir);
                 self.comic_download_dir = Some(first_dir.clone());
                 self.is_downloaded = Some(true);
             }
@@ -201,15 +487,29 @@ impl Comic {
     }
 
     pub fn save_metadata(&self) -> anyhow::Result<()> {
+        let comic_json = self.to_metadata_json()?;
+        self.write_metadata_json(&comic_json)
+    }
+
+    /// 序列化为`元数据.json`的内容，`is_downloaded`和`cover_path`字段不会被保存
+    pub fn to_metadata_json(&self) -> anyhow::Result<String> {
         let mut comic = self.clone();
         // 将所有的is_downloaded字段设置为None，这样能使is_downloaded字段在序列化时被忽略
         comic.is_downloaded = None;
+        // 封面路径每次都从漫画目录中重新查找，不保存到元数据中
+        comic.cover_path = None;
         for chapter_infos in comic.comic.groups.values_mut() {
             for chapter_info in chapter_infos.iter_mut() {
                 chapter_info.is_downloaded = None;
             }
         }
 
+        let comic_json = serde_json::to_string_pretty(&comic).context("将Comic序列化为json失败")?;
+        Ok(comic_json)
+    }
+
+    /// 将`to_metadata_json`的结果写入漫画目录中的`元数据.json`
+    pub fn write_metadata_json(&self, comic_json: &str) -> anyhow::Result<()> {
         let comic_download_dir = self
             .comic_download_dir
             .as_ref()
@@ -219,20 +519,22 @@ impl Comic {
         std::fs::create_dir_all(comic_download_dir)
             .context(format!("创建目录`{}`失败", comic_download_dir.display()))?;
 
-        let comic_json = serde_json::to_string_pretty(&comic).context("将Comic序列化为json失败")?;
-
-        std::fs::write(&metadata_path, comic_json)
+        // 同一漫画的多个章节会并发保存元数据，用原子写入避免`元数据.json`内容交错或被截断
+        utils::write_atomically(&metadata_path, comic_json)
             .context(format!("写入文件`{}`失败", metadata_path.display()))?;
 
         Ok(())
     }
 
-    pub fn get_comic_export_dir(&self, app: &AppHandle) -> anyhow::Result<PathBuf> {
-        let (download_dir, export_dir) = {
-            let config = app.get_config();
-            let config = config.read();
-            (config.download_dir.clone(), config.export_dir.clone())
-        };
+    /// 以`export_dir`为导出根目录，获取漫画的导出目录
+    ///
+    /// 导出目录相对于`export_dir`的结构与下载目录相对于`download_dir`的结构一致
+    pub fn get_comic_export_dir(
+        &self,
+        app: &AppHandle,
+        export_dir: &Path,
+    ) -> anyhow::Result<PathBuf> {
+        let download_dir = app.get_config().read().download_dir.clone();
 
         let Some(comic_download_dir) = self.comic_download_dir.clone() else {
             return Err(anyhow!("`comic_download_dir`字段为`None`"));
@@ -254,6 +556,25 @@ impl Comic {
         &self,
         comic_download_dir: &Path,
     ) -> anyhow::Result<()> {
+        for (chapter_info, old_chapter_dir) in self.find_old_version_chapters(comic_download_dir)? {
+            let old_chapter_metadata_exists = old_chapter_dir.join("章节元数据.json").exists();
+            if !old_chapter_metadata_exists {
+                // 如果旧版本的章节目录存在，但没有元数据文件，就创建一个
+                let mut info = chapter_info.clone();
+                info.chapter_download_dir = Some(old_chapter_dir);
+                info.is_downloaded = Some(true);
+                info.save_metadata()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 找出`comic_download_dir`中旧版本(`{group_title}/{order} {chapter_title}`)结构的章节目录
+    fn find_old_version_chapters(
+        &self,
+        comic_download_dir: &Path,
+    ) -> anyhow::Result<Vec<(&ChapterInfo, PathBuf)>> {
         let mut chapter_dirs = HashSet::new();
         for group_entry in std::fs::read_dir(comic_download_dir)?.filter_map(Result::ok) {
             let Ok(file_type) = group_entry.file_type() else {
@@ -274,9 +595,12 @@ impl Comic {
             }
         }
 
+        let mut old_version_chapters = Vec::new();
         for chapter_info in self.comic.groups.values().flatten() {
-            let group_title = utils::filename_filter(&chapter_info.group_name);
-            let chapter_title = utils::filename_filter(&chapter_info.chapter_title);
+            // 旧版本总是用全角字符替换非法字符，与当前的`filename_sanitize_mode`无关
+            let sanitize_mode = FilenameSanitizeMode::FullWidthSubstitute;
+            let group_title = utils::filename_filter(&chapter_info.group_name, sanitize_mode);
+            let chapter_title = utils::filename_filter(&chapter_info.chapter_title, sanitize_mode);
             let order = chapter_info.order;
             let prefixed_chapter_title = format!("{order} {chapter_title}");
 
@@ -284,22 +608,506 @@ impl Comic {
                 .join(&group_title)
                 .join(&prefixed_chapter_title);
 
-            let old_chapter_dir_exists = chapter_dirs.contains(&old_chapter_dir);
-            let old_chapter_metadata_exists = old_chapter_dir.join("章节元数据.json").exists();
+            if chapter_dirs.contains(&old_chapter_dir) {
+                old_version_chapters.push((chapter_info, old_chapter_dir));
+            }
+        }
 
-            if old_chapter_dir_exists && !old_chapter_metadata_exists {
-                // 如果旧版本的章节目录存在，但没有元数据文件，就创建一个
+        Ok(old_version_chapters)
+    }
+
+    /// 主动迁移旧版本结构的章节目录
+    ///
+    /// - 为缺少元数据的旧版本章节目录创建元数据
+    /// - 如果`rename`为`true`，则按照当前的`chapter_dir_fmt`重命名这些目录
+    pub fn migrate_old_version_chapters(
+        &self,
+        app: &AppHandle,
+        rename: bool,
+    ) -> anyhow::Result<Vec<MigratedChapter>> {
+        let comic_download_dir = self
+            .comic_download_dir
+            .as_ref()
+            .context("`comic_download_dir`字段为`None`")?;
+
+        // 计算每个章节在当前fmt下相对于漫画目录的路径
+        let mut comic_by_fmt = self.clone();
+        if rename {
+            comic_by_fmt
+                .update_download_dir_fields_by_fmt(app)
+                .context("根据fmt计算章节下载目录失败")?;
+        }
+        let chapter_infos_by_fmt: HashMap<&str, &ChapterInfo> = comic_by_fmt
+            .comic
+            .groups
+            .values()
+            .flatten()
+            .map(|chapter_info| (chapter_info.chapter_uuid.as_str(), chapter_info))
+            .collect();
+
+        let mut migrated_chapters = Vec::new();
+        // 被移走章节的原父目录，迁移结束后如果变空了就删除
+        let mut old_parent_dirs = HashSet::new();
+        for (chapter_info, old_chapter_dir) in self.find_old_version_chapters(comic_download_dir)? {
+            let metadata_created = !old_chapter_dir.join("章节元数据.json").exists();
+            if metadata_created {
                 let mut info = chapter_info.clone();
-                info.chapter_download_dir = Some(old_chapter_dir);
+                info.chapter_download_dir = Some(old_chapter_dir.clone());
                 info.is_downloaded = Some(true);
-                info.save_metadata()?;
+                info.save_metadata().context(format!(
+                    "为`{}`创建章节元数据失败",
+                    old_chapter_dir.display()
+                ))?;
+            }
+
+            let mut new_chapter_dir = None;
+            if rename {
+                let chapter_info_by_fmt = chapter_infos_by_fmt
+                    .get(chapter_info.chapter_uuid.as_str())
+                    .context(format!("找不到章节`{}`", chapter_info.chapter_uuid))?;
+                let relative_dir = chapter_info_by_fmt.get_chapter_relative_dir(&comic_by_fmt)?;
+                // 只重命名章节目录，漫画目录保持不变
+                let chapter_dir = comic_download_dir.join(relative_dir);
+                if chapter_dir.exists() {
+                    let err_title = "迁移旧版本章节目录失败，已跳过";
+                    let message = format!("目标目录`{}`已存在", chapter_dir.display());
+                    tracing::error!(err_title, message);
+                } else {
+                    if let Some(parent) = chapter_dir.parent() {
+                        std::fs::create_dir_all(parent)
+                            .context(format!("创建目录`{}`失败", parent.display()))?;
+                    }
+                    std::fs::rename(&old_chapter_dir, &chapter_dir).context(format!(
+                        "将`{}`重命名为`{}`失败",
+                        old_chapter_dir.display(),
+                        chapter_dir.display()
+                    ))?;
+                    if let Some(old_parent_dir) = old_chapter_dir.parent() {
+                        old_parent_dirs.insert(old_parent_dir.to_path_buf());
+                    }
+                    new_chapter_dir = Some(chapter_dir);
+                }
+            }
+
+            migrated_chapters.push(MigratedChapter {
+                chapter_uuid: chapter_info.chapter_uuid.clone(),
+                chapter_title: chapter_info.chapter_title.clone(),
+                group_name: chapter_info.group_name.clone(),
+                old_chapter_dir,
+                new_chapter_dir,
+                metadata_created,
+            });
+        }
+
+        // 重命名后可能留下空的旧分组目录，只删除因为章节被移走而变空的目录，用户原本的空目录保持不变
+        for old_parent_dir in &old_parent_dirs {
+            remove_emptied_dirs(old_parent_dir, comic_download_dir);
+        }
+
+        Ok(migrated_chapters)
+    }
+
+    /// 找出漫画目录中有图片、但没有有效`章节元数据.json`的目录
+    ///
+    /// `update_chapter_infos_fields`依赖章节元数据，这些目录中的章节不会被识别为已下载，
+    /// 可以根据返回的`chapter_uuid`用`regenerate_chapter_metadata`重新生成元数据
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn find_chapter_dirs_without_metadata(
+        &self,
+        app: &AppHandle,
+    ) -> anyhow::Result<Vec<ChapterDirWithoutMetadata>> {
+        let comic_download_dir = self
+            .comic_download_dir
+            .as_ref()
+            .context("`comic_download_dir`字段为`None`")?;
+
+        // 按照当前的fmt推测每个目录对应的章节，漫画目录可能被改过名，所以只比较相对路径
+        let mut comic_by_fmt = self.clone();
+        let mut dir_to_chapter_uuid = HashMap::new();
+        if comic_by_fmt.update_download_dir_fields_by_fmt(app).is_ok() {
+            for chapter_info in comic_by_fmt.comic.groups.values().flatten() {
+                if let Ok(relative_dir) = chapter_info.get_chapter_relative_dir(&comic_by_fmt) {
+                    let chapter_dir = comic_download_dir.join(relative_dir);
+                    dir_to_chapter_uuid.insert(chapter_dir, chapter_info.chapter_uuid.clone());
+                }
+            }
+        }
+
+        let mut chapter_dirs = Vec::new();
+        for (chapter_dir, img_count) in find_dirs_with_imgs(comic_download_dir)? {
+            let metadata_path = chapter_dir.join("章节元数据.json");
+            let metadata_exists = metadata_path.exists();
+            let metadata_is_valid = std::fs::read_to_string(&metadata_path)
+                .ok()
+                .and_then(|metadata_str| {
+                    serde_json::from_str::<serde_json::Value>(&metadata_str).ok()
+                })
+                .is_some_and(|chapter_json| {
+                    chapter_json
+                        .get("chapterUuid")
+                        .is_some_and(serde_json::Value::is_string)
+                        && chapter_json
+                            .get("groupPathWord")
+                            .is_some_and(serde_json::Value::is_string)
+                });
+            if metadata_is_valid {
+                continue;
+            }
+
+            let chapter_uuid = dir_to_chapter_uuid.get(&chapter_dir).cloned();
+            chapter_dirs.push(ChapterDirWithoutMetadata {
+                chapter_dir,
+                img_count: img_count as u32,
+                metadata_exists,
+                chapter_uuid,
+            });
+        }
+
+        Ok(chapter_dirs)
+    }
+
+    /// 按照当前的fmt预先创建漫画目录和所有章节目录，已存在的目录不受影响
+    ///
+    /// 某个章节目录创建失败不影响其他章节，返回所有创建失败的章节
+    pub fn create_download_dirs(
+        &self,
+        app: &AppHandle,
+    ) -> anyhow::Result<Vec<ChapterDirCreateFailure>> {
+        let mut comic = self.clone();
+        comic
+            .update_download_dir_fields_by_fmt(app)
+            .context("根据fmt计算下载目录失败")?;
+        let comic_download_dir = comic
+            .comic_download_dir
+            .as_ref()
+            .context("`comic_download_dir`字段为`None`")?;
+        std::fs::create_dir_all(comic_download_dir)
+            .context(format!("创建目录`{}`失败", comic_download_dir.display()))?;
+
+        let mut failures = Vec::new();
+        for chapter_info in comic.comic.groups.values().flatten() {
+            let Some(chapter_download_dir) = &chapter_info.chapter_download_dir else {
+                continue;
+            };
+            if let Err(err) = std::fs::create_dir_all(chapter_download_dir)
+                .context(format!("创建目录`{}`失败", chapter_download_dir.display()))
+            {
+                failures.push(ChapterDirCreateFailure {
+                    chapter_uuid: chapter_info.chapter_uuid.clone(),
+                    chapter_title: chapter_info.chapter_title.clone(),
+                    chapter_download_dir: chapter_download_dir.clone(),
+                    err_message: err.to_string_chain(),
+                });
+            }
+        }
+
+        Ok(failures)
+    }
+
+    /// 找出图片不完整的已下载章节
+    ///
+    /// 章节目录中的图片数少于`chapter_size`，或者有大小为0的图片，都视为不完整
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn find_incomplete_chapters(&self) -> anyhow::Result<Vec<IncompleteChapter>> {
+        let mut incomplete_chapters = Vec::new();
+        for chapter_info in self.comic.groups.values().flatten() {
+            if chapter_info.is_downloaded != Some(true) {
+                continue;
+            }
+            let Some(chapter_download_dir) = &chapter_info.chapter_download_dir else {
+                continue;
+            };
+
+            let img_sizes: Vec<u64> = std::fs::read_dir(chapter_download_dir)
+                .context(format!("读取目录`{}`失败", chapter_download_dir.display()))?
+                .filter_map(Result::ok)
+                .filter(|entry| {
+                    let path = entry.path();
+                    path.is_img() || path.is_unrecognized_img()
+                })
+                .map(|entry| entry.metadata().map(|metadata| metadata.len()).unwrap_or(0))
+                .collect();
+            let img_count = img_sizes.len() as u32;
+            let empty_img_count = img_sizes.iter().filter(|size| **size == 0).count() as u32;
+
+            let missing_imgs = i64::from(img_count) < chapter_info.chapter_size;
+            if !missing_imgs && empty_img_count == 0 {
+                continue;
+            }
+
+            incomplete_chapters.push(IncompleteChapter {
+                chapter_uuid: chapter_info.chapter_uuid.clone(),
+                chapter_title: chapter_info.chapter_title.clone(),
+                chapter_size: chapter_info.chapter_size,
+                img_count,
+                empty_img_count,
+            });
+        }
+
+        Ok(incomplete_chapters)
+    }
+
+    /// 将所有分组的章节目录写入`path`，供外部工具使用
+    ///
+    /// 与`元数据.json`不同，字段名使用`snake_case`且保持稳定，章节按分组和`order`排序
+    pub fn export_catalog(&self, path: &Path) -> anyhow::Result<()> {
+        let mut chapters: Vec<CatalogChapter> = self
+            .comic
+            .groups
+            .values()
+            .flatten()
+            .map(|chapter_info| CatalogChapter {
+                chapter_uuid: chapter_info.chapter_uuid.clone(),
+                chapter_title: chapter_info.chapter_title.clone(),
+                order: self.get_chapter_order(chapter_info),
+                group_path_word: chapter_info.group_path_word.clone(),
+                group_name: chapter_info.group_name.clone(),
+                is_downloaded: chapter_info.is_downloaded == Some(true),
+                chapter_download_dir: chapter_info.chapter_download_dir.clone(),
+            })
+            .collect();
+        chapters.sort_by(|a, b| {
+            a.group_path_word
+                .cmp(&b.group_path_word)
+                .then_with(|| FloatOrd(a.order).cmp(&FloatOrd(b.order)))
+        });
+
+        let catalog = Catalog {
+            comic_uuid: self.comic.uuid.clone(),
+            comic_title: self.comic.name.clone(),
+            comic_path_word: self.comic.path_word.clone(),
+            comic_download_dir: self.comic_download_dir.clone(),
+            chapters,
+        };
+        let catalog_json =
+            serde_json::to_string_pretty(&catalog).context("将章节目录序列化为json失败")?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .context(format!("创建目录`{}`失败", parent.display()))?;
+        }
+        utils::write_atomically(path, catalog_json)
+            .context(format!("写入文件`{}`失败", path.display()))?;
+
+        Ok(())
+    }
+
+    /// 为漫画目录中有图片、但没有`章节元数据.json`的目录，按标题和序号模糊匹配章节并写入章节元数据
+    ///
+    /// 用于修复手动整理过目录的漫画，匹配不到章节的目录只记录日志，不会导致失败
+    pub fn repair_metadata(&mut self) -> anyhow::Result<()> {
+        let comic_title = self.comic.name.clone();
+        let comic_download_dir = self
+            .comic_download_dir
+            .clone()
+            .context("`comic_download_dir`字段为`None`")?;
+
+        let chapter_dirs: Vec<PathBuf> = find_dirs_with_imgs(&comic_download_dir)?
+            .into_iter()
+            .map(|(chapter_dir, _)| chapter_dir)
+            .filter(|chapter_dir| !chapter_dir.join("章节元数据.json").exists())
+            .collect();
+
+        for chapter_dir in chapter_dirs {
+            let chapter_dir_display = chapter_dir.display().to_string();
+            let dir_name = chapter_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let Some(chapter_uuid) = self
+                .match_chapter_by_dir_name(&dir_name)
+                .map(|chapter_info| chapter_info.chapter_uuid.clone())
+            else {
+                tracing::warn!(
+                    comic_title,
+                    chapter_dir = chapter_dir_display,
+                    "没有与目录匹配的章节，已跳过"
+                );
+                continue;
+            };
+            let Some(chapter_info) = self
+                .comic
+                .groups
+                .values_mut()
+                .flatten()
+                .find(|chapter_info| chapter_info.chapter_uuid == chapter_uuid)
+            else {
+                continue;
+            };
+
+            chapter_info.chapter_download_dir = Some(chapter_dir);
+            if let Err(err) = chapter_info.save_metadata() {
+                let err_title =
+                    format!("`{comic_title}`为目录`{chapter_dir_display}`写入章节元数据失败");
+                let string_chain = err.to_string_chain();
+                tracing::error!(err_title, message = string_chain);
+                continue;
+            }
+            chapter_info.is_downloaded = Some(true);
+            self.is_downloaded = Some(true);
+
+            let chapter_title = &chapter_info.chapter_title;
+            tracing::info!(
+                comic_title,
+                chapter_title,
+                chapter_dir = chapter_dir_display,
+                "已为目录写入章节元数据"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// 按目录名模糊匹配未下载的章节
+    ///
+    /// 目录名包含章节标题(都忽略标点、空白和大小写)的章节为候选，
+    /// 有多个候选时优先选目录名中含有其序号的，其次选标题最长的
+    fn match_chapter_by_dir_name(&self, dir_name: &str) -> Option<&ChapterInfo> {
+        fn normalize(s: &str) -> String {
+            s.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect()
+        }
+
+        let normalized_dir_name = normalize(dir_name);
+        let numbers_in_dir_name: Vec<f64> = dir_name
+            .split(|c: char| !c.is_ascii_digit() && c != '.')
+            .filter_map(|s| s.parse().ok())
+            .collect();
+
+        self.comic
+            .groups
+            .values()
+            .flatten()
+            .filter(|chapter_info| chapter_info.is_downloaded != Some(true))
+            .filter_map(|chapter_info| {
+                let normalized_title = normalize(&chapter_info.chapter_title);
+                if normalized_title.is_empty() || !normalized_dir_name.contains(&normalized_title) {
+                    return None;
+                }
+                let order = self.get_chapter_order(chapter_info);
+                let order_matches = numbers_in_dir_name
+                    .iter()
+                    .any(|number| (number - order).abs() < 1e-6);
+                let title_len = normalized_title.chars().count();
+                Some((chapter_info, order_matches, title_len))
+            })
+            .max_by_key(|(chapter_info, order_matches, title_len)| {
+                (
+                    *order_matches,
+                    *title_len,
+                    chapter_info.group_path_word == "default",
+                )
+            })
+            .map(|(chapter_info, _, _)| chapter_info)
+    }
+
+    /// 按照当前的`comic_dir_fmt`重命名漫画的下载目录，并更新元数据中所有章节的下载目录
+    ///
+    /// 新旧目录相同时什么都不做，新目录已存在时返回错误，不会覆盖
+    pub fn rename_comic_download_dir(&mut self, app: &AppHandle) -> anyhow::Result<()> {
+        let old_comic_download_dir = self
+            .comic_download_dir
+            .clone()
+            .context("`comic_download_dir`字段为`None`")?;
+
+        let mut comic_by_fmt = self.clone();
+        comic_by_fmt
+            .update_download_dir_fields_by_fmt(app)
+            .context("根据fmt计算漫画下载目录失败")?;
+        let new_comic_download_dir = comic_by_fmt
+            .comic_download_dir
+            .context("`comic_download_dir`字段为`None`")?;
+
+        if new_comic_download_dir == old_comic_download_dir {
+            return Ok(());
+        }
+        if new_comic_download_dir.exists() {
+            return Err(anyhow!(
+                "目标目录`{}`已存在",
+                new_comic_download_dir.display()
+            ));
+        }
+
+        if let Some(parent) = new_comic_download_dir.parent() {
+            std::fs::create_dir_all(parent)
+                .context(format!("创建目录`{}`失败", parent.display()))?;
+        }
+        std::fs::rename(&old_comic_download_dir, &new_comic_download_dir).context(format!(
+            "将`{}`重命名为`{}`失败",
+            old_comic_download_dir.display(),
+            new_comic_download_dir.display()
+        ))?;
+        utils::invalidate_library_cache(app);
+
+        // 章节目录相对于漫画目录的结构不变，只需要替换前缀
+        for chapter_info in self.comic.groups.values_mut().flatten() {
+            let Some(chapter_download_dir) = &chapter_info.chapter_download_dir else {
+                continue;
+            };
+            if let Ok(relative_dir) = chapter_download_dir.strip_prefix(&old_comic_download_dir) {
+                chapter_info.chapter_download_dir = Some(new_comic_download_dir.join(relative_dir));
             }
         }
+        self.cover_path = Comic::get_cover_path(&new_comic_download_dir);
+        self.comic_download_dir = Some(new_comic_download_dir);
+
+        self.save_metadata().context("保存元数据失败")?;
 
         Ok(())
     }
 }
 
+/// 是否为漫画目录中的封面`cover.<ext>`，章节目录中的同名图片不算
+fn is_comic_cover(comic_download_dir: &Path, path: &Path) -> bool {
+    path.parent() == Some(comic_download_dir)
+        && path.is_img()
+        && path.file_stem().is_some_and(|stem| stem == "cover")
+}
+
+/// 遍历`comic_download_dir`，返回所有含有图片(不包括漫画封面)的目录及其中的图片数量
+///
+/// 未下载完成的临时目录(见`WalkDirEntryExt::is_temp_download_dir`)会被跳过
+fn find_dirs_with_imgs(comic_download_dir: &Path) -> anyhow::Result<Vec<(PathBuf, usize)>> {
+    let mut dirs_with_imgs = Vec::new();
+    for entry in WalkDir::new(comic_download_dir)
+        .into_iter()
+        .filter_entry(|entry| !entry.is_temp_download_dir())
+        .filter_map(Result::ok)
+    {
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+
+        let dir = entry.path();
+        let img_count = std::fs::read_dir(dir)
+            .context(format!("读取目录`{}`失败", dir.display()))?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_img() && !is_comic_cover(comic_download_dir, path))
+            .count();
+        if img_count > 0 {
+            dirs_with_imgs.push((dir.to_path_buf(), img_count));
+        }
+    }
+    Ok(dirs_with_imgs)
+}
+
+/// 旧版本章节目录的迁移结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct MigratedChapter {
+    pub chapter_uuid: String,
+    pub chapter_title: String,
+    pub group_name: String,
+    pub old_chapter_dir: PathBuf,
+    /// 重命名后的目录，没有重命名则为`None`
+    pub new_chapter_dir: Option<PathBuf>,
+    /// 是否为此章节新创建了元数据
+    pub metadata_created: bool,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 #[serde(rename_all = "camelCase")]
 #[allow(clippy::struct_excessive_bools)]
@@ -494,6 +1302,74 @@ impl LastChapter {
     }
 }
 
+/// 有图片但没有有效章节元数据的目录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterDirWithoutMetadata {
+    pub chapter_dir: PathBuf,
+    pub img_count: u32,
+    /// `章节元数据.json`存在但内容无效时为`true`
+    pub metadata_exists: bool,
+    /// 按照当前的fmt推测出的章节ID，推测不出时为`None`
+    pub chapter_uuid: Option<String>,
+}
+
+/// 图片不完整的已下载章节
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct IncompleteChapter {
+    pub chapter_uuid: String,
+    pub chapter_title: String,
+    pub chapter_size: i64,
+    pub img_count: u32,
+    /// 大小为0的图片数
+    pub empty_img_count: u32,
+}
+
+/// `Comic::export_catalog`导出的章节目录，字段名是对外的格式，不要随意修改
+#[derive(Debug, Clone, Serialize)]
+struct Catalog {
+    comic_uuid: String,
+    comic_title: String,
+    comic_path_word: String,
+    comic_download_dir: Option<PathBuf>,
+    chapters: Vec<CatalogChapter>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CatalogChapter {
+    chapter_uuid: String,
+    chapter_title: String,
+    order: f64,
+    group_path_word: String,
+    group_name: String,
+    is_downloaded: bool,
+    chapter_download_dir: Option<PathBuf>,
+}
+
+/// 创建失败的章节目录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterDirCreateFailure {
+    pub chapter_uuid: String,
+    pub chapter_title: String,
+    pub chapter_download_dir: PathBuf,
+    pub err_message: String,
+}
+
+/// 分组的章节数统计
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupDownloadCount {
+    pub group_path_word: String,
+    pub group_name: String,
+    /// 服务器给出的章节数，分组信息中没有这个分组时为`None`
+    pub server_count: Option<u32>,
+    /// 章节列表中实际的章节数，可能与`server_count`不一致
+    pub chapter_count: u32,
+    pub downloaded_count: u32,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 #[serde(rename_all = "camelCase")]
 pub struct Group {
@@ -516,4 +1392,167 @@ impl Group {
             })
             .collect()
     }
+
+    /// 将分组按章节数从多到少排序，章节数相同时按名称排序
+    pub fn sorted_by_count(group: HashMap<String, GroupRespData>) -> Vec<Group> {
+        let mut groups: Vec<Group> = Group::from(group).into_values().collect();
+        groups.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        groups
+    }
+}
+
+/// 从`dir`开始逐级向上删除空目录，遇到非空目录或到达`stop_dir`(不删除)时停止
+fn remove_emptied_dirs(dir: &Path, stop_dir: &Path) {
+    let mut dir = dir;
+    while dir != stop_dir && dir.starts_with(stop_dir) {
+        // `remove_dir`只能删除空目录，失败说明目录非空，更上层的目录也就不会是空的
+        if std::fs::remove_dir(dir).is_err() {
+            break;
+        }
+        let Some(parent) = dir.parent() else {
+            break;
+        };
+        dir = parent;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::test_utils::create_test_dir;
+
+    #[test]
+    fn save_metadata_replaces_garbage_metadata() {
+        let comic_download_dir = create_test_dir();
+        let metadata_path = comic_download_dir.join("元数据.json");
+        std::fs::write(&metadata_path, b"\0\xff{\"comic\": {\"name\": \"trunc").unwrap();
+
+        let mut comic = Comic {
+            comic_download_dir: Some(comic_download_dir.clone()),
+            ..Default::default()
+        };
+        comic.comic.name = "漫画".to_string();
+        comic
+            .order_overrides
+            .insert("chapter-uuid".to_string(), 1.5);
+        let save_result = comic.save_metadata();
+
+        let metadata_str = std::fs::read_to_string(&metadata_path);
+        let entry_count = std::fs::read_dir(&comic_download_dir).unwrap().count();
+        let _ = std::fs::remove_dir_all(&comic_download_dir);
+        save_result.unwrap();
+        let saved: Comic = serde_json::from_str(&metadata_str.unwrap()).unwrap();
+        assert_eq!(saved.comic.name, "漫画");
+        assert_eq!(saved.order_overrides, comic.order_overrides);
+        // 不应该留下临时文件
+        assert_eq!(entry_count, 1);
+    }
+    #[test]
+    fn concurrent_save_metadata_never_leaves_torn_metadata() {
+        const WRITER_COUNT: usize = 8;
+        const SAVE_COUNT: usize = 20;
+
+        let comic_download_dir = create_test_dir();
+        let metadata_path = comic_download_dir.join("元数据.json");
+        let comics: Vec<Comic> = (0..WRITER_COUNT)
+            .map(|i| {
+                let mut comic = Comic {
+                    comic_download_dir: Some(comic_download_dir.clone()),
+                    ..Default::default()
+                };
+                // 每个写入者的内容长度不同，内容交错或被截断时就无法解析
+                comic.comic.name = format!("漫画{i}").repeat(i * 100 + 1);
+                comic
+            })
+            .collect();
+
+        let writers_done = AtomicBool::new(false);
+        let torn_read_count = std::thread::scope(|scope| {
+            let reader = scope.spawn(|| {
+                let mut torn_read_count = 0;
+                while !writers_done.load(Ordering::Relaxed) {
+                    let Ok(metadata_str) = std::fs::read_to_string(&metadata_path) else {
+                        continue;
+                    };
+                    if serde_json::from_str::<Comic>(&metadata_str).is_err() {
+                        torn_read_count += 1;
+                    }
+                }
+                torn_read_count
+            });
+            let writers: Vec<_> = comics
+                .iter()
+                .map(|comic| {
+                    scope.spawn(move || {
+                        for _ in 0..SAVE_COUNT {
+                            comic.save_metadata().unwrap();
+                        }
+                    })
+                })
+                .collect();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            writers_done.store(true, Ordering::Relaxed);
+            reader.join().unwrap()
+        });
+
+        let metadata_str = std::fs::read_to_string(&metadata_path).unwrap();
+        let entry_count = std::fs::read_dir(&comic_download_dir).unwrap().count();
+        let _ = std::fs::remove_dir_all(&comic_download_dir);
+        assert_eq!(torn_read_count, 0);
+        let saved: Comic = serde_json::from_str(&metadata_str).unwrap();
+        assert!(comics
+            .iter()
+            .any(|comic| comic.comic.name == saved.comic.name));
+        // 不应该留下临时文件
+        assert_eq!(entry_count, 1);
+    }
+    #[test]
+    fn only_dirs_emptied_by_migration_are_removed() {
+        let comic_download_dir = create_test_dir();
+        let old_group_dir = comic_download_dir.join("旧分组").join("话");
+        let kept_group_dir = comic_download_dir.join("保留分组");
+        let user_empty_dir = comic_download_dir.join("用户的空目录");
+        for dir in [&old_group_dir, &kept_group_dir, &user_empty_dir] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(kept_group_dir.join("备注.txt"), b"").unwrap();
+
+        // 章节已经从`old_group_dir`和`kept_group_dir`移走
+        remove_emptied_dirs(&old_group_dir, &comic_download_dir);
+        remove_emptied_dirs(&kept_group_dir, &comic_download_dir);
+
+        let old_group_exists = comic_download_dir.join("旧分组").exists();
+        let kept_group_exists = kept_group_dir.exists();
+        let user_empty_dir_exists = user_empty_dir.exists();
+        let comic_download_dir_exists = comic_download_dir.exists();
+        let _ = std::fs::remove_dir_all(&comic_download_dir);
+        assert!(!old_group_exists);
+        assert!(kept_group_exists);
+        assert!(user_empty_dir_exists);
+        assert!(comic_download_dir_exists);
+    }
+    #[test]
+    fn find_dirs_with_imgs_skips_temp_dirs_and_cover() {
+        let comic_download_dir = create_test_dir();
+        let chapter_dir = comic_download_dir.join("默认").join("第1话");
+        let temp_dir = comic_download_dir.join("默认").join(".下载中-第2话");
+        let json_only_dir = comic_download_dir.join("默认").join("第3话");
+        for dir in [&chapter_dir, &temp_dir, &json_only_dir] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(comic_download_dir.join("cover.jpg"), b"").unwrap();
+        for filename in ["001.webp", "002.webp", "章节元数据.json"] {
+            std::fs::write(chapter_dir.join(filename), b"").unwrap();
+        }
+        std::fs::write(temp_dir.join("001.webp"), b"").unwrap();
+        std::fs::write(json_only_dir.join("章节元数据.json"), b"").unwrap();
+
+        let dirs_with_imgs = find_dirs_with_imgs(&comic_download_dir);
+        let _ = std::fs::remove_dir_all(&comic_download_dir);
+        assert_eq!(dirs_with_imgs.unwrap(), [(chapter_dir, 2)]);
+    }
 }
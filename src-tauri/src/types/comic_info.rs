@@ -49,10 +49,44 @@ pub struct ComicInfo {
     /// - `其他非零值` => Ended
     #[yaserde(rename = "Count")]
     pub count: i64,
+    /// 每一页的信息，Komga/Kavita根据`Type="FrontCover"`选择封面
+    #[yaserde(rename = "Pages")]
+    pub pages: ComicInfoPages,
 }
+
+#[derive(
+    Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type, YaSerialize, YaDeserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ComicInfoPages {
+    #[yaserde(rename = "Page")]
+    pub pages: Vec<ComicInfoPage>,
+}
+
+#[derive(
+    Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type, YaSerialize, YaDeserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ComicInfoPage {
+    /// 图片在cbz中的序号，从0开始
+    #[yaserde(attribute = true, rename = "Image")]
+    pub image: i64,
+    /// 页面类型，第一页为`FrontCover`，其他页不填
+    #[yaserde(attribute = true, rename = "Type")]
+    pub page_type: Option<String>,
+}
+
 impl ComicInfo {
+    /// `image_count`为章节中实际导出的图片数量(包括专用封面)，用于生成`Pages`
+    ///
+    /// `has_dedicated_cover`为`true`时，第一张图片是专用封面，它被标记为`FrontCover`，但不算在`PageCount`中
     #[allow(clippy::cast_possible_wrap)]
-    pub fn from(comic: &Comic, chapter_info: &ChapterInfo) -> ComicInfo {
+    pub fn from(
+        comic: &Comic,
+        chapter_info: &ChapterInfo,
+        image_count: usize,
+        has_dedicated_cover: bool,
+    ) -> ComicInfo {
         let order = Some(chapter_info.order.to_string());
         let (number, volume, format) = match chapter_info.group_path_word.as_str() {
             "default" => (order, None, None),
@@ -60,6 +94,21 @@ impl ComicInfo {
             _ => (order, None, Some("Special".to_string())),
         };
 
+        let pages = (0..image_count)
+            .map(|i| ComicInfoPage {
+                image: i as i64,
+                page_type: (i == 0).then(|| "FrontCover".to_string()),
+            })
+            .collect();
+
+        // 章节大小未知时用实际导出的图片数量，专用封面不算页数
+        let page_count = if chapter_info.chapter_size > 0 {
+            chapter_info.chapter_size
+        } else {
+            let cover_count = usize::from(has_dedicated_cover);
+            image_count.saturating_sub(cover_count) as i64
+        };
+
         let count = match chapter_info.comic_status {
             ComicStatus::Ongoing => 0,
             ComicStatus::Completed => chapter_info.group_size,
@@ -88,8 +137,9 @@ impl ComicInfo {
             number,
             volume,
             format,
-            page_count: chapter_info.chapter_size,
+            page_count,
             count,
+            pages: ComicInfoPages { pages },
         }
     }
 }
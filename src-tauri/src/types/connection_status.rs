@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// 连接测试的结果
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionStatus {
+    pub state: ConnectionState,
+    /// 从发送请求到收到响应的耗时(毫秒)，无法连接时为`None`
+    pub latency_ms: Option<u64>,
+    /// 无法连接时的错误信息
+    pub err_message: Option<String>,
+}
+
+/// - `Ok`: 域名可以访问，token有效
+/// - `DomainUnreachable`: 所有API域名都无法连接
+/// - `TokenInvalid`: 域名可以访问，但token错误、过期或未登录
+/// - `RiskControl`: 域名可以访问，但触发了风控
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Type)]
+pub enum ConnectionState {
+    Ok,
+    DomainUnreachable,
+    TokenInvalid,
+    RiskControl,
+}
@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::AppHandle;
+use walkdir::WalkDir;
+
+use crate::{
+    config::DEFAULT_USER_AGENT,
+    extensions::{AppHandleExt, WalkDirEntryExt},
+};
+
+const REDACTED: &str = "<已隐藏>";
+
+/// 用于反馈问题的诊断信息，敏感字段已隐藏
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostics {
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub config: serde_json::Value,
+    pub dirs: Vec<DirDiagnostics>,
+    pub downloaded_comic_count: u32,
+    pub downloaded_chapter_count: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DirDiagnostics {
+    pub name: String,
+    pub path: PathBuf,
+    pub exists: bool,
+    /// 所在磁盘的剩余空间(字节)，目录不存在或获取失败时为`None`
+    pub available_space: Option<u64>,
+}
+
+impl Diagnostics {
+    pub fn new(app: &AppHandle) -> anyhow::Result<Diagnostics> {
+        let config = app.get_config().read().clone();
+
+        let mut config_json = serde_json::to_value(&config).context("将Config序列化为json失败")?;
+        // token、代理地址(可能包含账号密码)和额外的header(可能包含cookie)不能出现在诊断信息中
+        config_json["token"] = serde_json::Value::String(REDACTED.to_string());
+        if let Some(proxy_rules) = config_json["proxyRules"].as_array_mut() {
+            for proxy_rule in proxy_rules {
+                proxy_rule["proxyUrl"] = serde_json::Value::String(REDACTED.to_string());
+            }
+        }
+        if let Some(extra_headers) = config_json["extraHeaders"].as_object_mut() {
+            for header_value in extra_headers.values_mut() {
+                *header_value = serde_json::Value::String(REDACTED.to_string());
+            }
+        }
+        // 自定义的User-Agent可能包含能识别用户的信息，默认值则无需隐藏
+        if !config.user_agent.is_empty() && config.user_agent != DEFAULT_USER_AGENT {
+            config_json["userAgent"] = serde_json::Value::String(REDACTED.to_string());
+        }
+
+        let mut dirs = vec![
+            DirDiagnostics::new("downloadDir", config.download_dir.clone()),
+            DirDiagnostics::new("exportDir", config.export_dir.clone()),
+        ];
+        if let Some(cbz_export_dir) = config.cbz_export_dir.clone() {
+            dirs.push(DirDiagnostics::new("cbzExportDir", cbz_export_dir));
+        }
+        if let Some(pdf_export_dir) = config.pdf_export_dir.clone() {
+            dirs.push(DirDiagnostics::new("pdfExportDir", pdf_export_dir));
+        }
+
+        let mut downloaded_comic_count = 0;
+        let mut downloaded_chapter_count = 0;
+        for entry in WalkDir::new(&config.download_dir)
+            .into_iter()
+            .filter_map(Result::ok)
+        {
+            if entry.is_comic_metadata() {
+                downloaded_comic_count += 1;
+            } else if entry.is_chapter_metadata() {
+                downloaded_chapter_count += 1;
+            }
+        }
+
+        Ok(Diagnostics {
+            app_version: app.package_info().version.to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            config: config_json,
+            dirs,
+            downloaded_comic_count,
+            downloaded_chapter_count,
+        })
+    }
+}
+
+impl DirDiagnostics {
+    fn new(name: &str, path: PathBuf) -> DirDiagnostics {
+        let exists = path.exists();
+        let available_space = if exists {
+            fs4::available_space(&path).ok()
+        } else {
+            None
+        };
+
+        DirDiagnostics {
+            name: name.to_string(),
+            path,
+            exists,
+            available_space,
+        }
+    }
+}
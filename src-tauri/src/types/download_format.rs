@@ -2,10 +2,14 @@ use image::ImageFormat;
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
+/// `Jxl`无论是否启用`jxl`功能都存在，这样配置文件和前端的类型不随编译选项变化
+///
+/// 未启用`jxl`功能时，保存或转换为`Jxl`会返回错误
 #[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize, Type)]
 pub enum DownloadFormat {
     Webp,
     Jpeg,
+    Jxl,
 }
 
 impl DownloadFormat {
@@ -13,13 +17,16 @@ impl DownloadFormat {
         match self {
             DownloadFormat::Webp => "webp",
             DownloadFormat::Jpeg => "jpg",
+            DownloadFormat::Jxl => "jxl",
         }
     }
 
-    pub fn to_image_format(self) -> ImageFormat {
+    /// 对应的`ImageFormat`，`image`库不支持的格式(如jxl)返回`None`
+    pub fn to_image_format(self) -> Option<ImageFormat> {
         match self {
-            DownloadFormat::Webp => ImageFormat::WebP,
-            DownloadFormat::Jpeg => ImageFormat::Jpeg,
+            DownloadFormat::Webp => Some(ImageFormat::WebP),
+            DownloadFormat::Jpeg => Some(ImageFormat::Jpeg),
+            DownloadFormat::Jxl => None,
         }
     }
 }
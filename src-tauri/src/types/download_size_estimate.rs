@@ -0,0 +1,138 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::AppHandle;
+
+use crate::{
+    extensions::{AnyhowErrorToStringChain, AppHandleExt},
+    types::Comic,
+};
+
+/// 漫画下载大小的估算结果，所有`estimated_`开头的字段都只是估算值
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadSizeEstimate {
+    pub chapter_count: i64,
+    /// `chapter_size`缺失或为0的章节数，这些章节的页数按其他章节的平均页数估算
+    pub chapters_without_size: i64,
+    pub estimated_page_count: i64,
+    /// 实际通过HEAD请求获取到大小的图片数
+    pub sampled_img_count: i64,
+    pub estimated_avg_img_bytes: Option<u64>,
+    /// 采样失败或没有采样时为`None`
+    pub estimated_total_bytes: Option<u64>,
+}
+
+impl DownloadSizeEstimate {
+    /// 估算`comic`中`chapter_uuids`对应章节的下载大小
+    ///
+    /// 如果`sample_img_count`大于0，会从第一个有页数的章节中均匀抽取图片，用HEAD请求获取平均图片大小
+    #[allow(clippy::cast_possible_wrap)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_possible_truncation)]
+    pub async fn estimate(
+        app: &AppHandle,
+        comic: &Comic,
+        chapter_uuids: &[String],
+        sample_img_count: usize,
+    ) -> anyhow::Result<DownloadSizeEstimate> {
+        let chapter_infos: Vec<_> = comic
+            .comic
+            .groups
+            .values()
+            .flatten()
+            .filter(|chapter_info| chapter_uuids.contains(&chapter_info.chapter_uuid))
+            .collect();
+
+        let chapter_count = chapter_infos.len() as i64;
+        let known_page_count: i64 = chapter_infos
+            .iter()
+            .map(|chapter_info| chapter_info.chapter_size.max(0))
+            .sum();
+        let chapters_without_size = chapter_infos
+            .iter()
+            .filter(|chapter_info| chapter_info.chapter_size <= 0)
+            .count() as i64;
+        let chapters_with_size = chapter_count - chapters_without_size;
+        let estimated_page_count = if chapters_with_size > 0 {
+            let avg_page_count = known_page_count as f64 / chapters_with_size as f64;
+            known_page_count + (avg_page_count * chapters_without_size as f64).round() as i64
+        } else {
+            known_page_count
+        };
+
+        let mut estimate = DownloadSizeEstimate {
+            chapter_count,
+            chapters_without_size,
+            estimated_page_count,
+            ..Default::default()
+        };
+
+        let sample_chapter = chapter_infos
+            .iter()
+            .find(|chapter_info| chapter_info.chapter_size > 0);
+        let Some(sample_chapter) = sample_chapter.filter(|_| sample_img_count > 0) else {
+            return Ok(estimate);
+        };
+
+        let img_sizes = match sample_img_sizes(
+            app,
+            comic,
+            &sample_chapter.chapter_uuid,
+            sample_img_count,
+        )
+        .await
+        {
+            Ok(img_sizes) => img_sizes,
+            Err(err) => {
+                // 采样失败时仍然返回页数的估算
+                let err_title = format!("`{}`采样图片大小失败", comic.comic.name);
+                let string_chain = err.to_string_chain();
+                tracing::error!(err_title, message = string_chain);
+                return Ok(estimate);
+            }
+        };
+        if img_sizes.is_empty() {
+            return Ok(estimate);
+        }
+
+        let avg_img_bytes = img_sizes.iter().sum::<u64>() / img_sizes.len() as u64;
+        estimate.sampled_img_count = img_sizes.len() as i64;
+        estimate.estimated_avg_img_bytes = Some(avg_img_bytes);
+        estimate.estimated_total_bytes = Some(avg_img_bytes * estimated_page_count.max(0) as u64);
+
+        Ok(estimate)
+    }
+}
+
+/// 从章节中均匀抽取最多`sample_img_count`张图片，返回它们的大小
+async fn sample_img_sizes(
+    app: &AppHandle,
+    comic: &Comic,
+    chapter_uuid: &str,
+    sample_img_count: usize,
+) -> anyhow::Result<Vec<u64>> {
+    let copy_client = app.get_copy_client();
+    let comic_path_word = &comic.comic.path_word;
+
+    let chapter_resp_data = copy_client
+        .get_chapter(comic_path_word, chapter_uuid)
+        .await
+        .context(format!("获取章节`{chapter_uuid}`失败"))?;
+    // 与下载时使用的URL保持一致，条漫是否跳过高清图片也一样
+    let urls = comic.get_img_urls(app, &chapter_resp_data.chapter);
+    if urls.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let step = (urls.len() / sample_img_count).max(1);
+    let mut img_sizes = Vec::new();
+    for url in urls.iter().step_by(step).take(sample_img_count) {
+        if let Some(img_size) = copy_client.get_img_size(url).await? {
+            img_sizes.push(img_size);
+        }
+    }
+
+    Ok(img_sizes)
+}
@@ -0,0 +1,58 @@
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use walkdir::WalkDir;
+
+use crate::extensions::WalkDirEntryExt;
+
+/// 漫画的下载状态，通过比较已下载章节数与元数据中的章节总数得到
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub enum DownloadStatus {
+    NotDownloaded,
+    /// 只下载了部分章节
+    Partial,
+    Complete,
+}
+
+impl DownloadStatus {
+    /// 章节总数来自`元数据.json`，已下载章节数通过统计`章节元数据.json`的数量得到
+    pub fn from_comic_download_dir(comic_download_dir: &Path) -> anyhow::Result<DownloadStatus> {
+        let metadata_path = comic_download_dir.join("元数据.json");
+        let metadata_str = std::fs::read_to_string(&metadata_path)
+            .context(format!("读取`{}`失败", metadata_path.display()))?;
+        let comic_json: serde_json::Value =
+            serde_json::from_str(&metadata_str).context(format!(
+                "将`{}`反序列化为serde_json::Value失败",
+                metadata_path.display()
+            ))?;
+        let total_chapter_count: usize = comic_json
+            .pointer("/comic/groups")
+            .and_then(|groups| groups.as_object())
+            .context(format!(
+                "`{}`没有`comic.groups`字段",
+                metadata_path.display()
+            ))?
+            .values()
+            .filter_map(|chapters| chapters.as_array())
+            .map(Vec::len)
+            .sum();
+
+        let downloaded_chapter_count = WalkDir::new(comic_download_dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.is_chapter_metadata())
+            .count();
+
+        let download_status = if downloaded_chapter_count == 0 {
+            DownloadStatus::NotDownloaded
+        } else if downloaded_chapter_count >= total_chapter_count {
+            DownloadStatus::Complete
+        } else {
+            DownloadStatus::Partial
+        };
+
+        Ok(download_status)
+    }
+}
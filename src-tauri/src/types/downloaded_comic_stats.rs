@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use walkdir::WalkDir;
+
+use crate::extensions::{AnyhowErrorToStringChain, WalkDirEntryExt};
+
+/// 已下载漫画的统计信息，用于展示本地书库
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadedComicStats {
+    pub name: String,
+    pub path_word: String,
+    pub comic_download_dir: PathBuf,
+    pub downloaded_chapter_count: u32,
+    pub total_bytes: u64,
+    /// 漫画目录中最新的文件修改时间(Unix时间戳，单位为秒)
+    pub last_modified: i64,
+    /// `元数据.json`损坏时的错误信息，此时`name`为目录名，`path_word`为空
+    pub err_message: Option<String>,
+}
+
+impl DownloadedComicStats {
+    /// 只解析`元数据.json`，章节数通过统计`章节元数据.json`的数量得到，不解析章节元数据
+    pub fn from_comic_download_dir(comic_download_dir: PathBuf) -> DownloadedComicStats {
+        let mut stats = DownloadedComicStats::default();
+
+        match read_name_and_path_word(&comic_download_dir) {
+            Ok((name, path_word)) => {
+                stats.name = name;
+                stats.path_word = path_word;
+            }
+            Err(err) => {
+                stats.name = comic_download_dir
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                stats.err_message = Some(err.to_string_chain());
+            }
+        }
+
+        for entry in WalkDir::new(&comic_download_dir)
+            .into_iter()
+            .filter_map(Result::ok)
+        {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            if entry.is_chapter_metadata() {
+                stats.downloaded_chapter_count += 1;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            stats.total_bytes += metadata.len();
+            if let Ok(modified) = metadata.modified() {
+                let modified = chrono::DateTime::<chrono::Local>::from(modified).timestamp();
+                stats.last_modified = stats.last_modified.max(modified);
+            }
+        }
+
+        stats.comic_download_dir = comic_download_dir;
+        stats
+    }
+}
+
+fn read_name_and_path_word(comic_download_dir: &Path) -> anyhow::Result<(String, String)> {
+    let metadata_path = comic_download_dir.join("元数据.json");
+    let metadata_str = std::fs::read_to_string(&metadata_path)
+        .context(format!("读取`{}`失败", metadata_path.display()))?;
+    let comic_json: serde_json::Value = serde_json::from_str(&metadata_str).context(format!(
+        "将`{}`反序列化为serde_json::Value失败",
+        metadata_path.display()
+    ))?;
+
+    let get_str = |pointer: &str| -> anyhow::Result<String> {
+        let value = comic_json
+            .pointer(pointer)
+            .and_then(|value| value.as_str())
+            .context(format!("`{}`没有`{pointer}`字段", metadata_path.display()))?;
+        Ok(value.to_string())
+    };
+
+    Ok((get_str("/comic/name")?, get_str("/comic/path_word")?))
+}
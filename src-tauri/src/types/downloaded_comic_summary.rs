@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tauri::AppHandle;
+
+use crate::{extensions::AnyhowErrorToStringChain, utils};
+
+/// 已下载漫画的简要信息，直接从元数据中读取，不需要解析完整的`Comic`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadedComicSummary {
+    pub uuid: String,
+    pub path_word: String,
+    pub name: String,
+    pub alias: Option<String>,
+    pub cover: String,
+    pub comic_download_dir: PathBuf,
+}
+
+impl DownloadedComicSummary {
+    /// 在已下载的漫画中搜索标题或别名包含`query`的漫画(不区分大小写)
+    pub fn search(app: &AppHandle, query: &str) -> anyhow::Result<Vec<DownloadedComicSummary>> {
+        let query = query.trim().to_lowercase();
+        let path_word_to_dir_map =
+            utils::create_path_word_to_dir_map(app).context("创建漫画路径词到下载目录映射失败")?;
+
+        let mut summaries = Vec::new();
+        for comic_download_dir in path_word_to_dir_map.into_values().flatten() {
+            let summary = match DownloadedComicSummary::from_metadata(comic_download_dir) {
+                Ok(summary) => summary,
+                Err(err) => {
+                    let err_title = "搜索已下载漫画的过程中遇到错误，已跳过";
+                    let string_chain = err.to_string_chain();
+                    tracing::error!(err_title, message = string_chain);
+                    continue;
+                }
+            };
+
+            let name_matched = summary.name.to_lowercase().contains(&query);
+            let alias_matched = summary
+                .alias
+                .as_ref()
+                .is_some_and(|alias| alias.to_lowercase().contains(&query));
+            if name_matched || alias_matched {
+                summaries.push(summary);
+            }
+        }
+        summaries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(summaries)
+    }
+
+    fn from_metadata(comic_download_dir: PathBuf) -> anyhow::Result<DownloadedComicSummary> {
+        let metadata_path = comic_download_dir.join("元数据.json");
+        let metadata_str = std::fs::read_to_string(&metadata_path)
+            .context(format!("读取`{}`失败", metadata_path.display()))?;
+        let comic_json: serde_json::Value =
+            serde_json::from_str(&metadata_str).context(format!(
+                "将`{}`反序列化为serde_json::Value失败",
+                metadata_path.display()
+            ))?;
+
+        let get_str = |pointer: &str| -> anyhow::Result<String> {
+            let value = comic_json
+                .pointer(pointer)
+                .and_then(|value| value.as_str())
+                .context(format!("`{}`没有`{pointer}`字段", metadata_path.display()))?;
+            Ok(value.to_string())
+        };
+
+        Ok(DownloadedComicSummary {
+            uuid: get_str("/comic/uuid")?,
+            path_word: get_str("/comic/path_word")?,
+            name: get_str("/comic/name")?,
+            alias: get_str("/comic/alias").ok(),
+            cover: get_str("/comic/cover").unwrap_or_default(),
+            comic_download_dir,
+        })
+    }
+}
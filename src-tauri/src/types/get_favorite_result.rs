@@ -10,7 +10,11 @@ use specta::Type;
 use tauri::AppHandle;
 
 use crate::{
-    responses::{AuthorRespData, ComicInGetFavoriteRespData, GetFavoriteRespData, Pagination},
+    extensions::AppHandleExt,
+    responses::{
+        AuthorRespData, ComicInGetFavoriteRespData, FavoriteItemRespData, GetFavoriteRespData,
+        Pagination,
+    },
     utils,
 };
 
@@ -42,16 +46,8 @@ impl GetFavoriteResult {
 
         let path_word_to_dir_map =
             utils::create_path_word_to_dir_map(app).context("创建漫画路径词到下载目录映射失败")?;
-        let mut list = Vec::with_capacity(resp_data.list.len());
-
-        for item in resp_data.0.list {
-            let comic = ComicInFavorite::from_resp_data(&item.comic, &path_word_to_dir_map);
-            list.push(FavoriteItem {
-                uuid: item.uuid,
-                b_folder: item.b_folder,
-                comic,
-            });
-        }
+        let concurrency = app.get_config().read().download_status_concurrency;
+        let list = resolve_favorite_items(resp_data.0.list, &path_word_to_dir_map, concurrency)?;
 
         let get_favorite_result = GetFavoriteResult(Pagination {
             list,
@@ -64,6 +60,25 @@ impl GetFavoriteResult {
     }
 }
 
+/// 在大小为`concurrency`的线程池中计算每个收藏漫画的下载状态，结果保持原来的顺序
+///
+/// `path_word_to_dir_map`只读，可以在多个线程间共享
+fn resolve_favorite_items(
+    items: Vec<FavoriteItemRespData>,
+    path_word_to_dir_map: &HashMap<String, Vec<PathBuf>>,
+    concurrency: usize,
+) -> anyhow::Result<Vec<FavoriteItem>> {
+    utils::par_map_in_pool(items, concurrency, |item| {
+        let comic = ComicInFavorite::from_resp_data(&item.comic, path_word_to_dir_map);
+        FavoriteItem {
+            uuid: item.uuid,
+            b_folder: item.b_folder,
+            comic,
+        }
+    })
+    .context("计算收藏漫画的下载状态失败")
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 #[serde(rename_all = "camelCase")]
 pub struct FavoriteItem {
@@ -125,3 +140,57 @@ impl ComicInFavorite {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    /// 创建一页有`count`个漫画的收藏，其中偶数下标的漫画已下载
+    fn create_favorite_page(
+        count: usize,
+    ) -> (Vec<FavoriteItemRespData>, HashMap<String, Vec<PathBuf>>) {
+        let mut path_word_to_dir_map = HashMap::new();
+        let items = (0..count)
+            .map(|i| {
+                let path_word = format!("comic{i}");
+                if i % 2 == 0 {
+                    let comic_download_dir = PathBuf::from(format!("漫画下载/漫画{i}"));
+                    path_word_to_dir_map.insert(path_word.clone(), vec![comic_download_dir]);
+                }
+                FavoriteItemRespData {
+                    uuid: i as i64,
+                    comic: ComicInGetFavoriteRespData {
+                        name: format!("漫画{i}"),
+                        path_word,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }
+            })
+            .collect();
+        (items, path_word_to_dir_map)
+    }
+
+    #[test]
+    fn favorite_page_resolved_in_parallel_matches_sequential() {
+        let (items, path_word_to_dir_map) = create_favorite_page(50);
+
+        let start = Instant::now();
+        let sequential = resolve_favorite_items(items.clone(), &path_word_to_dir_map, 1).unwrap();
+        let sequential_elapsed = start.elapsed();
+
+        let start = Instant::now();
+        let parallel = resolve_favorite_items(items, &path_word_to_dir_map, 8).unwrap();
+        let parallel_elapsed = start.elapsed();
+
+        println!("50个收藏 串行: {sequential_elapsed:?} 8线程: {parallel_elapsed:?}");
+        assert_eq!(sequential, parallel);
+        assert_eq!(parallel.len(), 50);
+        for (i, item) in parallel.iter().enumerate() {
+            assert_eq!(item.uuid, i as i64);
+            assert_eq!(item.comic.is_downloaded, i % 2 == 0);
+        }
+    }
+}
@@ -1,19 +1,34 @@
 mod chapter_info;
 mod comic;
 mod comic_info;
+mod connection_status;
+mod diagnostics;
 mod download_format;
+mod download_size_estimate;
+mod download_status;
+mod downloaded_comic_stats;
+mod downloaded_comic_summary;
 mod get_favorite_ordering;
 mod get_favorite_result;
 mod log_level;
+mod overall_progress;
 mod search_result;
 
 pub use chapter_info::*;
 pub use comic::*;
 pub use comic_info::*;
+pub use connection_status::*;
+pub use diagnostics::*;
 pub use download_format::*;
+pub use download_size_estimate::*;
+pub use download_status::*;
+pub use downloaded_comic_stats::*;
+pub use downloaded_comic_summary::*;
 pub use get_favorite_ordering::*;
 pub use get_favorite_result::*;
 pub use log_level::*;
+pub use overall_progress::*;
 pub use search_result::*;
 
 pub type AsyncRwLock<T> = tokio::sync::RwLock<T>;
+pub type AsyncMutex<T> = tokio::sync::Mutex<T>;
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// 所有下载任务的总体进度快照
+#[derive(Default, Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct OverallProgress {
+    pub downloaded_img_count: u64,
+    pub total_img_count: u64,
+    pub pending_count: u32,
+    pub downloading_count: u32,
+    pub paused_count: u32,
+    pub cancelled_count: u32,
+    pub completed_count: u32,
+    pub failed_count: u32,
+    pub banned_count: u32,
+}
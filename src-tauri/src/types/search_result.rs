@@ -10,7 +10,9 @@ use specta::Type;
 use tauri::AppHandle;
 
 use crate::{
+    extensions::{AnyhowErrorToStringChain, AppHandleExt},
     responses::{AuthorRespData, ComicInSearchRespData, Pagination, SearchRespData},
+    types::DownloadStatus,
     utils,
 };
 
@@ -42,12 +44,20 @@ impl SearchResult {
 
         let path_word_to_dir_map =
             utils::create_path_word_to_dir_map(app).context("创建漫画路径词到下载目录映射失败")?;
-        let mut list = Vec::with_capacity(resp_data.list.len());
-
-        for comic in resp_data.0.list {
-            let comic = ComicInSearch::from_resp_data(&comic, &path_word_to_dir_map);
-            list.push(comic);
-        }
+        let (compute_download_status, concurrency) = {
+            let config = app.get_config();
+            let config = config.read();
+            (
+                config.compute_search_download_status,
+                config.download_status_concurrency,
+            )
+        };
+        let list = resolve_search_comics(
+            resp_data.0.list,
+            &path_word_to_dir_map,
+            compute_download_status,
+            concurrency,
+        )?;
 
         let search_result = SearchResult(Pagination {
             list,
@@ -60,6 +70,21 @@ impl SearchResult {
     }
 }
 
+/// 在大小为`concurrency`的线程池中计算每个搜索结果的下载状态，结果保持原来的顺序
+///
+/// 开启`compute_download_status`时每个漫画都要读取元数据和遍历目录，并行计算的收益最明显
+fn resolve_search_comics(
+    comics: Vec<ComicInSearchRespData>,
+    path_word_to_dir_map: &HashMap<String, Vec<PathBuf>>,
+    compute_download_status: bool,
+    concurrency: usize,
+) -> anyhow::Result<Vec<ComicInSearch>> {
+    utils::par_map_in_pool(comics, concurrency, |comic| {
+        ComicInSearch::from_resp_data(&comic, path_word_to_dir_map, compute_download_status)
+    })
+    .context("计算搜索结果的下载状态失败")
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 #[serde(rename_all = "camelCase")]
 pub struct ComicInSearch {
@@ -72,12 +97,15 @@ pub struct ComicInSearch {
     pub popular: i64,
     pub is_downloaded: bool,
     pub comic_download_dir: PathBuf,
+    /// 只有开启`config.compute_search_download_status`时才会计算，否则为`None`，此时只能用`is_downloaded`判断
+    pub download_status: Option<DownloadStatus>,
 }
 
 impl ComicInSearch {
     pub fn from_resp_data(
         resp_data: &ComicInSearchRespData,
         path_word_to_dir_map: &HashMap<String, Vec<PathBuf>>,
+        compute_download_status: bool,
     ) -> Self {
         let mut comic = ComicInSearch {
             name: resp_data.name.clone(),
@@ -89,19 +117,124 @@ impl ComicInSearch {
             popular: resp_data.popular,
             is_downloaded: false,
             comic_download_dir: PathBuf::new(),
+            download_status: None,
         };
 
-        comic.update_fields(path_word_to_dir_map);
+        comic.update_fields(path_word_to_dir_map, compute_download_status);
 
         comic
     }
 
-    pub fn update_fields(&mut self, path_word_to_dir_map: &HashMap<String, Vec<PathBuf>>) {
+    /// `compute_download_status`为`true`时会读取漫画元数据来计算`download_status`，比较慢
+    pub fn update_fields(
+        &mut self,
+        path_word_to_dir_map: &HashMap<String, Vec<PathBuf>>,
+        compute_download_status: bool,
+    ) {
         if let Some(comic_download_dirs) = path_word_to_dir_map.get(&self.path_word) {
             if let Some(first_dir) = comic_download_dirs.first() {
                 self.comic_download_dir = first_dir.clone();
                 self.is_downloaded = true;
             }
         }
+
+        if !compute_download_status {
+            self.download_status = None;
+            return;
+        }
+
+        if !self.is_downloaded {
+            self.download_status = Some(DownloadStatus::NotDownloaded);
+            return;
+        }
+
+        self.download_status =
+            match DownloadStatus::from_comic_download_dir(&self.comic_download_dir) {
+                Ok(download_status) => Some(download_status),
+                Err(err) => {
+                    let err_title = format!("`{}`计算下载状态失败", self.name);
+                    let string_chain = err.to_string_chain();
+                    tracing::error!(err_title, message = string_chain);
+                    None
+                }
+            };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Instant};
+
+    use super::*;
+    use crate::test_utils::create_test_dir;
+
+    /// 在`download_dir`中创建`count`个漫画，每个漫画有20个章节，其中偶数下标的漫画只下载了一半
+    fn create_search_page(
+        download_dir: &Path,
+        count: usize,
+    ) -> (Vec<ComicInSearchRespData>, HashMap<String, Vec<PathBuf>>) {
+        const CHAPTER_COUNT: usize = 20;
+
+        let mut path_word_to_dir_map = HashMap::new();
+        let comics = (0..count)
+            .map(|i| {
+                let path_word = format!("comic{i}");
+                let comic_download_dir = download_dir.join(format!("漫画{i}"));
+                let chapters = vec![serde_json::json!({}); CHAPTER_COUNT];
+                let metadata =
+                    serde_json::json!({ "comic": { "groups": { "default": chapters } } });
+                std::fs::create_dir_all(&comic_download_dir).unwrap();
+                std::fs::write(comic_download_dir.join("元数据.json"), metadata.to_string())
+                    .unwrap();
+
+                let downloaded_count = if i % 2 == 0 {
+                    CHAPTER_COUNT / 2
+                } else {
+                    CHAPTER_COUNT
+                };
+                for j in 0..downloaded_count {
+                    let chapter_download_dir = comic_download_dir.join(format!("第{j}话"));
+                    std::fs::create_dir_all(&chapter_download_dir).unwrap();
+                    std::fs::write(chapter_download_dir.join("章节元数据.json"), "{}").unwrap();
+                }
+
+                path_word_to_dir_map.insert(path_word.clone(), vec![comic_download_dir]);
+                ComicInSearchRespData {
+                    name: format!("漫画{i}"),
+                    path_word,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        (comics, path_word_to_dir_map)
+    }
+
+    #[test]
+    fn search_page_resolved_in_parallel_matches_sequential() {
+        let download_dir = create_test_dir();
+        let (comics, path_word_to_dir_map) = create_search_page(&download_dir, 50);
+
+        let start = Instant::now();
+        let sequential =
+            resolve_search_comics(comics.clone(), &path_word_to_dir_map, true, 1).unwrap();
+        let sequential_elapsed = start.elapsed();
+
+        let start = Instant::now();
+        let parallel = resolve_search_comics(comics, &path_word_to_dir_map, true, 8).unwrap();
+        let parallel_elapsed = start.elapsed();
+        let _ = std::fs::remove_dir_all(&download_dir);
+
+        println!("50个搜索结果 串行: {sequential_elapsed:?} 8线程: {parallel_elapsed:?}");
+        assert_eq!(sequential, parallel);
+        assert_eq!(parallel.len(), 50);
+        for (i, comic) in parallel.iter().enumerate() {
+            assert_eq!(comic.name, format!("漫画{i}"));
+            let expected_status = if i % 2 == 0 {
+                DownloadStatus::Partial
+            } else {
+                DownloadStatus::Complete
+            };
+            assert_eq!(comic.download_status, Some(expected_status));
+        }
     }
 }
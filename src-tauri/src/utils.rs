@@ -1,31 +1,178 @@
-use std::{collections::HashMap, io::Cursor, path::PathBuf};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    io::Cursor,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use image::ImageReader;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use tauri::AppHandle;
+use tauri_specta::Event;
 use walkdir::WalkDir;
 
 use crate::{
+    config::FilenameSanitizeMode,
+    events::{GetComicProgressEvent, LibraryScanEvent},
     extensions::{AppHandleExt, WalkDirEntryExt},
-    types::Comic,
+    types::{Comic, DownloadedComicStats, FavoriteItem, GetFavoriteOrdering, GetFavoriteResult},
 };
 
-pub fn filename_filter(s: &str) -> String {
-    s.chars()
-        .map(|c| match c {
-            '\\' | '/' => ' ',
-            ':' => '：',
-            '*' => '⭐',
-            '?' => '？',
-            '"' => '\'',
-            '<' => '《',
-            '>' => '》',
-            '|' => '丨',
-            _ => c,
+/// Windows的保留设备名，不区分大小写，带扩展名(如`con.txt`)也不能用作文件名
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+pub fn filename_filter(s: &str, mode: FilenameSanitizeMode) -> String {
+    let filtered = s
+        .chars()
+        .filter_map(|c| {
+            let is_illegal = matches!(c, '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|');
+            match mode {
+                FilenameSanitizeMode::FullWidthSubstitute => Some(match c {
+                    '\\' | '/' => ' ',
+                    ':' => '：',
+                    '*' => '⭐',
+                    '?' => '？',
+                    '"' => '\'',
+                    '<' => '《',
+                    '>' => '》',
+                    '|' => '丨',
+                    _ => c,
+                }),
+                FilenameSanitizeMode::Remove => (!is_illegal).then_some(c),
+                FilenameSanitizeMode::Underscore => Some(if is_illegal { '_' } else { c }),
+            }
         })
-        .collect::<String>()
-        .trim()
-        .to_string()
+        .collect::<String>();
+    // Windows会自动删掉末尾的`.`和空格
+    let filtered = filtered.trim().trim_end_matches(['.', ' ']);
+    avoid_windows_reserved_name(filtered)
+}
+
+/// 如果`name`(不含扩展名)是Windows的保留设备名，则在其后加上`_`，如`CON`变为`CON_`，`con.txt`变为`con_.txt`
+fn avoid_windows_reserved_name(name: &str) -> String {
+    let stem = name.split('.').next().unwrap_or_default();
+    let is_reserved = WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved_name| stem.trim_end().eq_ignore_ascii_case(reserved_name));
+    if !is_reserved {
+        return name.to_string();
+    }
+    let stem = stem.trim_end();
+    let rest = &name[stem.len()..];
+    format!("{stem}_{rest}")
+}
+
+/// 确保`path`位于`allowed_dirs`中的某个目录之下
+///
+/// 会先将路径规范化(解析`..`和符号链接)，避免通过`../`之类的路径绕过检查
+pub fn ensure_path_in_dirs(path: &Path, allowed_dirs: &[&Path]) -> anyhow::Result<()> {
+    let canonical_path = path
+        .canonicalize()
+        .context(format!("规范化路径`{}`失败", path.display()))?;
+
+    for allowed_dir in allowed_dirs {
+        let Ok(canonical_allowed_dir) = allowed_dir.canonicalize() else {
+            continue;
+        };
+        if canonical_path.starts_with(&canonical_allowed_dir) {
+            return Ok(());
+        }
+    }
+
+    let allowed_dirs_string = allowed_dirs
+        .iter()
+        .map(|dir| format!("`{}`", dir.display()))
+        .collect::<Vec<String>>()
+        .join(", ");
+    Err(anyhow!(
+        "`{}`不在允许的目录[{allowed_dirs_string}]中",
+        path.display()
+    ))
+}
+
+/// 删除目录`dir`，`dir`必须位于`download_dir`之下，且不能是`download_dir`本身
+pub fn remove_dir_in_download_dir(app: &AppHandle, dir: &Path) -> anyhow::Result<()> {
+    let download_dir = app.get_config().read().download_dir.clone();
+    ensure_path_in_dirs(dir, &[&download_dir])?;
+    if dir.canonicalize().ok() == download_dir.canonicalize().ok() {
+        return Err(anyhow!("不能删除下载目录`{}`本身", download_dir.display()));
+    }
+
+    std::fs::remove_dir_all(dir).context(format!("删除目录`{}`失败", dir.display()))?;
+    invalidate_library_cache(app);
+    Ok(())
+}
+
+/// 先写入同目录下的临时文件，再重命名为`path`
+///
+/// 同一文件系统内的重命名是原子的，所以`path`要么是旧内容，要么是完整的新内容，
+/// 多个线程同时写同一个文件时也不会出现内容交错
+pub fn write_atomically(path: &Path, contents: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    write_atomically_with(path, |temp_path| {
+        std::fs::write(temp_path, contents)
+            .context(format!("写入临时文件`{}`失败", temp_path.display()))
+    })
+}
+
+/// 与`write_atomically`相同，但由`write`负责把内容写入传给它的临时文件路径
+///
+/// `write`返回错误时删除临时文件，`path`保持不变，中断或失败的写入不会留下不完整的`path`
+pub fn write_atomically_with(
+    path: &Path,
+    write: impl FnOnce(&Path) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let parent = path
+        .parent()
+        .context(format!("`{}`没有父目录", path.display()))?;
+    let filename = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context(format!("获取`{}`的文件名失败", path.display()))?;
+    // 每次写入都用不同的临时文件，避免并发写入时互相覆盖
+    let temp_path = parent.join(format!(".{filename}.{}.tmp", uuid::Uuid::new_v4()));
+
+    if let Err(err) = write(&temp_path) {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(err);
+    }
+    if let Err(err) = std::fs::rename(&temp_path, path) {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(anyhow!(err).context(format!(
+            "将`{}`重命名为`{}`失败",
+            temp_path.display(),
+            path.display()
+        )));
+    }
+
+    Ok(())
+}
+
+/// 按文件名开头的整数比较，使`2.webp`排在`10.webp`前面
+///
+/// 开头的整数相同或有一方没有数字时，按字典序比较
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (a_digits, b_digits) = (leading_digits(a), leading_digits(b));
+    if a_digits.is_empty() || b_digits.is_empty() {
+        return a.cmp(b);
+    }
+    // 去掉前导零后先比较长度再按字典序比较，等价于按数值比较，且不会溢出
+    let a_num = a_digits.trim_start_matches('0');
+    let b_num = b_digits.trim_start_matches('0');
+    a_num
+        .len()
+        .cmp(&b_num.len())
+        .then_with(|| a_num.cmp(b_num))
+        .then_with(|| a.cmp(b))
+}
+
+fn leading_digits(s: &str) -> &str {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    &s[..end]
 }
 
 pub fn get_dimensions(img_data: &[u8]) -> anyhow::Result<(u32, u32)> {
@@ -34,25 +181,129 @@ pub fn get_dimensions(img_data: &[u8]) -> anyhow::Result<(u32, u32)> {
     Ok(dimensions)
 }
 
-pub fn create_path_word_to_dir_map(app: &AppHandle) -> anyhow::Result<HashMap<String, Vec<PathBuf>>> {
+/// 优先使用`LibraryCache`中缓存的映射，缓存失效时才重新扫描下载目录
+pub fn create_path_word_to_dir_map(
+    app: &AppHandle,
+) -> anyhow::Result<HashMap<String, Vec<PathBuf>>> {
+    app.get_library_cache().get_path_word_to_dir_map(app)
+}
+
+/// 在大小为`concurrency`的线程池中对`items`的每一项调用`f`，返回的结果保持`items`原来的顺序
+///
+/// 用单独的线程池而不是全局线程池，避免和导出抢线程，`concurrency`不大于1时直接在当前线程中执行
+pub fn par_map_in_pool<T, U, F>(items: Vec<T>, concurrency: usize, f: F) -> anyhow::Result<Vec<U>>
+where
+    T: Send,
+    U: Send,
+    F: Fn(T) -> U + Sync + Send,
+{
+    if concurrency <= 1 {
+        return Ok(items.into_iter().map(f).collect());
+    }
+
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(concurrency)
+        .build()
+        .context("rayon线程池创建失败")?;
+
+    Ok(thread_pool.install(|| items.into_par_iter().map(f).collect()))
+}
+
+/// 下载完成、删除或移动漫画目录后调用，下次获取映射时会重新扫描下载目录
+pub fn invalidate_library_cache(app: &AppHandle) {
+    app.get_library_cache().invalidate();
+}
+
+/// 扫描下载目录，创建`path_word`到漫画下载目录的映射，不经过缓存
+pub fn scan_path_word_to_dir_map(app: &AppHandle) -> anyhow::Result<HashMap<String, Vec<PathBuf>>> {
     let mut path_word_to_dir_map: HashMap<String, Vec<PathBuf>> = HashMap::new();
     let download_dir = app.get_config().read().download_dir.clone();
     
     // 基础下载目录。因为现在分类文件夹是在漫画文件夹下面的，所以只需要扫描基础下载目录即可
     if download_dir.exists() {
-        collect_comic_dirs(&download_dir, &mut path_word_to_dir_map)?;
+        collect_comic_dirs(app, &download_dir, &mut path_word_to_dir_map)?;
     }
 
     Ok(path_word_to_dir_map)
 }
 
+/// 列出下载目录中的所有漫画及其统计信息，按漫画名排序
+///
+/// 不使用`create_path_word_to_dir_map`，因为它遇到损坏的`元数据.json`会直接报错，
+/// 而这里需要把元数据损坏的漫画也列出来，并在`err_message`中说明原因
+pub fn list_downloaded_comics(app: &AppHandle) -> anyhow::Result<Vec<DownloadedComicStats>> {
+    let download_dir = app.get_config().read().download_dir.clone();
+    if !download_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut comic_download_dirs = Vec::new();
+    for entry in WalkDir::new(&download_dir) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) if err.depth() == 0 => {
+                let err = anyhow::Error::from(err);
+                return Err(err.context(format!("读取下载目录`{}`失败", download_dir.display())));
+            }
+            Err(_) => continue,
+        };
+        if !entry.is_comic_metadata() {
+            continue;
+        }
+        if let Some(comic_download_dir) = entry.path().parent() {
+            comic_download_dirs.push(comic_download_dir.to_path_buf());
+        }
+    }
+
+    let mut stats_list: Vec<DownloadedComicStats> = comic_download_dirs
+        .into_iter()
+        .map(DownloadedComicStats::from_comic_download_dir)
+        .collect();
+    stats_list.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(stats_list)
+}
+
+/// 扫描`root_dir`中的所有漫画元数据，每扫描完`root_dir`下的一个条目就发送一次`LibraryScanEvent`
+///
+/// 下载章节时的临时目录中不会有漫画元数据，直接跳过
+#[allow(clippy::cast_possible_truncation)]
 fn collect_comic_dirs(
-    root_dir: &std::path::Path,
+    app: &AppHandle,
+    root_dir: &Path,
     map: &mut HashMap<String, Vec<PathBuf>>,
 ) -> anyhow::Result<()> {
-    for entry in WalkDir::new(root_dir)
+    let top_level_paths: Vec<PathBuf> = WalkDir::new(root_dir)
+        .min_depth(1)
+        .max_depth(1)
         .into_iter()
         .filter_map(Result::ok)
+        .filter(|entry| !entry.is_temp_download_dir())
+        .map(|entry| entry.path().to_path_buf())
+        .collect();
+    let total = top_level_paths.len() as u32;
+    let _ = LibraryScanEvent { scanned: 0, total }.emit(app);
+
+    for (i, top_level_path) in top_level_paths.into_iter().enumerate() {
+        collect_comic_dirs_in(&top_level_path, map)?;
+        let _ = LibraryScanEvent {
+            scanned: (i + 1) as u32,
+            total,
+        }
+        .emit(app);
+    }
+
+    Ok(())
+}
+
+fn collect_comic_dirs_in(
+    dir: &Path,
+    map: &mut HashMap<String, Vec<PathBuf>>,
+) -> anyhow::Result<()> {
+    for entry in WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| !entry.is_temp_download_dir())
+        .filter_map(Result::ok)
     {
         let path = entry.path();
         if !entry.is_comic_metadata() {
@@ -83,6 +334,17 @@ pub async fn get_comic(app: AppHandle, comic_path_word: &str) -> anyhow::Result<
     let copy_client = app.get_copy_client();
 
     let get_comic_resp_data = copy_client.get_comic(comic_path_word).await?;
+    #[allow(clippy::cast_possible_truncation)]
+    let total = get_comic_resp_data.groups.len() as u32;
+    let emit_progress_event = |done: u32| {
+        let _ = GetComicProgressEvent {
+            comic_path_word: comic_path_word.to_string(),
+            done,
+            total,
+        }
+        .emit(&app);
+    };
+    emit_progress_event(0);
     // TODO: 这里可以并发获取groups_chapters
     let mut groups_chapters = HashMap::new();
     for group_path_word in get_comic_resp_data.groups.keys() {
@@ -90,8 +352,172 @@ pub async fn get_comic(app: AppHandle, comic_path_word: &str) -> anyhow::Result<
             .get_group_chapters(comic_path_word, group_path_word)
             .await?;
         groups_chapters.insert(group_path_word.clone(), chapters);
+        #[allow(clippy::cast_possible_truncation)]
+        emit_progress_event(groups_chapters.len() as u32);
     }
     let comic = Comic::from_resp_data(&app, get_comic_resp_data, groups_chapters)?;
 
     Ok(comic)
 }
+
+/// 逐页获取收藏夹中的所有漫画，直到获取数量达到`total`或某一页为空
+///
+/// 每获取完一页休息1秒，避免触发风控
+pub async fn get_all_favorites(app: &AppHandle) -> anyhow::Result<Vec<FavoriteItem>> {
+    let copy_client = app.get_copy_client();
+
+    let mut favorites = Vec::new();
+    let mut page_num = 1;
+    loop {
+        let get_favorite_resp_data = copy_client
+            .get_favorite(page_num, GetFavoriteOrdering::Added)
+            .await
+            .map_err(anyhow::Error::from)
+            .context(format!("获取收藏夹第{page_num}页失败"))?;
+        let mut get_favorite_result =
+            GetFavoriteResult::from_resp_data(app, get_favorite_resp_data)
+                .context(format!("处理收藏夹第{page_num}页失败"))?;
+
+        let total = get_favorite_result.total;
+        let page = std::mem::take(&mut get_favorite_result.list);
+        if page.is_empty() {
+            break;
+        }
+        favorites.extend(page);
+        #[allow(clippy::cast_possible_wrap)]
+        let fetched = favorites.len() as i64;
+        if fetched >= total {
+            break;
+        }
+
+        page_num += 1;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    Ok(favorites)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_test_dir;
+
+    #[test]
+    fn reserved_names_get_underscore_suffix() {
+        let mode = FilenameSanitizeMode::FullWidthSubstitute;
+        assert_eq!(filename_filter("CON", mode), "CON_");
+        assert_eq!(filename_filter("con.txt", mode), "con_.txt");
+        assert_eq!(filename_filter("NUL ", mode), "NUL_");
+        assert_eq!(filename_filter("PRN.", mode), "PRN_");
+        assert_eq!(filename_filter("COM1.", mode), "COM1_");
+    }
+
+    #[test]
+    fn non_reserved_names_are_kept() {
+        let mode = FilenameSanitizeMode::FullWidthSubstitute;
+        assert_eq!(filename_filter("CONSOLE", mode), "CONSOLE");
+        assert_eq!(filename_filter("COM10", mode), "COM10");
+        assert_eq!(filename_filter("第1话 con", mode), "第1话 con");
+    }
+
+    const ALL_ILLEGAL_CHARS: &str = r#"a\b/c:d*e?f"g<h>i|j"#;
+
+    #[test]
+    fn full_width_substitute_mode() {
+        let mode = FilenameSanitizeMode::FullWidthSubstitute;
+        assert_eq!(
+            filename_filter(ALL_ILLEGAL_CHARS, mode),
+            "a b c：d⭐e？f'g《h》i丨j"
+        );
+    }
+
+    #[test]
+    fn remove_mode() {
+        let mode = FilenameSanitizeMode::Remove;
+        assert_eq!(filename_filter(ALL_ILLEGAL_CHARS, mode), "abcdefghij");
+    }
+
+    #[test]
+    fn underscore_mode() {
+        let mode = FilenameSanitizeMode::Underscore;
+        assert_eq!(
+            filename_filter(ALL_ILLEGAL_CHARS, mode),
+            "a_b_c_d_e_f_g_h_i_j"
+        );
+    }
+
+    #[test]
+    fn natural_cmp_sorts_by_leading_number() {
+        let mut names = ["100.webp", "11.webp", "2.webp", "10.webp", "1.webp"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            ["1.webp", "2.webp", "10.webp", "11.webp", "100.webp"]
+        );
+
+        assert!(natural_cmp("002_b", "010_a").is_lt());
+        assert!(natural_cmp("2_b", "010_a").is_lt());
+        assert!(natural_cmp("001_b", "001_c").is_lt());
+    }
+
+    #[test]
+    fn natural_cmp_falls_back_to_lexicographic_without_leading_number() {
+        let mut names = ["cover", "10", "a2", "2", "a10"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["2", "10", "a10", "a2", "cover"]);
+    }
+
+    fn dir_entry_names(dir: &Path) -> Vec<String> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn write_atomically_replaces_garbage_metadata() {
+        let dir = create_test_dir();
+        let metadata_path = dir.join("元数据.json");
+        std::fs::write(&metadata_path, b"\0\xff{\"name\": \"trunc").unwrap();
+
+        let comic_json = r#"{"name": "漫画"}"#;
+        write_atomically(&metadata_path, comic_json).unwrap();
+
+        let written = std::fs::read_to_string(&metadata_path).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(written, comic_json);
+        serde_json::from_str::<serde_json::Value>(&written).unwrap();
+    }
+
+    #[test]
+    fn write_atomically_leaves_no_temp_file() {
+        let dir = create_test_dir();
+        let metadata_path = dir.join("元数据.json");
+        std::fs::write(&metadata_path, "garbage").unwrap();
+
+        write_atomically(&metadata_path, "{}").unwrap();
+
+        let names = dir_entry_names(&dir);
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(names, vec!["元数据.json".to_string()]);
+    }
+
+    #[test]
+    fn failed_write_keeps_original_file() {
+        let dir = create_test_dir();
+        let metadata_path = dir.join("元数据.json");
+        std::fs::write(&metadata_path, "original").unwrap();
+
+        let result = write_atomically_with(&metadata_path, |temp_path| {
+            std::fs::write(temp_path, "partial").unwrap();
+            Err(anyhow!("写入中断"))
+        });
+
+        let content = std::fs::read_to_string(&metadata_path).unwrap();
+        let names = dir_entry_names(&dir);
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(result.is_err());
+        assert_eq!(content, "original");
+        assert_eq!(names, vec!["元数据.json".to_string()]);
+    }
+}
@@ -0,0 +1,162 @@
+//! 导出cbz时的内存占用测试
+//!
+//! 需要替换全局分配器来统计内存占用，所以单独放在一个集成测试里，不影响其他测试
+
+#[path = "../src/test_utils.rs"]
+mod test_utils;
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    io::{Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use copymanga_downloader_lib::write_chapter_zip;
+use test_utils::create_test_dir;
+
+thread_local! {
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+    static PEAK_ALLOCATED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// 只统计当前线程的内存占用，避免并行运行的其他测试干扰结果
+struct ThreadTrackingAllocator;
+
+impl ThreadTrackingAllocator {
+    fn record_alloc(size: usize) {
+        let _ = ALLOCATED.try_with(|allocated| {
+            let current = allocated.get() + size;
+            allocated.set(current);
+            let _ = PEAK_ALLOCATED.try_with(|peak| peak.set(peak.get().max(current)));
+        });
+    }
+
+    fn record_dealloc(size: usize) {
+        let _ = ALLOCATED.try_with(|allocated| allocated.set(allocated.get().saturating_sub(size)));
+    }
+
+    /// 执行`f`，返回`f`的结果和执行期间当前线程内存占用的峰值增量
+    fn measure_peak_growth<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        let start = ALLOCATED.with(Cell::get);
+        PEAK_ALLOCATED.with(|peak| peak.set(start));
+        let result = f();
+        let peak = PEAK_ALLOCATED.with(Cell::get);
+        (result, peak.saturating_sub(start))
+    }
+}
+
+unsafe impl GlobalAlloc for ThreadTrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            Self::record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        Self::record_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            Self::record_dealloc(layout.size());
+            Self::record_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: ThreadTrackingAllocator = ThreadTrackingAllocator;
+
+/// 记录写入底层文件的次数和字节数，用来检查写入没有被拆成大量小块
+struct CountingWriter<W> {
+    inner: W,
+    write_count: usize,
+    written_bytes: usize,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.write_count += 1;
+        self.written_bytes += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Seek> Seek for CountingWriter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// 生成`len`字节的伪随机数据，压缩不了，写入压缩包的大小和原始大小相当
+fn incompressible_data(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state.to_le_bytes()[0]
+        })
+        .collect()
+}
+
+#[test]
+fn large_chapter_zip_memory_stays_bounded() {
+    const IMAGE_COUNT: usize = 300;
+    const IMAGE_SIZE: usize = 64 * 1024;
+    const MAX_PEAK_GROWTH: usize = 4 * 1024 * 1024;
+    const MIN_AVERAGE_WRITE_SIZE: usize = 4 * 1024;
+
+    let dir = create_test_dir();
+    let image_paths: Vec<PathBuf> = (0..IMAGE_COUNT)
+        .map(|i| {
+            let image_path = dir.join(format!("{:03}.jpg", i + 1));
+            std::fs::write(&image_path, incompressible_data(i as u64, IMAGE_SIZE)).unwrap();
+            image_path
+        })
+        .collect();
+    let zip_file = std::fs::File::create(dir.join("chapter.cbz")).unwrap();
+    let mut writer = CountingWriter {
+        inner: zip_file,
+        write_count: 0,
+        written_bytes: 0,
+    };
+
+    let (result, peak_growth) = ThreadTrackingAllocator::measure_peak_growth(|| {
+        write_chapter_zip(
+            &mut writer,
+            Some("<ComicInfo />"),
+            image_paths,
+            None,
+            false,
+            None,
+        )
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let filenames = result.unwrap();
+    assert_eq!(filenames.len(), IMAGE_COUNT);
+    // 总共写入了约20MB的图片，内存峰值不应该随之增长
+    assert!(
+        peak_growth < MAX_PEAK_GROWTH,
+        "导出时内存峰值增长了{peak_growth}字节"
+    );
+    assert!(writer.written_bytes > IMAGE_COUNT * IMAGE_SIZE);
+    let average_write_size = writer.written_bytes / writer.write_count;
+    assert!(
+        average_write_size >= MIN_AVERAGE_WRITE_SIZE,
+        "平均每次只写入了{average_write_size}字节"
+    );
+}
@@ -43,6 +43,14 @@ export const commands = {
             else return { status: "error", error: e as any };
         }
     },
+    async checkConnection(): Promise<Result<ConnectionStatus, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("check_connection") };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
     async search(keyword: string, pageNum: number): Promise<Result<SearchResult, CommandError>> {
         try {
             return { status: "ok", data: await TAURI_INVOKE("search", { keyword, pageNum }) };
@@ -59,6 +67,14 @@ export const commands = {
             else return { status: "error", error: e as any };
         }
     },
+    async getComicGroups(comicPathWord: string): Promise<Result<Group[], CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("get_comic_groups", { comicPathWord }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
     async getGroupChapters(comicPathWord: string, groupPathWord: string): Promise<Result<ChapterInGetChaptersRespData[], CommandError>> {
         try {
             return { status: "ok", data: await TAURI_INVOKE("get_group_chapters", { comicPathWord, groupPathWord }) };
@@ -75,6 +91,14 @@ export const commands = {
             else return { status: "error", error: e as any };
         }
     },
+    async estimateDownloadSize(comic: Comic, chapterUuids: string[], sampleImgCount: number): Promise<Result<DownloadSizeEstimate, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("estimate_download_size", { comic, chapterUuids, sampleImgCount }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
     async getFavorite(pageNum: number, ordering: GetFavoriteOrdering): Promise<Result<GetFavoriteResult, CommandError>> {
         try {
             return { status: "ok", data: await TAURI_INVOKE("get_favorite", { pageNum, ordering }) };
@@ -83,6 +107,17 @@ export const commands = {
             else return { status: "error", error: e as any };
         }
     },
+    /**
+     * 获取收藏夹中的所有漫画，用于批量下载收藏
+     */
+    async getAllFavorites(): Promise<Result<FavoriteItem[], CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("get_all_favorites") };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
     async createDownloadTask(comic: Comic, chapterUuid: string): Promise<Result<null, CommandError>> {
         try {
             return { status: "ok", data: await TAURI_INVOKE("create_download_task", { comic, chapterUuid }) };
@@ -91,6 +126,28 @@ export const commands = {
             else return { status: "error", error: e as any };
         }
     },
+    /**
+     * 为`order`最大的`n`个章节创建下载任务，已下载的章节会被跳过，返回创建了下载任务的章节ID
+     */
+    async downloadNewestChapters(comic: Comic, n: number): Promise<Result<string[], CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("download_newest_chapters", { comic, n }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 为分组中`order`最大的`n`个未下载章节创建下载任务，返回创建了下载任务的章节ID
+     */
+    async downloadLatestChaptersInGroup(comic: Comic, groupPathWord: string, n: number): Promise<Result<string[], CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("download_latest_chapters_in_group", { comic, groupPathWord, n }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
     async pauseDownloadTask(chapterUuid: string): Promise<Result<null, CommandError>> {
         try {
             return { status: "ok", data: await TAURI_INVOKE("pause_download_task", { chapterUuid }) };
@@ -115,6 +172,35 @@ export const commands = {
             else return { status: "error", error: e as any };
         }
     },
+    async clearFinishedDownloadTasks(): Promise<string[]> {
+        return await TAURI_INVOKE("clear_finished_download_tasks");
+    },
+    async getOverallDownloadProgress(): Promise<OverallProgress> {
+        return await TAURI_INVOKE("get_overall_download_progress");
+    },
+    async setDownloadPausedGlobal(paused: boolean): Promise<null> {
+        return await TAURI_INVOKE("set_download_paused_global", { paused });
+    },
+    async getRiskControlCount(): Promise<number> {
+        return await TAURI_INVOKE("get_risk_control_count");
+    },
+    async resetRiskControlCount(): Promise<null> {
+        return await TAURI_INVOKE("reset_risk_control_count");
+    },
+    async transcodeDownloadedComic(comic: Comic, targetFormat: DownloadFormat): Promise<Result<null, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("transcode_downloaded_comic", { comic, targetFormat }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    async cancelTranscode(comicUuid: string): Promise<null> {
+        return await TAURI_INVOKE("cancel_transcode", { comicUuid });
+    },
+    async getGroupDownloadCounts(comic: Comic): Promise<GroupDownloadCount[]> {
+        return await TAURI_INVOKE("get_group_download_counts", { comic });
+    },
     async saveMetadata(comic: Comic): Promise<Result<null, CommandError>> {
         try {
             return { status: "ok", data: await TAURI_INVOKE("save_metadata", { comic }) };
@@ -123,9 +209,152 @@ export const commands = {
             else return { status: "error", error: e as any };
         }
     },
+    async setChapterOrderOverride(comic: Comic, chapterUuid: string, order: number | null): Promise<Result<Comic, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("set_chapter_order_override", { comic, chapterUuid, order }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    async setPreferredExportArchive(comic: Comic, archive: Archive | null): Promise<Result<Comic, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("set_preferred_export_archive", { comic, archive }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    async migrateOldVersionChapters(comic: Comic, rename: boolean): Promise<Result<MigratedChapter[], CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("migrate_old_version_chapters", { comic, rename }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    async renameComicDownloadDir(comic: Comic): Promise<Result<Comic, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("rename_comic_download_dir", { comic }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 为手动整理过的目录按标题和序号匹配章节，并写入缺失的章节元数据
+     */
+    async repairComicMetadata(comic: Comic): Promise<Result<Comic, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("repair_comic_metadata", { comic }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 删除已下载的章节目录
+     */
+    async deleteChapterDownload(chapterInfo: ChapterInfo): Promise<Result<null, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("delete_chapter_download", { chapterInfo }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 删除漫画中已下载的章节，返回更新了`is_downloaded`字段的漫画
+     */
+    async deleteComicChapterDownload(comic: Comic, chapterUuid: string): Promise<Result<Comic, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("delete_comic_chapter_download", { comic, chapterUuid }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 取消漫画所有未结束的下载任务，然后删除整个漫画下载目录
+     */
+    async deleteComicDownload(comic: Comic): Promise<Result<null, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("delete_comic_download", { comic }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    async createComicDownloadDirs(comic: Comic): Promise<Result<ChapterDirCreateFailure[], CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("create_comic_download_dirs", { comic }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 找出`comic`中图片不完整的已下载章节，并为它们重新创建下载任务
+     *
+     * 返回成功创建了下载任务的章节
+     */
+    async redownloadIncompleteChapters(comic: Comic): Promise<Result<IncompleteChapter[], CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("redownload_incomplete_chapters", { comic }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    async findChapterDirsWithoutMetadata(comic: Comic): Promise<Result<ChapterDirWithoutMetadata[], CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("find_chapter_dirs_without_metadata", { comic }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 重新获取漫画信息，为已存在的章节目录重新生成`章节元数据.json`，不会重新下载图片
+     */
+    async regenerateChapterMetadata(comic: Comic, chapterUuid: string): Promise<Result<null, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("regenerate_chapter_metadata", { comic, chapterUuid }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 重新获取漫画信息，返回已下载但远端已经不存在的章节
+     */
+    async getOrphanedChapters(comic: Comic): Promise<Result<ChapterInfo[], CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("get_orphaned_chapters", { comic }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
     async getDownloadedComics(): Promise<Comic[]> {
         return await TAURI_INVOKE("get_downloaded_comics");
     },
+    async searchDownloadedComics(query: string): Promise<Result<DownloadedComicSummary[], CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("search_downloaded_comics", { query }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    async listDownloadedComics(): Promise<Result<DownloadedComicStats[], CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("list_downloaded_comics") };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
     async exportCbz(comic: Comic): Promise<Result<null, CommandError>> {
         try {
             return { status: "ok", data: await TAURI_INVOKE("export_cbz", { comic }) };
@@ -142,6 +371,66 @@ export const commands = {
             else return { status: "error", error: e as any };
         }
     },
+    async exportComics(comics: Comic[], archive: Archive): Promise<Result<null, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("export_comics", { comics, archive }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 按照漫画偏好的导出格式导出，没有设置时使用`config.default_export_archive`
+     */
+    async exportComic(comic: Comic): Promise<Result<null, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("export_comic", { comic }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 批量导出多部漫画，每部漫画都按照其偏好的导出格式导出
+     */
+    async exportComicsByPreference(comics: Comic[]): Promise<Result<null, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("export_comics_by_preference", { comics }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 根据已下载的元数据和图片重新导出漫画，不需要重新下载
+     */
+    async reexportComic(comicPathWord: string, archive: Archive): Promise<Result<null, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("reexport_comic", { comicPathWord, archive }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    async exportCombinedPdf(comics: Comic[], title: string): Promise<Result<string, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("export_combined_pdf", { comics, title }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 将漫画所有章节的目录导出为json，保存在漫画的导出目录中，返回文件路径
+     */
+    async exportComicCatalog(comic: Comic): Promise<Result<string, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("export_comic_catalog", { comic }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
     async updateDownloadedComics(): Promise<Result<null, CommandError>> {
         try {
             return { status: "ok", data: await TAURI_INVOKE("update_downloaded_comics") };
@@ -150,6 +439,17 @@ export const commands = {
             else return { status: "error", error: e as any };
         }
     },
+    /**
+     * 为收藏夹中所有漫画的章节创建下载任务，`only_not_downloaded`为`true`时只下载未下载的章节
+     */
+    async downloadAllFavorites(onlyNotDownloaded: boolean): Promise<Result<null, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("download_all_favorites", { onlyNotDownloaded }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
     async getLogsDirSize(): Promise<Result<number, CommandError>> {
         try {
             return { status: "ok", data: await TAURI_INVOKE("get_logs_dir_size") };
@@ -158,9 +458,52 @@ export const commands = {
             else return { status: "error", error: e as any };
         }
     },
-    async showPathInFileManager(path: string): Promise<Result<null, CommandError>> {
+    /**
+     * 获取当前日志文件的最后`line_count`行，新的日志会通过`LogEvent`实时发送到前端
+     */
+    async getLogTail(lineCount: number): Promise<Result<string[], CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("get_log_tail", { lineCount }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    async getDiagnostics(): Promise<Result<Diagnostics, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("get_diagnostics") };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    async revealPath(path: string): Promise<Result<null, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("reveal_path", { path }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 在文件管理器中打开漫画的下载目录，返回该漫画的所有下载目录
+     *
+     * 同一个漫画有多个下载目录时(如修改过`comic_dir_fmt`)，只打开第一个
+     */
+    async revealComicDir(comicPathWord: string): Promise<Result<string[], CommandError>> {
         try {
-            return { status: "ok", data: await TAURI_INVOKE("show_path_in_file_manager", { path }) };
+            return { status: "ok", data: await TAURI_INVOKE("reveal_comic_dir", { comicPathWord }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
+    },
+    /**
+     * 在文件管理器中打开章节的下载目录
+     */
+    async revealChapterDir(comicPathWord: string, chapterUuid: string): Promise<Result<null, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("reveal_chapter_dir", { comicPathWord, chapterUuid }) };
         } catch (e) {
             if (e instanceof Error) throw e;
             else return { status: "error", error: e as any };
@@ -189,6 +532,17 @@ export const commands = {
             if (e instanceof Error) throw e;
             else return { status: "error", error: e as any };
         }
+    },
+    /**
+     * 返回`path_words`中每个漫画当前是否已下载，用于刷新前端已获取的搜索结果和收藏列表
+     */
+    async getDownloadedStatus(pathWords: string[]): Promise<Result<{ [key in string]: boolean }, CommandError>> {
+        try {
+            return { status: "ok", data: await TAURI_INVOKE("get_downloaded_status", { pathWords }) };
+        } catch (e) {
+            if (e instanceof Error) throw e;
+            else return { status: "error", error: e as any };
+        }
     }
 }
 
@@ -196,22 +550,40 @@ export const commands = {
 
 
 export const events = __makeEvents__<{
+    comicDownloadCompleteEvent: ComicDownloadCompleteEvent,
     downloadControlRiskEvent: DownloadControlRiskEvent,
+    downloadInsufficientSpaceEvent: DownloadInsufficientSpaceEvent,
     downloadSleepingEvent: DownloadSleepingEvent,
     downloadSpeedEvent: DownloadSpeedEvent,
     downloadTaskEvent: DownloadTaskEvent,
     exportCbzEvent: ExportCbzEvent,
     exportPdfEvent: ExportPdfEvent,
+    exportZipEvent: ExportZipEvent,
+    favoriteBatchEvent: FavoriteBatchEvent,
+    getComicProgressEvent: GetComicProgressEvent,
+    imgRateLimitedEvent: ImgRateLimitedEvent,
+    libraryScanEvent: LibraryScanEvent,
     logEvent: LogEvent,
+    riskControlCooldownEvent: RiskControlCooldownEvent,
+    transcodeEvent: TranscodeEvent,
     updateDownloadedComicsEvent: UpdateDownloadedComicsEvent
 }>({
+    comicDownloadCompleteEvent: "comic-download-complete-event",
     downloadControlRiskEvent: "download-control-risk-event",
+    downloadInsufficientSpaceEvent: "download-insufficient-space-event",
     downloadSleepingEvent: "download-sleeping-event",
     downloadSpeedEvent: "download-speed-event",
     downloadTaskEvent: "download-task-event",
     exportCbzEvent: "export-cbz-event",
     exportPdfEvent: "export-pdf-event",
+    exportZipEvent: "export-zip-event",
+    favoriteBatchEvent: "favorite-batch-event",
+    getComicProgressEvent: "get-comic-progress-event",
+    imgRateLimitedEvent: "img-rate-limited-event",
+    libraryScanEvent: "library-scan-event",
     logEvent: "log-event",
+    riskControlCooldownEvent: "risk-control-cooldown-event",
+    transcodeEvent: "transcode-event",
     updateDownloadedComicsEvent: "update-downloaded-comics-event"
 })
 
@@ -222,8 +594,27 @@ export const events = __makeEvents__<{
 /** user-defined types **/
 
 export type ApiDomainMode = "Default" | "Custom"
+export type Archive = "Cbz" | "Pdf" | "Zip"
 export type Author = { name: string; alias: string | null; path_word: string }
 export type AuthorRespData = { name: string; alias: string | null; path_word: string }
+/**
+ * 创建失败的章节目录
+ */
+export type ChapterDirCreateFailure = { chapterUuid: string; chapterTitle: string; chapterDownloadDir: string; errMessage: string }
+/**
+ * 有图片但没有有效章节元数据的目录
+ */
+export type ChapterDirWithoutMetadata = {
+    chapterDir: string; imgCount: number;
+    /**
+     * `章节元数据.json`存在但内容无效时为`true`
+     */
+    metadataExists: boolean;
+    /**
+     * 按照当前的fmt推测出的章节ID，推测不出时为`None`
+     */
+    chapterUuid: string | null
+}
 export type ChapterInGetChapterRespData = { index: number; uuid: string; count: number; ordered: number; size: number; name: string; comic_id: string; comic_path_word: string; group_id: string | null; group_path_word: string; type: number; news: string; datetime_created: string; prev: string | null; next: string | null; contents: ContentRespData[]; words: number[]; is_long: boolean }
 export type ChapterInGetChaptersRespData = { index: number; uuid: string; count: number; ordered: number; size: number; name: string; comic_id: string; comic_path_word: string; group_id: string | null; group_path_word: string; type: number; news: string; datetime_created: string; prev: string | null; next: string | null }
 export type ChapterInfo = {
@@ -249,7 +640,28 @@ export type ChapterInfo = {
      */
     chapterType: number; isDownloaded?: boolean | null; chapterDownloadDir?: string | null
 }
-export type Comic = { is_banned: boolean; is_lock: boolean; is_login: boolean; is_mobile_bind: boolean; is_vip: boolean; comic: ComicDetail; popular: number; groups: { [key in string]: Group }; isDownloaded?: boolean | null; comicDownloadDir?: string | null }
+/**
+ * 章节在排队后被删除或屏蔽(404)时的处理方式
+ *
+ * - `Skip`: 将任务标记为`Banned`，继续下载其他章节
+ * - `Fail`: 将任务标记为`Failed`，并记录错误日志
+ */
+export type ChapterUnavailableAction = "Skip" | "Fail"
+export type Comic = {
+    is_banned: boolean; is_lock: boolean; is_login: boolean; is_mobile_bind: boolean; is_vip: boolean; comic: ComicDetail; popular: number; groups: { [key in string]: Group }; isDownloaded?: boolean | null; comicDownloadDir?: string | null;
+    /**
+     * 漫画目录中的封面`cover.<ext>`，不保存到元数据中
+     */
+    coverPath?: string | null;
+    /**
+     * `chapter_uuid` -> 手动指定的`order`，用于修正API返回的错误顺序
+     */
+    orderOverrides: { [key in string]: number };
+    /**
+     * 这部漫画偏好的导出格式，为`None`时使用`config.default_export_archive`
+     */
+    preferredExportArchive: Archive | null
+}
 export type ComicDetail = {
     uuid: string; b_404: boolean; b_hidden: boolean; ban: number; ban_ip: boolean | null; name: string; alias: string | null; path_word: string; close_comment: boolean; close_roast: boolean; free_type: LabeledValue; restrict: LabeledValue; reclass: LabeledValue; seo_baidu: string | null; region: LabeledValue; status: LabeledValue; author: Author[]; theme: Theme[]; brief: string; datetime_updated: string; cover: string; last_chapter: LastChapter; popular: number;
     /**
@@ -257,27 +669,149 @@ export type ComicDetail = {
      */
     groups: { [key in string]: ChapterInfo[] }
 }
+/**
+ * 漫画这一批的章节全部下载完成时发送，有章节被取消、失败或屏蔽时不发送
+ */
+export type ComicDownloadCompleteEvent = { comicPathWord: string }
 export type ComicInFavorite = { uuid: string; bDisplay: boolean; name: string; pathWord: string; author: AuthorRespData[]; cover: string; status: number; popular: number; datetimeUpdated: string; lastChapterId: string; lastChapterName: string; isDownloaded: boolean; comicDownloadDir: string }
 export type ComicInGetChapterRespData = { name: string; uuid: string; path_word: string; restrict: RestrictRespData }
-export type ComicInSearch = { name: string; alias: string | null; pathWord: string; cover: string; ban: number; author: AuthorRespData[]; popular: number; isDownloaded: boolean; comicDownloadDir: string }
+export type ComicInSearch = {
+    name: string; alias: string | null; pathWord: string; cover: string; ban: number; author: AuthorRespData[]; popular: number; isDownloaded: boolean; comicDownloadDir: string;
+    /**
+     * 只有开启`config.compute_search_download_status`时才会计算，否则为`None`，此时只能用`is_downloaded`判断
+     */
+    downloadStatus: DownloadStatus | null
+}
+/**
+ * 单个漫画的下载设置，以`path_word`为key保存在`comic_overrides`中
+ *
+ * 为`None`的字段使用全局配置
+ */
+export type ComicOverride = { downloadFormat: DownloadFormat | null; chapterDirFmt: string | null; skipHdUpgradeForWebtoon: boolean | null }
 export type ComicStatus = "ongoing" | "completed"
 export type CommandError = { err_title: string; err_message: string }
-export type Config = {
-    token: string; downloadDir: string; exportDir: string; apiDomainMode: ApiDomainMode; customApiDomain: string; downloadFormat: DownloadFormat; enableFileLogger: boolean; chapterConcurrency: number; chapterDownloadIntervalSec: number; imgConcurrency: number; imgDownloadIntervalSec: number; updateDownloadedComicsIntervalSec: number; comicDirFmt: string; chapterDirFmt: string; createPdfConcurrency: number;
-    enableMergePdf: boolean;
-    separateChapterType: boolean;
+export type Config = { token: string; downloadDir: string; exportDir: string; apiDomainMode: ApiDomainMode; customApiDomain: string; downloadFormat: DownloadFormat; enableFileLogger: boolean; chapterConcurrency: number; chapterDownloadIntervalSec: number; imgConcurrency: number; imgDownloadIntervalSec: number; updateDownloadedComicsIntervalSec: number; comicDirFmt: string; chapterDirFmt: string; createPdfConcurrency: number; enableMergePdf: boolean; separateChapterType: boolean; exportFormatSubdir: boolean; exportFilenameFmt: string; cbzExportDir: string | null; pdfExportDir: string | null; exportConcurrency: number; exportStampPageNumbers: boolean; connectTimeoutSec: number; readTimeoutSec: number; verifyExports: boolean; skipHdUpgradeForWebtoon: boolean; proxyRules: ProxyRule[]; createProgressFile: boolean; exportNormalizeFormat: DownloadFormat | null; chapterUnavailableAction: ChapterUnavailableAction; saveUnrecognizedImgAsBin: boolean; exportUseDedicatedCover: boolean; orderFracWidth: number; orderDecimalSeparator: string; chapterTitleStripPattern: string | null; defaultExportArchive: Archive; incompleteChapterRequeueCount: number; userAgent: string; extraHeaders: { [key in string]: string }; filenameSanitizeMode: FilenameSanitizeMode; autoUpdateDownloadedComicsIntervalMin: number; imageNameFmt: string; fallbackApiDomains: string[]; logMaxSizeMb: number; logMaxFiles: number; enableDownloadEventLog: boolean; diskSpaceSafetyMarginMb: number; keepGifAsIs: boolean; comicOverrides: { [key in string]: ComicOverride }; riskControlCooldownSec: number; autoExportOnComplete: Archive | null; skipUnchangedExports: boolean; exportDirFmt: string; convertGifToAnimatedWebp: boolean; computeSearchDownloadStatus: boolean; downloadStatusConcurrency: number; downloadCover: boolean }
+/**
+ * - `Ok`: 域名可以访问，token有效
+ * - `DomainUnreachable`: 所有API域名都无法连接
+ * - `TokenInvalid`: 域名可以访问，但token错误、过期或未登录
+ * - `RiskControl`: 域名可以访问，但触发了风控
+ */
+export type ConnectionState = "Ok" | "DomainUnreachable" | "TokenInvalid" | "RiskControl"
+/**
+ * 连接测试的结果
+ */
+export type ConnectionStatus = {
+    state: ConnectionState;
+    /**
+     * 从发送请求到收到响应的耗时(毫秒)，无法连接时为`None`
+     */
+    latencyMs: number | null;
+    /**
+     * 无法连接时的错误信息
+     */
+    errMessage: string | null
 }
 export type ContentRespData = { url: string }
+/**
+ * 用于反馈问题的诊断信息，敏感字段已隐藏
+ */
+export type Diagnostics = { appVersion: string; os: string; arch: string; config: JsonValue; dirs: DirDiagnostics[]; downloadedComicCount: number; downloadedChapterCount: number }
+export type DirDiagnostics = {
+    name: string; path: string; exists: boolean;
+    /**
+     * 所在磁盘的剩余空间(字节)，目录不存在或获取失败时为`None`
+     */
+    availableSpace: number | null
+}
 export type DownloadControlRiskEvent = { chapterUuid: string; retryAfter: number }
-export type DownloadFormat = "Webp" | "Jpeg"
+/**
+ * `Jxl`无论是否启用`jxl`功能都存在，这样配置文件和前端的类型不随编译选项变化
+ *
+ * 未启用`jxl`功能时，保存或转换为`Jxl`会返回错误
+ */
+export type DownloadFormat = "Webp" | "Jpeg" | "Jxl"
+/**
+ * 下载章节前发现磁盘空间不足时发送，任务会被标记为`Failed`
+ */
+export type DownloadInsufficientSpaceEvent = { chapterUuid: string; availableBytes: number; requiredBytes: number }
+/**
+ * 漫画下载大小的估算结果，所有`estimated_`开头的字段都只是估算值
+ */
+export type DownloadSizeEstimate = {
+    chapterCount: number;
+    /**
+     * `chapter_size`缺失或为0的章节数，这些章节的页数按其他章节的平均页数估算
+     */
+    chaptersWithoutSize: number; estimatedPageCount: number;
+    /**
+     * 实际通过HEAD请求获取到大小的图片数
+     */
+    sampledImgCount: number; estimatedAvgImgBytes: number | null;
+    /**
+     * 采样失败或没有采样时为`None`
+     */
+    estimatedTotalBytes: number | null
+}
 export type DownloadSleepingEvent = { chapterUuid: string; remainingSec: number }
 export type DownloadSpeedEvent = { speed: string }
-export type DownloadTaskEvent = { event: "Create"; data: { state: DownloadTaskState; comic: Comic; chapterInfo: ChapterInfo; downloadedImgCount: number; totalImgCount: number } } | { event: "Update"; data: { chapterUuid: string; state: DownloadTaskState; downloadedImgCount: number; totalImgCount: number } }
-export type DownloadTaskState = "Pending" | "Downloading" | "Paused" | "Cancelled" | "Completed" | "Failed"
+/**
+ * 漫画的下载状态，通过比较已下载章节数与元数据中的章节总数得到
+ */
+export type DownloadStatus ="NotDownloaded" |
+    /**
+     * 只下载了部分章节
+     */
+    "Partial" | "Complete"
+export type DownloadTaskEvent ={ event: "Create"; data: { state: DownloadTaskState; comic: Comic; chapterInfo: ChapterInfo; downloadedImgCount: number; totalImgCount: number } } | { event: "Update"; data: { chapterUuid: string; state: DownloadTaskState; downloadedImgCount: number; totalImgCount: number } } |
+    /**
+     * 章节已完整保存到`chapter_download_dir`，每个下载任务最多发送一次
+     */
+    { event: "Finished"; data: { chapterUuid: string; chapterDownloadDir: string; imgCount: number } }
+export type DownloadTaskState ="Pending" | "Downloading" | "Paused" | "Cancelled" | "Completed" | "Failed" |
+    /**
+     * 章节已被删除或屏蔽
+     */
+    "Banned"
+/**
+ * 已下载漫画的统计信息，用于展示本地书库
+ */
+export type DownloadedComicStats = {
+    name: string; pathWord: string; comicDownloadDir: string; downloadedChapterCount: number; totalBytes: number;
+    /**
+     * 漫画目录中最新的文件修改时间(Unix时间戳，单位为秒)
+     */
+    lastModified: number;
+    /**
+     * `元数据.json`损坏时的错误信息，此时`name`为目录名，`path_word`为空
+     */
+    errMessage: string | null
+}
+/**
+ * 已下载漫画的简要信息，直接从元数据中读取，不需要解析完整的`Comic`
+ */
+export type DownloadedComicSummary = { uuid: string; pathWord: string; name: string; alias: string | null; cover: string; comicDownloadDir: string }
 export type ExportCbzEvent = { event: "Start"; data: { uuid: string; comicTitle: string; total: number } } | { event: "Progress"; data: { uuid: string; current: number } } | { event: "Error"; data: { uuid: string } } | { event: "End"; data: { uuid: string; chapterExportDir: string } }
 export type ExportPdfEvent = { event: "CreateStart"; data: { uuid: string; comicTitle: string; total: number } } | { event: "CreateProgress"; data: { uuid: string; current: number } } | { event: "CreateError"; data: { uuid: string } } | { event: "CreateEnd"; data: { uuid: string; chapterExportDir: string } } | { event: "MergeStart"; data: { uuid: string; comicTitle: string; total: number } } | { event: "MergeProgress"; data: { uuid: string; current: number } } | { event: "MergeError"; data: { uuid: string } } | { event: "MergeEnd"; data: { uuid: string; chapterExportDir: string } }
+export type ExportZipEvent = { event: "Start"; data: { uuid: string; comicTitle: string; total: number } } | { event: "Progress"; data: { uuid: string; current: number } } | { event: "Error"; data: { uuid: string } } | { event: "End"; data: { uuid: string; chapterExportDir: string } }
+/**
+ * 批量下载收藏时，每开始处理一部收藏的漫画就发送一次
+ */
+export type FavoriteBatchEvent = { current: number; total: number; comicName: string }
 export type FavoriteItem = { uuid: number; bFolder: boolean; comic: ComicInFavorite }
+/**
+ * 文件名中非法字符(`\/:*?"<>|`)的处理方式
+ *
+ * - `FullWidthSubstitute`: 替换为全角或相似的字符，如`:`替换为`：`
+ * - `Remove`: 直接删除
+ * - `Underscore`: 替换为`_`
+ */
+export type FilenameSanitizeMode = "FullWidthSubstitute" | "Remove" | "Underscore"
 export type GetChapterRespData = { is_banned: boolean; show_app: boolean; is_lock: boolean; is_login: boolean; is_mobile_bind: boolean; is_vip: boolean; comic: ComicInGetChapterRespData; chapter: ChapterInGetChapterRespData }
+/**
+ * 获取漫画时，每获取完一个分组的章节就发送一次
+ */
+export type GetComicProgressEvent = { comicPathWord: string; done: number; total: number }
 export type GetFavoriteOrdering =
     /**
      * 按加到书架时间降序排序
@@ -293,17 +827,85 @@ export type GetFavoriteOrdering =
     "Read"
 export type GetFavoriteResult = Pagination<FavoriteItem>
 export type Group = { path_word: string; count: number; name: string }
+/**
+ * 分组的章节数统计
+ */
+export type GroupDownloadCount = {
+    groupPathWord: string; groupName: string;
+    /**
+     * 服务器给出的章节数，分组信息中没有这个分组时为`None`
+     */
+    serverCount: number | null;
+    /**
+     * 章节列表中实际的章节数，可能与`server_count`不一致
+     */
+    chapterCount: number; downloadedCount: number
+}
+/**
+ * 下载图片时CDN返回429，开始等待`wait_sec`秒后重试时发送
+ */
+export type ImgRateLimitedEvent = { url: string; waitSec: number }
+/**
+ * 图片不完整的已下载章节
+ */
+export type IncompleteChapter = {
+    chapterUuid: string; chapterTitle: string; chapterSize: number; imgCount: number;
+    /**
+     * 大小为0的图片数
+     */
+    emptyImgCount: number
+}
 export type JsonValue = null | boolean | number | string | JsonValue[] | { [key in string]: JsonValue }
 export type LabeledValue = { value: number; display: string }
 export type LastChapter = { uuid: string; name: string }
+/**
+ * 扫描下载目录中的漫画元数据时，每扫描完下载目录下的一个条目就发送一次
+ */
+export type LibraryScanEvent = { scanned: number; total: number }
 export type LogEvent = { timestamp: string; level: LogLevel; fields: { [key in string]: JsonValue }; target: string; filename: string; line_number: number }
 export type LogLevel = "TRACE" | "DEBUG" | "INFO" | "WARN" | "ERROR"
 export type LoginRespData = { token: string; user_id: string; username: string; nickname: string; avatar: string; datetime_created: string; ticket: number; reward_ticket: number; downloads: number; vip_downloads: number; reward_downloads: number; scy_answer: boolean }
+/**
+ * 旧版本章节目录的迁移结果
+ */
+export type MigratedChapter = {
+    chapterUuid: string; chapterTitle: string; groupName: string; oldChapterDir: string;
+    /**
+     * 重命名后的目录，没有重命名则为`None`
+     */
+    newChapterDir: string | null;
+    /**
+     * 是否为此章节新创建了元数据
+     */
+    metadataCreated: boolean
+}
+/**
+ * 所有下载任务的总体进度快照
+ */
+export type OverallProgress = { downloadedImgCount: number; totalImgCount: number; pendingCount: number; downloadingCount: number; pausedCount: number; cancelledCount: number; completedCount: number; failedCount: number; bannedCount: number }
 export type Pagination<T> = { list: T[]; total: number; limit: number; offset: number }
+/**
+ * 按host选择代理的规则
+ *
+ * - `host_pattern`: 完整的host(如`api.2025copy.com`)，或`*.`开头的通配符(如`*.mangafunb.fun`，同时匹配根域名)
+ * - `proxy_url`: 代理地址(如`http://127.0.0.1:7890`)，为空表示直连
+ *
+ * 有多条规则匹配同一个host时，列表中靠前的规则优先
+ */
+export type ProxyRule = { hostPattern: string; proxyUrl: string }
 export type RestrictRespData = { value: number; display: string }
+/**
+ * 有任务触发注册风控时发送，冷却的`remaining_sec`秒内所有任务都暂停请求章节
+ */
+export type RiskControlCooldownEvent = { remainingSec: number }
 export type SearchResult = Pagination<ComicInSearch>
 export type Theme = { name: string; path_word: string }
-export type UpdateDownloadedComicsEvent = { event: "GetComicStart"; data: { total: number } } | { event: "GetComicProgress"; data: { current: number; total: number } } | { event: "CreateDownloadTasksStart"; data: { comicPathWord: string; comicTitle: string; current: number; total: number } } | { event: "CreateDownloadTaskProgress"; data: { comicPathWord: string; current: number } } | { event: "CreateDownloadTasksEnd"; data: { comicPathWord: string } } | { event: "GetComicEnd" }
+export type TranscodeEvent = { event: "Start"; data: { uuid: string; comicTitle: string; total: number } } | { event: "Progress"; data: { uuid: string; current: number } } | { event: "Cancelled"; data: { uuid: string } } | { event: "Error"; data: { uuid: string } } | { event: "End"; data: { uuid: string } }
+export type UpdateDownloadedComicsEvent ={ event: "GetComicStart"; data: { total: number } } | { event: "GetComicProgress"; data: { current: number; total: number } } | { event: "CreateDownloadTasksStart"; data: { comicPathWord: string; comicTitle: string; current: number; total: number } } | { event: "CreateDownloadTaskProgress"; data: { comicPathWord: string; current: number } } | { event: "CreateDownloadTasksEnd"; data: { comicPathWord: string } } |
+    /**
+     * 漫画标题 -> 新章节数，只包含有新章节的漫画
+     */
+    { event: "NewChaptersSummary"; data: { newChapterCounts: { [key in string]: number } } } | { event: "GetComicEnd" }
 export type UserProfileRespData = { user_id: string; username: string; nickname: string; avatar: string; datetime_created: string; ticket: number; reward_ticket: number; downloads: number; vip_downloads: number; reward_downloads: number; scy_answer: boolean; day_downloads_refresh: string; day_downloads: number }
 
 /** tauri-specta globals **/